@@ -0,0 +1,27 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+#[allow(dead_code)]
+#[path = "../src/days/day_15.rs"]
+mod day_15;
+
+use day_15::MemoryGame;
+
+/// The 30000000th number with every number in the table, and with every number in the map.
+fn bench_30000000th_number(c: &mut Criterion) {
+    let mut group = c.benchmark_group("day_15_30000000th_number");
+    group.sample_size(10);
+    for (name, dense_limit) in [("dense", 1 << 25), ("sparse", 0)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                MemoryGame::with_dense_limit(black_box(&[0, 3, 6]), dense_limit)
+                    .nth(30000000 - 1)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_30000000th_number);
+criterion_main!(benches);
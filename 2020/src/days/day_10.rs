@@ -1,9 +1,9 @@
-use std::{collections::HashMap, fs};
+use std::{collections::BTreeMap, fs, ops::Add};
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
     let adaptors = parse_adaptors(&input);
-    let ordered_joltages = ordered_joltages(&adaptors);
+    let ordered_joltages = ordered_joltages(&adaptors, MAX_GAP);
     let differences = differences(&ordered_joltages);
     (differences.0 * differences.1).to_string()
 }
@@ -18,67 +18,156 @@ fn get_input_file_contents() -> String {
     fs::read_to_string("inputs/input10").expect("Failed to open input file")
 }
 
-fn parse_adaptors(input: &str) -> Vec<u8> {
+/// The largest difference in joltage an adaptor can take.
+const MAX_GAP: u32 = 3;
+
+fn parse_adaptors(input: &str) -> Vec<u32> {
     input
         .lines()
-        .map(|line| line.parse::<u8>().unwrap())
+        .map(|line| line.parse::<u32>().unwrap())
         .collect()
 }
 
-fn ordered_joltages(adaptors: &[u8]) -> Vec<u8> {
+/// The joltages of the outlet, every adaptor in order, and the device.
+fn ordered_joltages(adaptors: &[u32], max_gap: u32) -> Vec<u32> {
     let mut sorted = adaptors.to_vec();
     sorted.push(0);
-    sorted.push(sorted.iter().max().unwrap() + 3);
+    sorted.push(sorted.iter().max().unwrap() + max_gap);
     sorted.sort_unstable();
     sorted
 }
 
-/// 1- and 3-jolt differences in the ordered list.
-fn differences(joltages: &[u8]) -> (usize, usize) {
-    let mut differences: HashMap<u8, usize> = HashMap::new();
+/// How many times each difference appears between consecutive joltages.
+fn difference_distribution(joltages: &[u32]) -> BTreeMap<u32, usize> {
+    let mut differences = BTreeMap::new();
     for (j1, j2) in joltages.iter().zip(joltages.iter().skip(1)) {
         *differences.entry(j2 - j1).or_default() += 1;
     }
+    differences
+}
+
+/// 1- and 3-jolt differences in the ordered list.
+fn differences(joltages: &[u32]) -> (usize, usize) {
+    let differences = difference_distribution(joltages);
     (
         *differences.get(&1).unwrap_or(&0),
         *differences.get(&3).unwrap_or(&0),
     )
 }
 
-fn total_arrangements(adaptors: &[u8]) -> usize {
-    let adaptors = {
-        let mut a = adaptors.to_vec();
-        a.push(0);
-        a.push(a.iter().max().unwrap() + 3);
-        a.sort_unstable();
-        a
-    };
-    arrangements(&adaptors, 0, &mut HashMap::new())
+/// For each joltage, the number of ways to chain adaptors from it to the device.
+///
+/// Any type that can count is fine, so the counts can be as big as needed.
+fn arrangement_counts<T>(joltages: &[u32], max_gap: u32) -> Vec<T>
+where
+    T: Clone + Add<Output = T> + From<u8>,
+{
+    let mut counts = vec![T::from(0); joltages.len()];
+    counts[joltages.len() - 1] = T::from(1);
+    for start in (0..joltages.len() - 1).rev() {
+        counts[start] = (start + 1..joltages.len())
+            .take_while(|next| joltages[*next] - joltages[start] <= max_gap)
+            .fold(T::from(0), |count, next| count + counts[next].clone());
+    }
+    counts
+}
+
+fn count_arrangements<T>(joltages: &[u32], max_gap: u32) -> T
+where
+    T: Clone + Add<Output = T> + From<u8>,
+{
+    arrangement_counts::<T>(joltages, max_gap)[0].clone()
+}
+
+fn total_arrangements(adaptors: &[u32]) -> u128 {
+    count_arrangements(&ordered_joltages(adaptors, MAX_GAP), MAX_GAP)
+}
+
+/// Every valid chain of joltages from the outlet to the device, in lexicographic order.
+#[allow(dead_code)]
+struct Chains<'a> {
+    joltages: &'a [u32],
+    max_gap: u32,
+    /// Indices of the joltages in the last chain, or `None` before the first chain.
+    path: Option<Vec<usize>>,
 }
 
-fn arrangements(adaptors: &[u8], start: usize, states: &mut HashMap<usize, usize>) -> usize {
-    if start == adaptors.len() - 1 {
-        return 1;
+#[allow(dead_code)]
+impl<'a> Chains<'a> {
+    fn new(joltages: &'a [u32], max_gap: u32) -> Self {
+        Self {
+            joltages,
+            max_gap,
+            path: None,
+        }
     }
 
-    let next_options = adaptors[start..]
-        .iter()
-        .enumerate()
-        .skip(1)
-        .filter(|(_, a)| **a <= adaptors[start] + 3)
-        .map(|(i, _)| start + i)
-        .collect::<Vec<usize>>();
-    let mut count = 0;
-    for next in next_options {
-        if let Some(next_count) = states.get(&next) {
-            count += next_count;
-        } else {
-            let next_count = arrangements(adaptors, next, states);
-            states.insert(next, next_count);
-            count += next_count;
+    /// Finish a chain by using every remaining adaptor, if there are no gaps too big to cross.
+    fn complete(&self, mut path: Vec<usize>) -> Option<Vec<usize>> {
+        let last = self.joltages.len() - 1;
+        let mut i = *path.last().unwrap();
+        while i < last {
+            if self.joltages[i + 1] - self.joltages[i] > self.max_gap {
+                return None;
+            }
+            i += 1;
+            path.push(i);
         }
+        Some(path)
     }
-    count
+}
+
+impl<'a> Iterator for Chains<'a> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Vec<u32>> {
+        let path = match self.path.take() {
+            None => self.complete(vec![0])?,
+            Some(mut path) => loop {
+                // Skip the adaptor after the last one we can change
+                let skipped = path.pop().unwrap();
+                let previous = *path.last()?;
+                let next = skipped + 1;
+                if next < self.joltages.len()
+                    && self.joltages[next] - self.joltages[previous] <= self.max_gap
+                {
+                    path.push(next);
+                    break self.complete(path)?;
+                }
+            },
+        };
+        let chain = path.iter().map(|i| self.joltages[*i]).collect();
+        self.path = Some(path);
+        Some(chain)
+    }
+}
+
+/// The chain at the given index in lexicographic order, so that a random index gives a
+/// uniformly random chain.
+#[allow(dead_code)]
+fn nth_chain(joltages: &[u32], max_gap: u32, mut index: u128) -> Option<Vec<u32>> {
+    let counts = arrangement_counts::<u128>(joltages, max_gap);
+    if index >= counts[0] {
+        return None;
+    }
+    let mut chain = vec![joltages[0]];
+    let mut i = 0;
+    while i < joltages.len() - 1 {
+        let next = (i + 1..joltages.len())
+            .take_while(|next| joltages[*next] - joltages[i] <= max_gap)
+            .find(|next| {
+                if index < counts[*next] {
+                    true
+                } else {
+                    index -= counts[*next];
+                    false
+                }
+            })
+            .unwrap();
+        chain.push(joltages[next]);
+        i = next;
+    }
+    Some(chain)
 }
 
 #[test]
@@ -93,7 +182,7 @@ fn test_ordered_differences() {
     {
         let input = "16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4\n";
         let adaptors = parse_adaptors(input);
-        let ordered_joltages = ordered_joltages(&adaptors);
+        let ordered_joltages = ordered_joltages(&adaptors, MAX_GAP);
         let differences = differences(&ordered_joltages);
         assert_eq!(differences.0, 7);
         assert_eq!(differences.1, 5);
@@ -102,7 +191,7 @@ fn test_ordered_differences() {
     {
         let input = "28\n33\n18\n42\n31\n14\n46\n20\n48\n47\n24\n23\n49\n45\n19\n38\n39\n11\n1\n32\n25\n35\n8\n17\n7\n9\n4\n2\n34\n10\n3\n";
         let adaptors = parse_adaptors(input);
-        let ordered_joltages = ordered_joltages(&adaptors);
+        let ordered_joltages = ordered_joltages(&adaptors, MAX_GAP);
         let differences = differences(&ordered_joltages);
         assert_eq!(differences.0, 22);
         assert_eq!(differences.1, 10);
@@ -123,3 +212,62 @@ fn test_arrangements() {
         assert_eq!(total_arrangements(&adaptors), 19208);
     }
 }
+
+#[test]
+fn test_difference_distribution() {
+    let input = "16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4\n";
+    let joltages = ordered_joltages(&parse_adaptors(input), MAX_GAP);
+    assert_eq!(
+        difference_distribution(&joltages),
+        BTreeMap::from([(1, 7), (3, 5)])
+    );
+}
+
+#[test]
+fn test_arrangements_max_gap() {
+    // With a big gap, any subset of the other adaptors works as long as the 5 jolt adaptor is
+    // there to reach the 11 jolt device
+    let joltages = ordered_joltages(&[1, 2, 3, 4, 5], 6);
+    assert_eq!(count_arrangements::<u64>(&joltages, 6), 16);
+    // Only the full chain works when the gap is 1
+    let joltages = ordered_joltages(&[1, 2, 3, 4, 5], 1);
+    assert_eq!(count_arrangements::<u64>(&joltages, 1), 1);
+    // No chains when there's a gap too big to cross
+    assert_eq!(count_arrangements::<u64>(&[0, 1, 5, 6], 3), 0);
+    assert_eq!(Chains::new(&[0, 1, 5, 6], 3).count(), 0);
+
+    // Counts bigger than a u64: adaptors one jolt apart give tribonacci numbers
+    let adaptors = (1..=100).collect::<Vec<u32>>();
+    let count = count_arrangements::<u128>(&ordered_joltages(&adaptors, 3), 3);
+    assert!(count > u64::MAX as u128);
+    let expected = (0..100)
+        .fold((1_u128, 0, 0), |(a, b, c), _| (a + b + c, a, b))
+        .0;
+    assert_eq!(count, expected);
+}
+
+#[test]
+fn test_chains() {
+    let input = "16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4\n";
+    let joltages = ordered_joltages(&parse_adaptors(input), MAX_GAP);
+    let chains = Chains::new(&joltages, MAX_GAP).collect::<Vec<Vec<u32>>>();
+    assert_eq!(chains.len(), 8);
+    assert_eq!(
+        chains[0],
+        vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22]
+    );
+    assert_eq!(chains[7], vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+    for (i, chain) in chains.iter().enumerate() {
+        assert_eq!(
+            nth_chain(&joltages, MAX_GAP, i as u128).as_ref(),
+            Some(chain)
+        );
+        assert!(chain.windows(2).all(|pair| pair[1] - pair[0] <= MAX_GAP));
+    }
+    assert!(chains.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(nth_chain(&joltages, MAX_GAP, 8), None);
+
+    let input = "28\n33\n18\n42\n31\n14\n46\n20\n48\n47\n24\n23\n49\n45\n19\n38\n39\n11\n1\n32\n25\n35\n8\n17\n7\n9\n4\n2\n34\n10\n3\n";
+    let joltages = ordered_joltages(&parse_adaptors(input), MAX_GAP);
+    assert_eq!(Chains::new(&joltages, MAX_GAP).count(), 19208);
+}
@@ -26,27 +26,87 @@ fn parse(input: &str) -> Vec<u64> {
         .collect()
 }
 
-fn nth_number(starting_numbers: &[u64], n: usize) -> u64 {
-    let mut last_spoken: HashMap<u64, usize> = HashMap::new();
+/// The numbers spoken in the memory game, one per turn.
+///
+/// The turn each number was last spoken is kept in a table indexed by number, which grows as
+/// needed up to `dense_limit` entries. Numbers beyond that are kept in a map instead.
+#[derive(Debug, Clone)]
+pub(crate) struct MemoryGame {
+    starting_numbers: Vec<u64>,
+    /// The turn after each number was last spoken, or 0 if it hasn't been spoken.
+    dense: Vec<u32>,
+    dense_limit: usize,
+    sparse: HashMap<u64, u32>,
+    /// The number of turns so far.
+    turn: u32,
+    last_number: u64,
+}
+
+impl MemoryGame {
+    const DEFAULT_DENSE_LIMIT: usize = 1 << 25;
 
-    let mut last_n = 0;
-    let mut last_number = starting_numbers[last_n];
-    last_spoken.insert(last_number, last_n);
+    fn new(starting_numbers: &[u64]) -> Self {
+        Self::with_dense_limit(starting_numbers, Self::DEFAULT_DENSE_LIMIT)
+    }
+
+    pub(crate) fn with_dense_limit(starting_numbers: &[u64], dense_limit: usize) -> Self {
+        assert!(!starting_numbers.is_empty(), "Need some starting numbers");
+        Self {
+            starting_numbers: starting_numbers.to_vec(),
+            dense: Vec::new(),
+            dense_limit,
+            sparse: HashMap::new(),
+            turn: 0,
+            last_number: 0,
+        }
+    }
 
-    while last_n != n {
-        let next_number = if last_n < starting_numbers.len() - 1 {
-            starting_numbers[last_n + 1]
-        } else if let Some(last_spoken_n) = last_spoken.get(&last_number) {
-            (last_n - last_spoken_n) as u64
+    /// Record that a number was spoken, returning the turn it was previously spoken after.
+    fn speak(&mut self, number: u64, turn: u32) -> Option<u32> {
+        let previous = if (number as usize) < self.dense_limit {
+            let index = number as usize;
+            if index >= self.dense.len() {
+                let len = (index + 1).max(self.dense.len() * 2).min(self.dense_limit);
+                self.dense.resize(len, 0);
+            }
+            std::mem::replace(&mut self.dense[index], turn)
         } else {
-            0
+            self.sparse.insert(number, turn).unwrap_or(0)
         };
-        last_spoken.insert(last_number, last_n);
-        last_number = next_number;
-        last_n += 1;
+        (previous != 0).then_some(previous)
     }
+}
+
+impl Iterator for MemoryGame {
+    type Item = u64;
 
-    last_number
+    fn next(&mut self) -> Option<u64> {
+        let turn = self.turn as usize;
+        let number = if turn < self.starting_numbers.len() {
+            self.starting_numbers[turn]
+        } else {
+            // Only now do we record when the last number was spoken, having needed to know
+            // when it was spoken before that
+            match self.speak(self.last_number, self.turn) {
+                Some(previous) => (self.turn - previous) as u64,
+                None => 0,
+            }
+        };
+        if turn > 0 && turn < self.starting_numbers.len() {
+            self.speak(self.last_number, self.turn);
+        }
+        self.turn = self
+            .turn
+            .checked_add(1)
+            .expect("Too many turns to keep track of");
+        self.last_number = number;
+        Some(number)
+    }
+}
+
+/// The number spoken on turn `n`, counting from 0.
+fn nth_number(starting_numbers: &[u64], n: usize) -> u64 {
+    MemoryGame::new(starting_numbers).nth(n).unwrap()
 }
 
 #[test]
@@ -58,7 +118,7 @@ fn test_parse() {
 #[test]
 fn test_nth_number() {
     let input = "0,3,6\n";
-    let starting_numbers = parse(&input);
+    let starting_numbers = parse(input);
     assert_eq!(nth_number(&starting_numbers, 0), 0);
     assert_eq!(nth_number(&starting_numbers, 1), 3);
     assert_eq!(nth_number(&starting_numbers, 2), 6);
@@ -82,8 +142,34 @@ fn test_2020th_number() {
     assert_eq!(nth_number(&parse("3,1,2"), 2019), 1836);
 }
 
+#[test]
+fn test_memory_game() {
+    let numbers = MemoryGame::new(&[0, 3, 6]).take(10).collect::<Vec<u64>>();
+    assert_eq!(numbers, vec![0, 3, 6, 0, 3, 3, 1, 0, 4, 0]);
+
+    // Everything spills over into the map when there's no table
+    let sparse = MemoryGame::with_dense_limit(&[0, 3, 6], 0);
+    let dense = MemoryGame::with_dense_limit(&[0, 3, 6], 5);
+    assert!(sparse.zip(dense).take(10_000).all(|(a, b)| a == b));
+
+    // Starting numbers can repeat, and can be large
+    let numbers = MemoryGame::new(&[1, 1, 1_000_000_000_000])
+        .take(7)
+        .collect::<Vec<u64>>();
+    assert_eq!(numbers, vec![1, 1, 1_000_000_000_000, 0, 0, 1, 4]);
+}
+
 #[test]
 #[ignore]
+fn test_30000000th_number_small_table() {
+    // Most numbers spill over into the map with a small table
+    let number = MemoryGame::with_dense_limit(&[0, 3, 6], 1 << 16)
+        .nth(30000000 - 1)
+        .unwrap();
+    assert_eq!(number, 175594);
+}
+
+#[test]
 fn test_30000000th_number() {
     assert_eq!(nth_number(&parse("0,3,6"), 30000000 - 1), 175594);
     assert_eq!(nth_number(&parse("1,3,2"), 30000000 - 1), 2578);
@@ -1,4 +1,7 @@
-use std::{collections::HashMap, fs};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
@@ -31,6 +34,8 @@ fn get_input_file_contents() -> String {
 struct RulesAndMessages {
     rules: HashMap<usize, Rule>,
     orig_rules: HashMap<usize, Rule>,
+    /// The rules compiled for matching, kept in step with `rules`.
+    grammar: Grammar,
     messages: Vec<String>,
     replaced: bool,
 }
@@ -60,6 +65,7 @@ impl RulesAndMessages {
 
         let orig_rules = rules.clone();
         RulesAndMessages {
+            grammar: Grammar::compile(&rules),
             rules,
             orig_rules,
             messages,
@@ -67,94 +73,387 @@ impl RulesAndMessages {
         }
     }
 
+    /// Replace rules 8 and 11 with their recursive versions.
     fn replace(&mut self) {
-        const MAX_REPEATS: usize = 2;
+        self.rules.insert(8, Rule::parse("42 | 42 8"));
+        self.rules.insert(11, Rule::parse("42 31 | 42 11 31"));
+        self.grammar = Grammar::compile(&self.rules);
+        self.replaced = true;
+    }
+
+    fn matches_rule_zero(&self, message: &str) -> bool {
+        self.grammar.recognises(0, message)
+    }
+}
+
+impl Rule {
+    fn parse(input: &str) -> Self {
+        if input.starts_with('"') {
+            // Single character
+            Rule::Char(input.chars().nth(1).unwrap())
+        } else if input.contains('|') {
+            // Or
+            Rule::Or(input.split(" | ").map(|r| Rule::parse(r)).collect())
+        } else if input.contains(' ') {
+            // List
+            Rule::List(input.split_whitespace().map(Rule::parse).collect())
+        } else {
+            // Ref
+            Rule::Ref(input.parse::<usize>().unwrap())
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+enum Symbol {
+    Terminal(char),
+    NonTerminal(usize),
+}
+
+/// The rules as alternative sequences of symbols, which can be recursive in any way.
+#[derive(Debug, PartialEq, Clone)]
+struct Grammar {
+    productions: HashMap<usize, Vec<Vec<Symbol>>>,
+}
+
+/// How a message matches a rule.
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum ParseTree {
+    Leaf(char),
+    Node {
+        rule: usize,
+        children: Vec<ParseTree>,
+    },
+}
+
+/// What `Grammar::generate` has worked out so far.
+#[derive(Debug)]
+struct Generation {
+    /// The strings of each rule and length, once known to be complete.
+    memo: HashMap<(usize, usize), Vec<String>>,
+    /// The rules and lengths being expanded, with how deeply nested each one is.
+    in_progress: HashMap<(usize, usize), usize>,
+    /// The depth of the outermost expansion a cycle was cut back to, or `usize::MAX` if none.
+    shallowest_cut: usize,
+}
 
-        let mut rule_string = String::new();
-        for repeats in (1..=MAX_REPEATS).rev() {
-            rule_string.push_str(&"42 ".repeat(repeats));
-            if repeats > 1 {
-                rule_string.push_str("| ");
+impl Generation {
+    fn new() -> Self {
+        Generation {
+            memo: HashMap::new(),
+            in_progress: HashMap::new(),
+            shallowest_cut: usize::MAX,
+        }
+    }
+}
+
+/// A partially matched alternative of a rule in the Earley parser, where the symbols before
+/// `dot` have matched the message from `start` onwards.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+struct Item {
+    rule: usize,
+    alternative: usize,
+    dot: usize,
+    start: usize,
+}
+
+impl Grammar {
+    fn compile(rules: &HashMap<usize, Rule>) -> Self {
+        fn symbol(rule: &Rule) -> Symbol {
+            match rule {
+                Rule::Char(ch) => Symbol::Terminal(*ch),
+                Rule::Ref(id) => Symbol::NonTerminal(*id),
+                _ => panic!("Rules can't be nested like {:?}", rule),
             }
         }
-        eprintln!("New rule 8: {rule_string}");
-        self.rules.insert(8, Rule::parse(&rule_string));
-        // self.rules.insert(8, Rule::parse("42 | 42 8"));
-
-        let mut rule_string = String::new();
-        for repeats in (1..=MAX_REPEATS).rev() {
-            rule_string.push_str(&"42 ".repeat(repeats));
-            rule_string.push_str(&"31 ".repeat(repeats));
-            if repeats > 1 {
-                rule_string.push_str("| ");
+        fn sequence(rule: &Rule) -> Vec<Symbol> {
+            match rule {
+                Rule::List(rules) => rules.iter().map(symbol).collect(),
+                _ => vec![symbol(rule)],
             }
         }
-        eprintln!("New rule 11: {rule_string}");
-        self.rules.insert(11, Rule::parse(&rule_string));
-        // self.rules.insert(11, Rule::parse("42 31 | 42 11 31"));
 
-        self.replaced = true;
+        let productions = rules
+            .iter()
+            .map(|(id, rule)| {
+                let alternatives = match rule {
+                    Rule::Or(rules) => rules.iter().map(sequence).collect(),
+                    _ => vec![sequence(rule)],
+                };
+                (*id, alternatives)
+            })
+            .collect();
+        Self { productions }
     }
 
-    fn matches_rule_zero(&self, message: &str) -> bool {
-        self.matches(message, self.rules.get(&0).unwrap(), Some(0)) == Some(message.len())
+    fn symbols(&self, item: &Item) -> &[Symbol] {
+        &self.productions[&item.rule][item.alternative]
     }
 
-    fn matches<'a>(&'a self, message: &str, rule: &'a Rule, _id: Option<usize>) -> Option<usize> {
-        if message.is_empty() {
-            None
-        } else {
-            match rule {
-                Rule::Char(ch) => {
-                    if message.starts_with(*ch) {
-                        Some(1)
-                    } else {
-                        None
-                    }
+    /// Run the Earley parser, returning every alternative of every rule that matches a span of
+    /// the message, as `(rule, alternative, start, end)`.
+    fn chart(&self, start_rule: usize, message: &[char]) -> HashSet<(usize, usize, usize, usize)> {
+        let mut chart: Vec<Vec<Item>> = vec![Vec::new(); message.len() + 1];
+        let mut seen: Vec<HashSet<Item>> = vec![HashSet::new(); message.len() + 1];
+        let mut completed = HashSet::new();
+
+        let add =
+            |chart: &mut Vec<Vec<Item>>, seen: &mut Vec<HashSet<Item>>, pos: usize, item: Item| {
+                if seen[pos].insert(item) {
+                    chart[pos].push(item);
                 }
-                Rule::Ref(id) => self.matches(message, self.rules.get(id).unwrap(), Some(*id)),
-                Rule::List(rules) => {
-                    let mut message = &message[0..];
-                    let mut total_consumed = 0;
-                    for subrule in rules {
-                        if let Some(consumed) = self.matches(message, subrule, None) {
-                            message = &message[consumed..];
-                            total_consumed += consumed;
-                        } else {
-                            return None;
+            };
+        for alternative in 0..self.productions[&start_rule].len() {
+            let item = Item {
+                rule: start_rule,
+                alternative,
+                dot: 0,
+                start: 0,
+            };
+            add(&mut chart, &mut seen, 0, item);
+        }
+
+        for pos in 0..=message.len() {
+            let mut i = 0;
+            while i < chart[pos].len() {
+                let item = chart[pos][i];
+                i += 1;
+                match self.symbols(&item).get(item.dot) {
+                    // Predict
+                    Some(Symbol::NonTerminal(rule)) => {
+                        for alternative in 0..self.productions[rule].len() {
+                            let predicted = Item {
+                                rule: *rule,
+                                alternative,
+                                dot: 0,
+                                start: pos,
+                            };
+                            add(&mut chart, &mut seen, pos, predicted);
                         }
                     }
-                    Some(total_consumed)
-                }
-                Rule::Or(subrules) => {
-                    for rule in subrules {
-                        let result = self.matches(message, rule, None);
-                        if result.is_some() {
-                            return result;
+                    // Scan
+                    Some(Symbol::Terminal(ch)) => {
+                        if message.get(pos) == Some(ch) {
+                            let scanned = Item {
+                                dot: item.dot + 1,
+                                ..item
+                            };
+                            add(&mut chart, &mut seen, pos + 1, scanned);
+                        }
+                    }
+                    // Complete. Rules always match at least one character, so every item
+                    // waiting on this rule is already in the chart where it started.
+                    None => {
+                        completed.insert((item.rule, item.alternative, item.start, pos));
+                        let waiting = chart[item.start]
+                            .iter()
+                            .filter(|waiting| {
+                                self.symbols(waiting).get(waiting.dot)
+                                    == Some(&Symbol::NonTerminal(item.rule))
+                            })
+                            .map(|waiting| Item {
+                                dot: waiting.dot + 1,
+                                ..*waiting
+                            })
+                            .collect::<Vec<Item>>();
+                        for advanced in waiting {
+                            add(&mut chart, &mut seen, pos, advanced);
                         }
                     }
+                }
+            }
+        }
+        completed
+    }
+
+    /// Whether the whole message matches a rule, without working out how.
+    fn recognises(&self, start_rule: usize, message: &str) -> bool {
+        let message = message.chars().collect::<Vec<char>>();
+        if message.is_empty() {
+            return false;
+        }
+        let completed = self.chart(start_rule, &message);
+        (0..self.productions[&start_rule].len())
+            .any(|alternative| completed.contains(&(start_rule, alternative, 0, message.len())))
+    }
+
+    /// Find how the whole message matches a rule, if it does.
+    #[allow(dead_code)]
+    fn parse(&self, start_rule: usize, message: &str) -> Option<ParseTree> {
+        let message = message.chars().collect::<Vec<char>>();
+        if message.is_empty() {
+            return None;
+        }
+        let completed = self.chart(start_rule, &message);
+        let mut in_progress = HashSet::new();
+        self.build_tree(
+            start_rule,
+            0,
+            message.len(),
+            &message,
+            &completed,
+            &mut in_progress,
+        )
+    }
+
+    fn build_tree(
+        &self,
+        rule: usize,
+        start: usize,
+        end: usize,
+        message: &[char],
+        completed: &HashSet<(usize, usize, usize, usize)>,
+        in_progress: &mut HashSet<(usize, usize, usize)>,
+    ) -> Option<ParseTree> {
+        // A rule can't match the same span in terms of itself
+        if !in_progress.insert((rule, start, end)) {
+            return None;
+        }
+        let tree = (0..self.productions[&rule].len())
+            .filter(|alternative| completed.contains(&(rule, *alternative, start, end)))
+            .find_map(|alternative| {
+                let symbols = &self.productions[&rule][alternative];
+                self.build_children(symbols, start, end, message, completed, in_progress)
+            })
+            .map(|children| ParseTree::Node { rule, children });
+        in_progress.remove(&(rule, start, end));
+        tree
+    }
+
+    fn build_children(
+        &self,
+        symbols: &[Symbol],
+        start: usize,
+        end: usize,
+        message: &[char],
+        completed: &HashSet<(usize, usize, usize, usize)>,
+        in_progress: &mut HashSet<(usize, usize, usize)>,
+    ) -> Option<Vec<ParseTree>> {
+        let Some((first, rest)) = symbols.split_first() else {
+            return (start == end).then(Vec::new);
+        };
+        match first {
+            Symbol::Terminal(ch) => {
+                if start < end && message[start] == *ch {
+                    let mut children = vec![ParseTree::Leaf(*ch)];
+                    children.extend(self.build_children(
+                        rest,
+                        start + 1,
+                        end,
+                        message,
+                        completed,
+                        in_progress,
+                    )?);
+                    Some(children)
+                } else {
                     None
                 }
             }
+            Symbol::NonTerminal(rule) => (start + 1..=end).find_map(|mid| {
+                if !(0..self.productions[rule].len())
+                    .any(|alternative| completed.contains(&(*rule, alternative, start, mid)))
+                {
+                    return None;
+                }
+                let rest = self.build_children(rest, mid, end, message, completed, in_progress)?;
+                let tree = self.build_tree(*rule, start, mid, message, completed, in_progress)?;
+                Some(std::iter::once(tree).chain(rest).collect())
+            }),
         }
     }
-}
 
-impl Rule {
-    fn parse(input: &str) -> Self {
-        if input.starts_with('"') {
-            // Single character
-            Rule::Char(input.chars().nth(1).unwrap())
-        } else if input.contains('|') {
-            // Or
-            Rule::Or(input.split(" | ").map(|r| Rule::parse(r)).collect())
-        } else if input.contains(' ') {
-            // List
-            Rule::List(input.split_whitespace().map(Rule::parse).collect())
+    /// Generate up to `limit` different strings of the given length that match a rule.
+    #[allow(dead_code)]
+    fn generate(&self, rule: usize, length: usize, limit: usize) -> Vec<String> {
+        let mut state = Generation::new();
+        let mut strings = self.generate_rule(rule, length, limit, &mut state);
+        strings.sort();
+        strings
+    }
+
+    fn generate_rule(
+        &self,
+        rule: usize,
+        length: usize,
+        limit: usize,
+        state: &mut Generation,
+    ) -> Vec<String> {
+        let key = (rule, length);
+        if let Some(strings) = state.memo.get(&key) {
+            return strings.clone();
+        }
+        let depth = state.in_progress.len();
+        if let Some(&cut_depth) = state.in_progress.get(&key) {
+            // Break the cycle, leaving the strings through it to the outer expansion
+            state.shallowest_cut = state.shallowest_cut.min(cut_depth);
+            return Vec::new();
+        }
+        state.in_progress.insert(key, depth);
+        let outer_cut = std::mem::replace(&mut state.shallowest_cut, usize::MAX);
+
+        let mut strings = HashSet::new();
+        for symbols in &self.productions[&rule] {
+            for string in self.generate_sequence(symbols, length, limit, state) {
+                if strings.len() == limit {
+                    break;
+                }
+                strings.insert(string);
+            }
+        }
+        state.in_progress.remove(&key);
+        let strings = strings.into_iter().collect::<Vec<String>>();
+
+        // Cutting a cycle back to this rule loses nothing, but cutting one back to a rule
+        // further out leaves these strings incomplete until that rule is done
+        if state.shallowest_cut < depth {
+            state.shallowest_cut = state.shallowest_cut.min(outer_cut);
         } else {
-            // Ref
-            Rule::Ref(input.parse::<usize>().unwrap())
+            state.shallowest_cut = outer_cut;
+            state.memo.insert(key, strings.clone());
+        }
+        strings
+    }
+
+    fn generate_sequence(
+        &self,
+        symbols: &[Symbol],
+        length: usize,
+        limit: usize,
+        state: &mut Generation,
+    ) -> Vec<String> {
+        let Some((first, rest)) = symbols.split_first() else {
+            return if length == 0 {
+                vec![String::new()]
+            } else {
+                Vec::new()
+            };
+        };
+        // Every symbol matches at least one character
+        if length < symbols.len() {
+            return Vec::new();
+        }
+        let mut strings = Vec::new();
+        for first_length in 1..=length - rest.len() {
+            let firsts = match first {
+                Symbol::Terminal(ch) if first_length == 1 => vec![ch.to_string()],
+                Symbol::Terminal(_) => continue,
+                Symbol::NonTerminal(rule) => self.generate_rule(*rule, first_length, limit, state),
+            };
+            if firsts.is_empty() {
+                continue;
+            }
+            let rests = self.generate_sequence(rest, length - first_length, limit, state);
+            for first in &firsts {
+                for rest in &rests {
+                    if strings.len() == limit {
+                        return strings;
+                    }
+                    strings.push(format!("{}{}", first, rest));
+                }
+            }
         }
+        strings
     }
 }
 
@@ -197,6 +496,7 @@ fn test_parse() {
     assert_eq!(
         rules_and_messages,
         RulesAndMessages {
+            grammar: Grammar::compile(&rules),
             rules,
             orig_rules,
             messages: vec![
@@ -240,21 +540,92 @@ fn test_rule_matches_replacement() {
     let mut rules_and_messages = RulesAndMessages::parse(input);
     rules_and_messages.replace();
 
-    // assert!(rules_and_messages.matches_rule_zero("bbabbbbaabaabba"));
-    // assert!(rules_and_messages.matches_rule_zero("ababaaaaaabaaab"));
-    // assert!(rules_and_messages.matches_rule_zero("ababaaaaabbbaba"));
+    assert!(rules_and_messages.matches_rule_zero("bbabbbbaabaabba"));
+    assert!(rules_and_messages.matches_rule_zero("ababaaaaaabaaab"));
+    assert!(rules_and_messages.matches_rule_zero("ababaaaaabbbaba"));
 
     assert!(rules_and_messages.matches_rule_zero("babbbbaabbbbbabbbbbbaabaaabaaa"));
-    // assert!(rules_and_messages.matches_rule_zero("aaabbbbbbaaaabaababaabababbabaaabbababababaaa"));
-    // assert!(rules_and_messages.matches_rule_zero("bbbbbbbaaaabbbbaaabbabaaa"));
-    // assert!(rules_and_messages.matches_rule_zero("bbbababbbbaaaaaaaabbababaaababaabab"));
-    // assert!(rules_and_messages.matches_rule_zero("baabbaaaabbaaaababbaababb"));
-    // assert!(rules_and_messages.matches_rule_zero("abbbbabbbbaaaababbbbbbaaaababb"));
-    // assert!(rules_and_messages.matches_rule_zero("aaaaabbaabaaaaababaa"));
-    // assert!(rules_and_messages.matches_rule_zero("aaaabbaabbaaaaaaabbbabbbaaabbaabaaa"));
-    // assert!(rules_and_messages.matches_rule_zero("aabbbbbaabbbaaaaaabbbbbababaaaaabbaaabba"));
-
-    // assert!(!rules_and_messages.matches_rule_zero("abbbbbabbbaaaababbaabbbbabababbbabbbbbbabaaaa"));
-    // assert!(!rules_and_messages.matches_rule_zero("aaaabbaaaabbaaa"));
-    // assert!(!rules_and_messages.matches_rule_zero("babaaabbbaaabaababbaabababaaab"));
+    assert!(rules_and_messages.matches_rule_zero("aaabbbbbbaaaabaababaabababbabaaabbababababaaa"));
+    assert!(rules_and_messages.matches_rule_zero("bbbbbbbaaaabbbbaaabbabaaa"));
+    assert!(rules_and_messages.matches_rule_zero("bbbababbbbaaaaaaaabbababaaababaabab"));
+    assert!(rules_and_messages.matches_rule_zero("baabbaaaabbaaaababbaababb"));
+    assert!(rules_and_messages.matches_rule_zero("abbbbabbbbaaaababbbbbbaaaababb"));
+    assert!(rules_and_messages.matches_rule_zero("aaaaabbaabaaaaababaa"));
+    assert!(rules_and_messages.matches_rule_zero("aaaabbaabbaaaaaaabbbabbbaaabbaabaaa"));
+    assert!(rules_and_messages.matches_rule_zero("aabbbbbaabbbaaaaaabbbbbababaaaaabbaaabba"));
+
+    assert!(!rules_and_messages.matches_rule_zero("abbbbbabbbaaaababbaabbbbabababbbabbbbbbabaaaa"));
+    assert!(!rules_and_messages.matches_rule_zero("aaaabbaaaabbaaa"));
+    assert!(!rules_and_messages.matches_rule_zero("babaaabbbaaabaababbaabababaaab"));
+}
+
+#[test]
+fn test_parse_tree() {
+    let input = "0: 4 1 5\n1: 2 3 | 3 2\n2: 4 4 | 5 5\n3: 4 5 | 5 4\n4: \"a\"\n5: \"b\"\n\n";
+    let rules_and_messages = RulesAndMessages::parse(input);
+    let grammar = Grammar::compile(&rules_and_messages.rules);
+    let node = |rule, children| ParseTree::Node { rule, children };
+    let a = || node(4, vec![ParseTree::Leaf('a')]);
+    let b = || node(5, vec![ParseTree::Leaf('b')]);
+    assert_eq!(
+        grammar.parse(0, "ababbb"),
+        Some(node(
+            0,
+            vec![
+                a(),
+                node(1, vec![node(3, vec![b(), a()]), node(2, vec![b(), b()])]),
+                b()
+            ]
+        ))
+    );
+    assert_eq!(grammar.parse(0, "bababa"), None);
+    assert_eq!(grammar.parse(0, ""), None);
+}
+
+#[test]
+fn test_left_and_right_recursion() {
+    // Rule 1 is left recursive and rule 2 is right recursive, both matching one or more "a"s
+    let input = "0: 1 3 2\n1: 4 | 1 4\n2: 4 | 4 2\n3: \"b\"\n4: \"a\"\n\n";
+    let rules_and_messages = RulesAndMessages::parse(input);
+    assert!(rules_and_messages.matches_rule_zero("aba"));
+    assert!(rules_and_messages.matches_rule_zero("aaaabaaa"));
+    assert!(!rules_and_messages.matches_rule_zero("ab"));
+    assert!(!rules_and_messages.matches_rule_zero("aabba"));
+
+    let grammar = Grammar::compile(&rules_and_messages.rules);
+    assert_eq!(
+        grammar.generate(0, 4, 10),
+        vec!["aaba".to_string(), "abaa".to_string()]
+    );
+    assert_eq!(grammar.generate(0, 10, 3).len(), 3);
+}
+
+#[test]
+fn test_generate() {
+    let input = "0: 4 1 5\n1: 2 3 | 3 2\n2: 4 4 | 5 5\n3: 4 5 | 5 4\n4: \"a\"\n5: \"b\"\n\n";
+    let rules_and_messages = RulesAndMessages::parse(input);
+    let grammar = Grammar::compile(&rules_and_messages.rules);
+    let strings = grammar.generate(0, 6, 100);
+    assert_eq!(strings.len(), 8);
+    assert!(strings.iter().all(|string| grammar.recognises(0, string)));
+    assert!(grammar.generate(0, 5, 100).is_empty());
+
+    // Rules 0 and 1 refer to each other at the same length, so each has both letters
+    let input = "5: 1 0\n0: 1 | 2\n1: 0 | 3\n2: \"a\"\n3: \"b\"\n\n";
+    let rules_and_messages = RulesAndMessages::parse(input);
+    let grammar = Grammar::compile(&rules_and_messages.rules);
+    assert_eq!(grammar.generate(5, 2, 100), vec!["aa", "ab", "ba", "bb"]);
+
+    // Every generated message matches the recursive rules
+    let input = "42: 9 14 | 10 1\n9: 14 27 | 1 26\n10: 23 14 | 28 1\n1: \"a\"\n11: 42 31\n5: 1 14 | 15 1\n19: 14 1 | 14 14\n12: 24 14 | 19 1\n16: 15 1 | 14 14\n31: 14 17 | 1 13\n6: 14 14 | 1 14\n2: 1 24 | 14 4\n0: 8 11\n13: 14 3 | 1 12\n15: 1 | 14\n17: 14 2 | 1 7\n23: 25 1 | 22 14\n28: 16 1\n4: 1 1\n20: 14 14 | 1 15\n3: 5 14 | 16 1\n27: 1 6 | 14 18\n14: \"b\"\n21: 14 1 | 1 14\n25: 1 1 | 1 14\n22: 14 14\n8: 42\n26: 14 22 | 1 20\n18: 15 15\n7: 14 5 | 1 21\n24: 14 1\n\n";
+    let mut rules_and_messages = RulesAndMessages::parse(input);
+    rules_and_messages.replace();
+    let grammar = Grammar::compile(&rules_and_messages.rules);
+    for length in [15, 20] {
+        let strings = grammar.generate(0, length, 20);
+        assert_eq!(strings.len(), 20);
+        assert!(strings
+            .iter()
+            .all(|string| string.len() == length && rules_and_messages.matches_rule_zero(string)));
+    }
 }
@@ -2,19 +2,15 @@ use std::fs;
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
-    let mut game = Game::parse(&input, false);
-    for _ in 0..100 {
-        game.do_move();
-    }
+    let mut game = Game::parse(&input, None);
+    game.play(100);
     game.order().to_string()
 }
 
 pub fn part2() -> String {
     let input = get_input_file_contents();
-    let mut game = Game::parse(&input, true);
-    for _ in 0..10_000_000 {
-        game.do_move();
-    }
+    let mut game = Game::parse(&input, Some(Game::BIG_GAME_CUPS));
+    game.play(Game::BIG_GAME_MOVES);
     let (cup1, cup2) = game.two_cups_after_cup_one();
     (cup1 as u64 * cup2 as u64).to_string()
 }
@@ -23,89 +19,67 @@ fn get_input_file_contents() -> String {
     fs::read_to_string("inputs/input23").expect("Failed to open input file")
 }
 
-// (3),8,9,1,2,5,4,6,7,10,..=1_000_000
-// 3,(2),8,9,1,5,4,6,7,10,..=1_000_000
-// 3,4,6,7,2,5,(10),..=1_000_000,8,9,1
-// 3,4,6,7,2,5,10,(14),15..=1_000_000,8,9,11,12,13,1
-// 3,4,6,7,2,5,10,14,(18),19..=1_000_000,8,9,11,12,13,15,16,17,1
-
 #[derive(Debug, PartialEq)]
 struct Game {
-    cups: Vec<u32>,
+    /// The cup clockwise of each cup, indexed by label. Labels start from 1, so `next[0]` is
+    /// unused.
+    next: Vec<u32>,
     current_cup: u32,
-    min_cup: u32,
-    max_cup: u32,
 }
 
 impl Game {
-    const BIG_GAME_MAX_CUP: u32 = 30;
-
-    fn new(mut cups: Vec<u32>, big_game: bool) -> Self {
-        let current_cup = cups[0];
-        let min_cup = *cups.iter().min().unwrap();
-        let mut max_cup = *cups.iter().max().unwrap();
-
-        if big_game {
-            cups.reserve(Self::BIG_GAME_MAX_CUP as usize - cups.len());
-            let mut cup = max_cup + 1;
-            while cup <= Self::BIG_GAME_MAX_CUP {
-                cups.push(cup);
-                cup += 1;
-            }
-            assert_eq!(cups.len(), Self::BIG_GAME_MAX_CUP as usize);
-            max_cup = Self::BIG_GAME_MAX_CUP;
-        } else {
+    const BIG_GAME_CUPS: u32 = 1_000_000;
+    const BIG_GAME_MOVES: usize = 10_000_000;
+
+    /// Start a game with the given cups, followed by cups with increasing labels until there
+    /// are `total_cups` cups in the circle.
+    fn new(cups: Vec<u32>, total_cups: u32) -> Self {
+        let mut sorted_cups = cups.clone();
+        sorted_cups.sort();
+        assert!(
+            sorted_cups.iter().copied().eq(1..=cups.len() as u32),
+            "Cups should be labelled from 1 upwards"
+        );
+        assert!(total_cups as usize >= cups.len());
+
+        let circle = cups
+            .iter()
+            .copied()
+            .chain(cups.len() as u32 + 1..=total_cups)
+            .collect::<Vec<u32>>();
+        let mut next = vec![0; total_cups as usize + 1];
+        for (cup, next_cup) in circle.iter().zip(circle.iter().cycle().skip(1)) {
+            next[*cup as usize] = *next_cup;
         }
 
         Game {
-            cups,
-            current_cup,
-            min_cup,
-            max_cup,
+            next,
+            current_cup: cups[0],
         }
     }
 
-    fn parse(input: &str, big_game: bool) -> Self {
-        Game::new(
-            input
-                .trim_end()
-                .chars()
-                .map(|ch| (ch as u8 - b'0') as u32)
-                .collect(),
-            big_game,
-        )
+    fn parse(input: &str, total_cups: Option<u32>) -> Self {
+        let cups = input
+            .trim_end()
+            .chars()
+            .map(|ch| (ch as u8 - b'0') as u32)
+            .collect::<Vec<u32>>();
+        let total_cups = total_cups.unwrap_or(cups.len() as u32);
+        Game::new(cups, total_cups)
+    }
+
+    fn max_cup(&self) -> u32 {
+        self.next.len() as u32 - 1
     }
 
     fn do_move(&mut self) {
         // - The crab picks up the three cups that are immediately clockwise of the
         //   current cup. They are removed from the circle; cup spacing is adjusted
         //   as necessary to maintain the circle.
-        let mut picked_up_cups = [0; 3];
-        let mut picked_up_idxs = [0; 3];
-        let mut picked_up_idx = {
-            let current_cup_idx = self
-                .cups
-                .iter()
-                .position(|cup| *cup == self.current_cup)
-                .unwrap();
-            if current_cup_idx == self.cups.len() - 1 {
-                0
-            } else {
-                current_cup_idx + 1
-            }
-        };
-        for i in 0..3 {
-            picked_up_cups[i] = self.cups[picked_up_idx];
-            picked_up_idxs[i] = picked_up_idx;
-            picked_up_idx += 1;
-            if picked_up_idx == self.cups.len() {
-                picked_up_idx = 0;
-            }
-        }
-        picked_up_idxs.sort();
-        for idx in picked_up_idxs.iter().rev() {
-            self.cups.remove(*idx);
-        }
+        let first = self.next[self.current_cup as usize];
+        let second = self.next[first as usize];
+        let third = self.next[second as usize];
+        self.next[self.current_cup as usize] = self.next[third as usize];
 
         // - The crab selects a destination cup: the cup with a label equal to the
         //   current cup's label minus one. If this would select one of the cups that
@@ -113,119 +87,62 @@ impl Game {
         //   that wasn't just picked up. If at any point in this process the value goes
         //   below the lowest value on any cup's label, it wraps around to the highest
         //   value on any cup's label instead.
-        let mut destination_cup = {
-            if self.current_cup == self.min_cup {
-                self.max_cup
+        let mut destination_cup = self.current_cup;
+        loop {
+            destination_cup = if destination_cup == 1 {
+                self.max_cup()
             } else {
-                self.current_cup - 1
-            }
-        };
-        while picked_up_cups.contains(&destination_cup) {
-            if destination_cup == self.min_cup {
-                destination_cup = self.max_cup
-            } else {
-                destination_cup -= 1;
+                destination_cup - 1
+            };
+            if ![first, second, third].contains(&destination_cup) {
+                break;
             }
         }
-        let destination_cup_idx = {
-            let i = self
-                .cups
-                .iter()
-                .position(|cup| *cup == destination_cup)
-                .unwrap();
-            if i == self.cups.len() - 1 {
-                0
-            } else {
-                i + 1
-            }
-        };
 
         // - The crab places the cups it just picked up so that they are immediately
         //   clockwise of the destination cup. They keep the same order as when they
         //   were picked up.
-        let mut dst_idx = destination_cup_idx;
-        for i in 0..3 {
-            self.cups.insert(dst_idx, picked_up_cups[i]);
-            dst_idx += 1;
-            if dst_idx == self.cups.len() {
-                dst_idx = 0;
-            }
-        }
+        self.next[third as usize] = self.next[destination_cup as usize];
+        self.next[destination_cup as usize] = first;
 
         // - The crab selects a new current cup: the cup which is immediately clockwise
         //   of the current cup.
-        let current_cup_idx = self
-            .cups
-            .iter()
-            .position(|cup| *cup == self.current_cup)
-            .unwrap();
-        self.current_cup = if current_cup_idx == self.cups.len() - 1 {
-            self.cups[0]
-        } else {
-            self.cups[current_cup_idx + 1]
-        };
+        self.current_cup = self.next[self.current_cup as usize];
     }
 
-    fn order(&self) -> u32 {
-        let cup_one_idx = self.cups.iter().position(|cup| *cup == 1).unwrap();
-        let mut digits = String::new();
-        let mut i = cup_one_idx + 1;
-        while digits.len() < self.cups.len() - 1 {
-            digits.push_str(&self.cups[i].to_string());
-            i += 1;
-            if i == self.cups.len() {
-                i = 0;
-            }
+    fn play(&mut self, moves: usize) {
+        for _ in 0..moves {
+            self.do_move();
         }
-        digits.parse::<u32>().unwrap()
+    }
+
+    /// The cups clockwise from the given cup, starting with that cup.
+    fn cups_from(&self, start: u32) -> impl Iterator<Item = u32> + '_ {
+        std::iter::successors(Some(start), |cup| Some(self.next[*cup as usize]))
+            .take(self.max_cup() as usize)
+    }
+
+    fn order(&self) -> u32 {
+        self.cups_from(1)
+            .skip(1)
+            .map(|cup| cup.to_string())
+            .collect::<String>()
+            .parse::<u32>()
+            .unwrap()
     }
 
     fn two_cups_after_cup_one(&self) -> (u32, u32) {
-        let cup_one_idx = self.cups.iter().position(|cup| *cup == 1).unwrap();
-        let next_idx = if cup_one_idx == self.cups.len() - 1 {
-            0
-        } else {
-            cup_one_idx + 1
-        };
-        let next_next_idx = if next_idx == self.cups.len() - 1 {
-            0
-        } else {
-            next_idx + 1
-        };
-        (self.cups[next_idx], self.cups[next_next_idx])
+        let next = self.next[1];
+        (next, self.next[next as usize])
     }
 }
 
 impl std::fmt::Display for Game {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let current_cup_idx = self
-            .cups
-            .iter()
-            .position(|c| *c == self.current_cup)
-            .unwrap();
-        let mut i = current_cup_idx;
         write!(f, "({}) ", self.current_cup)?;
-        i += 1;
-        if i == self.cups.len() {
-            i = 0;
+        for cup in self.cups_from(self.current_cup).skip(1) {
+            write!(f, "{}  ", cup)?;
         }
-
-        while i != current_cup_idx {
-            write!(f, "{}  ", self.cups[i])?;
-
-            i += 1;
-            if i == self.cups.len() {
-                i = 0;
-            }
-        }
-
-        // for cup in self.cups.iter() {
-        //     if *cup == self.current_cup {
-        //         write!(f, "({}) ", cup)?;
-        //     } else {
-        //         write!(f, "{}  ", cup)?;
-        //     }
-        // }
         Ok(())
     }
 }
@@ -233,82 +150,76 @@ impl std::fmt::Display for Game {
 #[test]
 fn test_parse() {
     let input = "32415\n";
-    let game = Game::parse(input, false);
-    assert_eq!(
-        game,
-        Game {
-            cups: vec![3, 2, 4, 1, 5],
-            current_cup: 3,
-            min_cup: 1,
-            max_cup: 5,
-        }
-    );
+    let game = Game::parse(input, None);
+    assert_eq!(game.cups_from(3).collect::<Vec<u32>>(), vec![3, 2, 4, 1, 5]);
+    assert_eq!(game.current_cup, 3);
+    assert_eq!(game.max_cup(), 5);
 }
 
 #[test]
 fn test_parse_big_game() {
     let input = "54321\n";
-    let game = Game::parse(input, true);
-    assert_eq!(game.cups.len(), 1_000_000);
-    assert_eq!(game.cups[0..5], vec![5, 4, 3, 2, 1]);
+    let game = Game::parse(input, Some(Game::BIG_GAME_CUPS));
+    let cups = game.cups_from(game.current_cup).collect::<Vec<u32>>();
+    assert_eq!(cups.len(), 1_000_000);
+    assert_eq!(cups[0..7], vec![5, 4, 3, 2, 1, 6, 7]);
+    assert_eq!(cups[999_999], 1_000_000);
     assert_eq!(game.current_cup, 5);
-    assert_eq!(game.min_cup, 1);
-    assert_eq!(game.max_cup, 1_000_000);
+    assert_eq!(game.max_cup(), 1_000_000);
 }
 
 #[test]
 fn test_order() {
-    let game = Game {
-        cups: vec![5, 8, 3, 7, 4, 1, 9, 2, 6],
-        current_cup: 8,
-        min_cup: 1,
-        max_cup: 9,
-    };
+    let game = Game::new(vec![5, 8, 3, 7, 4, 1, 9, 2, 6], 9);
     assert_eq!(game.order(), 92658374);
 }
 
 #[test]
 fn test_do_move() {
-    let mut game = Game::parse("389125467", false);
+    let mut game = Game::parse("389125467", None);
 
     game.do_move();
     assert_eq!(game.order(), 54673289);
+    assert_eq!(game.to_string(), "(2) 8  9  1  5  4  6  7  3  ");
 
-    for _ in 0..9 {
-        game.do_move();
-    }
+    game.play(9);
     assert_eq!(game.order(), 92658374);
 
-    for _ in 0..90 {
-        game.do_move();
-    }
+    game.play(90);
     assert_eq!(game.order(), 67384529);
 }
 
 #[test]
-#[ignore]
 fn test_do_move_big_game() {
-    let mut game = Game::parse("389125467", true);
-    for _ in 0..10_000_000 {
-        game.do_move();
-    }
+    let mut game = Game::parse("389125467", Some(Game::BIG_GAME_CUPS));
+    game.play(Game::BIG_GAME_MOVES);
     assert_eq!(game.two_cups_after_cup_one(), (934001, 159792));
 }
 
 #[test]
 fn medium_game() {
-    let mut game = Game::parse("123456789", true);
-    println!("{}", game);
-
-    game.do_move();
-    loop {
+    // Compare against playing the game directly on a list of 30 cups
+    let mut game = Game::parse("123456789", Some(30));
+    let mut cups = (1..=30).collect::<Vec<u32>>();
+    for _ in 0..1000 {
         game.do_move();
-        if game.current_cup == 1 && game.two_cups_after_cup_one() == (2, 3) {
-            println!("{}", game);
+
+        let picked_up = cups.drain(1..4).collect::<Vec<u32>>();
+        let mut destination_cup = cups[0];
+        loop {
+            destination_cup = if destination_cup == 1 {
+                30
+            } else {
+                destination_cup - 1
+            };
+            if !picked_up.contains(&destination_cup) {
+                break;
+            }
         }
+        let destination = cups.iter().position(|cup| *cup == destination_cup).unwrap();
+        cups.splice(destination + 1..destination + 1, picked_up);
+        cups.rotate_left(1);
+
+        assert_eq!(game.cups_from(game.current_cup).collect::<Vec<u32>>(), cups);
     }
-    // while game.current_cup != 1 {
-    //     game.do_move();
-    // }
-    // println!("{}", game);
 }
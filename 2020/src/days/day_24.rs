@@ -1,3 +1,5 @@
+use crate::hex::{render_svg, Axial, Orientation};
+use automaton::{Automaton, Grid, Neighbourhood, Rule, SparseGrid};
 use std::{collections::HashSet, fs};
 
 pub fn part1() -> String {
@@ -9,11 +11,17 @@ pub fn part1() -> String {
 pub fn part2() -> String {
     let input = get_input_file_contents();
     let tiles = parse_tiles(&input);
-    let mut flipped_tiles = flipped_tiles(&tiles);
-    for _ in 0..100 {
-        daily_flip(&mut flipped_tiles);
-    }
-    flipped_tiles.len().to_string()
+    let mut exhibit = art_exhibit(&flipped_tiles(&tiles));
+    exhibit.run(100);
+    let black_tiles = exhibit
+        .grid()
+        .cells()
+        .into_iter()
+        .map(|[q, r]| Axial::new(q, r))
+        .collect::<Vec<Axial>>();
+    fs::write("day_24_part_2.svg", render_flipped_tiles(&black_tiles))
+        .expect("Failed to write tile image");
+    exhibit.live_count().to_string()
 }
 
 fn get_input_file_contents() -> String {
@@ -24,7 +32,7 @@ fn parse_tiles(input: &str) -> Vec<Tile> {
     input.lines().map(Tile::parse).collect()
 }
 
-fn flipped_tiles(tiles: &[Tile]) -> HashSet<Coord> {
+fn flipped_tiles(tiles: &[Tile]) -> HashSet<Axial> {
     let mut flipped_tiles = HashSet::new();
     for tile in tiles {
         let coord = tile.coord();
@@ -37,192 +45,39 @@ fn flipped_tiles(tiles: &[Tile]) -> HashSet<Coord> {
     flipped_tiles
 }
 
-fn daily_flip(flipped_tiles: &mut HashSet<Coord>) {
-    // Flipped tiles to unflip
-    let mut to_unflip = Vec::new();
-    for coord in flipped_tiles.iter() {
-        let mut flipped_neighbors = 0;
-        for neighbor in coord.neighbors() {
-            if flipped_tiles.contains(&neighbor) {
-                flipped_neighbors += 1;
-            }
-        }
-        if flipped_neighbors == 0 || flipped_neighbors > 2 {
-            to_unflip.push(*coord);
-        }
-    }
-
-    // Unflipped tiles to flip
-    let mut to_flip = Vec::new();
-    let min_x = flipped_tiles.iter().map(|c| c.x).min().unwrap();
-    let max_x = flipped_tiles.iter().map(|c| c.x).max().unwrap();
-    let min_y = flipped_tiles.iter().map(|c| c.y).min().unwrap();
-    let max_y = flipped_tiles.iter().map(|c| c.y).max().unwrap();
-    for x in min_x - 1..=max_x + 1 {
-        for y in min_y - 1..=max_y + 1 {
-            let coord = Coord { x, y };
-            if flipped_tiles.contains(&coord) {
-                continue;
-            }
-            let mut flipped_neighbors = 0;
-            for neighbor in coord.neighbors() {
-                if flipped_tiles.contains(&neighbor) {
-                    flipped_neighbors += 1;
-                }
-            }
-            if flipped_neighbors == 2 {
-                to_flip.push(coord);
-            }
-        }
-    }
-
-    for coord in to_unflip {
-        flipped_tiles.remove(&coord);
-    }
-    flipped_tiles.extend(to_flip);
+/// Each day, a black tile with zero or more than 2 adjacent black tiles is flipped to white,
+/// and a white tile with exactly 2 adjacent black tiles is flipped to black.
+fn art_exhibit(flipped_tiles: &HashSet<Axial>) -> Automaton<2, SparseGrid<2>> {
+    Automaton::new(
+        flipped_tiles.iter().map(|coord| [coord.q, coord.r]),
+        Neighbourhood::HexAxial,
+        Rule::parse("B2/S12"),
+    )
 }
 
+/// A list of steps from the reference tile to a tile.
 #[derive(Debug, PartialEq)]
-struct Tile(Vec<Direction>);
-
-#[derive(Debug, PartialEq)]
-enum Direction {
-    East,
-    SouthEast,
-    SouthWest,
-    West,
-    NorthWest,
-    NorthEast,
-}
-
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
-struct Coord {
-    x: i32,
-    y: i32,
-}
+struct Tile(Vec<Axial>);
 
 impl Tile {
     fn parse(input: &str) -> Self {
-        let mut directions = Vec::new();
-        let chars: Vec<char> = input.chars().collect();
-        let mut i = 0;
-        while i < chars.len() {
-            match chars[i] {
-                'e' => {
-                    directions.push(Direction::East);
-                    i += 1;
-                }
-                's' => {
-                    match chars[i + 1] {
-                        'e' => directions.push(Direction::SouthEast),
-                        'w' => directions.push(Direction::SouthWest),
-                        _ => panic!("invalid direction"),
-                    }
-                    i += 2;
-                }
-                'w' => {
-                    directions.push(Direction::West);
-                    i += 1;
-                }
-                'n' => {
-                    match chars[i + 1] {
-                        'e' => directions.push(Direction::NorthEast),
-                        'w' => directions.push(Direction::NorthWest),
-                        _ => panic!("invalid direction"),
-                    }
-                    i += 2;
-                }
-                _ => panic!("invalid direction"),
-            }
-        }
-        Tile(directions)
+        Tile(
+            Orientation::Pointy
+                .parse_directions(input)
+                .expect("invalid direction"),
+        )
     }
 
-    fn coord(&self) -> Coord {
-        let mut coord = Coord { x: 0, y: 0 };
-        for dir in self.0.iter() {
-            coord = coord.move_dir(dir);
-        }
-        coord
+    fn coord(&self) -> Axial {
+        self.0
+            .iter()
+            .fold(Axial::ORIGIN, |coord, step| coord + *step)
     }
 }
 
-impl Coord {
-    fn neighbors(&self) -> [Coord; 6] {
-        [
-            self.move_dir(&Direction::East),
-            self.move_dir(&Direction::SouthEast),
-            self.move_dir(&Direction::SouthWest),
-            self.move_dir(&Direction::West),
-            self.move_dir(&Direction::NorthWest),
-            self.move_dir(&Direction::NorthEast),
-        ]
-    }
-
-    fn move_dir(&self, dir: &Direction) -> Coord {
-        match dir {
-            Direction::East => Coord {
-                x: self.x + 1,
-                y: self.y,
-            },
-            Direction::SouthEast => {
-                if self.y % 2 != 0 {
-                    Coord {
-                        x: self.x + 1,
-                        y: self.y - 1,
-                    }
-                } else {
-                    Coord {
-                        x: self.x,
-                        y: self.y - 1,
-                    }
-                }
-            }
-            Direction::SouthWest => {
-                if self.y % 2 == 0 {
-                    Coord {
-                        x: self.x - 1,
-                        y: self.y - 1,
-                    }
-                } else {
-                    Coord {
-                        x: self.x,
-                        y: self.y - 1,
-                    }
-                }
-            }
-            Direction::West => Coord {
-                x: self.x - 1,
-                y: self.y,
-            },
-            Direction::NorthWest => {
-                if self.y % 2 == 0 {
-                    Coord {
-                        x: self.x - 1,
-                        y: self.y + 1,
-                    }
-                } else {
-                    Coord {
-                        x: self.x,
-                        y: self.y + 1,
-                    }
-                }
-            }
-            Direction::NorthEast => {
-                if self.y % 2 != 0 {
-                    Coord {
-                        x: self.x + 1,
-                        y: self.y + 1,
-                    }
-                } else {
-                    Coord {
-                        x: self.x,
-                        y: self.y + 1,
-                    }
-                }
-            }
-        }
-    }
+/// Draw the flipped tiles, for inspecting the patterns they make.
+fn render_flipped_tiles<'a, I: IntoIterator<Item = &'a Axial>>(flipped_tiles: I) -> String {
+    render_svg(flipped_tiles, Orientation::Pointy, 10.0)
 }
 
 #[test]
@@ -230,18 +85,26 @@ fn test_parse_tile() {
     assert_eq!(
         Tile::parse("esenee"),
         Tile(vec![
-            Direction::East,
-            Direction::SouthEast,
-            Direction::NorthEast,
-            Direction::East
+            Axial::new(1, 0),
+            Axial::new(0, 1),
+            Axial::new(1, -1),
+            Axial::new(1, 0)
         ])
     );
 }
 
 #[test]
 fn test_tile_coord() {
-    assert_eq!(Tile::parse("esew").coord(), Coord { x: 0, y: -1 });
-    assert_eq!(Tile::parse("nwwswee").coord(), Coord { x: 0, y: 0 });
+    assert_eq!(Tile::parse("esew").coord(), Axial::new(0, 1));
+    assert_eq!(Tile::parse("nwwswee").coord(), Axial::new(0, 0));
+}
+
+#[test]
+fn test_render_flipped_tiles() {
+    let input = "esew\nnwwswee\n";
+    let tiles = parse_tiles(input);
+    let svg = render_flipped_tiles(&flipped_tiles(&tiles));
+    assert_eq!(svg.matches("<polygon").count(), 2);
 }
 
 #[test]
@@ -255,19 +118,17 @@ fn test_flipped_tiles_count() {
 fn test_daily_flip() {
     let input = "sesenwnenenewseeswwswswwnenewsewsw\nneeenesenwnwwswnenewnwwsewnenwseswesw\nseswneswswsenwwnwse\nnwnwneseeswswnenewneswwnewseswneseene\nswweswneswnenwsewnwneneseenw\neesenwseswswnenwswnwnwsewwnwsene\nsewnenenenesenwsewnenwwwse\nwenwwweseeeweswwwnwwe\nwsweesenenewnwwnwsenewsenwwsesesenwne\nneeswseenwwswnwswswnw\nnenwswwsewswnenenewsenwsenwnesesenew\nenewnwewneswsewnwswenweswnenwsenwsw\nsweneswneswneneenwnewenewwneswswnese\nswwesenesewenwneswnwwneseswwne\nenesenwswwswneneswsenwnewswseenwsese\nwnwnesenesenenwwnenwsewesewsesesew\nnenewswnwewswnenesenwnesewesw\neneswnwswnwsenenwnwnwwseeswneewsenese\nneswnwewnwnwseenwseesewsenwsweewe\nwseweeenwnesenwwwswnew\n";
     let tiles = parse_tiles(input);
-    let mut flipped_tiles = flipped_tiles(&tiles);
+    let mut exhibit = art_exhibit(&flipped_tiles(&tiles));
 
-    daily_flip(&mut flipped_tiles);
-    assert_eq!(flipped_tiles.len(), 15);
+    exhibit.step();
+    assert_eq!(exhibit.live_count(), 15);
 
-    daily_flip(&mut flipped_tiles);
-    assert_eq!(flipped_tiles.len(), 12);
+    exhibit.step();
+    assert_eq!(exhibit.live_count(), 12);
 
-    daily_flip(&mut flipped_tiles);
-    assert_eq!(flipped_tiles.len(), 25);
+    exhibit.step();
+    assert_eq!(exhibit.live_count(), 25);
 
-    for _ in 0..97 {
-        daily_flip(&mut flipped_tiles);
-    }
-    assert_eq!(flipped_tiles.len(), 2208);
+    exhibit.run(97);
+    assert_eq!(exhibit.live_count(), 2208);
 }
@@ -1,3 +1,4 @@
+use crate::modular::{discrete_log, mod_pow};
 use std::fs;
 
 pub fn part1() -> String {
@@ -21,36 +22,28 @@ fn parse_public_keys(input: &str) -> (u64, u64) {
     (key1, key2)
 }
 
-fn find_encryption_key(pub_key1: u64, pub_key2: u64) -> u64 {
-    let loop_size1 = find_loop_size(pub_key1);
-    let loop_size2 = find_loop_size(pub_key2);
+/// The modulus and subject number used by the card and door in the handshake.
+const MODULUS: u64 = 20201227;
+const SUBJECT_NUMBER: u64 = 7;
 
-    let enc_key = transform(pub_key2, loop_size1);
-    assert_eq!(enc_key, transform(pub_key1, loop_size2));
-    enc_key
+fn find_encryption_key(pub_key1: u64, pub_key2: u64) -> u64 {
+    find_encryption_key_with(pub_key1, pub_key2, SUBJECT_NUMBER, MODULUS)
+        .expect("Public keys can't be made from the subject number")
 }
 
-fn find_loop_size(pub_key: u64) -> u64 {
-    let mut loops = 0;
-    let mut value = 1;
-    loop {
-        value *= 7;
-        value %= 20201227;
-
-        loops += 1;
-        if value == pub_key {
-            return loops;
-        }
-    }
-}
+/// Find the encryption key for a handshake with any subject number and prime modulus.
+fn find_encryption_key_with(
+    pub_key1: u64,
+    pub_key2: u64,
+    subject_number: u64,
+    modulus: u64,
+) -> Option<u64> {
+    let loop_size1 = discrete_log(subject_number, pub_key1, modulus)?;
+    let loop_size2 = discrete_log(subject_number, pub_key2, modulus)?;
 
-fn transform(subject_number: u64, loop_size: u64) -> u64 {
-    let mut value = 1;
-    for _ in 0..loop_size {
-        value *= subject_number;
-        value %= 20201227;
-    }
-    value
+    let enc_key = mod_pow(pub_key2, loop_size1, modulus);
+    assert_eq!(enc_key, mod_pow(pub_key1, loop_size2, modulus));
+    Some(enc_key)
 }
 
 #[test]
@@ -63,17 +56,17 @@ fn test_parse_public_keys() {
 
 #[test]
 fn test_transform() {
-    assert_eq!(transform(7, 8), 5764801);
-    assert_eq!(transform(7, 11), 17807724);
+    assert_eq!(mod_pow(SUBJECT_NUMBER, 8, MODULUS), 5764801);
+    assert_eq!(mod_pow(SUBJECT_NUMBER, 11, MODULUS), 17807724);
 
-    assert_eq!(transform(17807724, 8), 14897079);
-    assert_eq!(transform(5764801, 11), 14897079);
+    assert_eq!(mod_pow(17807724, 8, MODULUS), 14897079);
+    assert_eq!(mod_pow(5764801, 11, MODULUS), 14897079);
 }
 
 #[test]
 fn test_find_loop_size() {
-    assert_eq!(find_loop_size(5764801), 8);
-    assert_eq!(find_loop_size(17807724), 11);
+    assert_eq!(discrete_log(SUBJECT_NUMBER, 5764801, MODULUS), Some(8));
+    assert_eq!(discrete_log(SUBJECT_NUMBER, 17807724, MODULUS), Some(11));
 }
 
 #[test]
@@ -82,3 +75,23 @@ fn test_find_encryption_key() {
     let (pub_key1, pub_key2) = parse_public_keys(input);
     assert_eq!(find_encryption_key(pub_key1, pub_key2), 14897079);
 }
+
+#[test]
+fn test_find_encryption_key_other_moduli() {
+    for (subject_number, modulus) in [(7, 20201227), (5, 1_000_000_007), (3, 65537), (2, 101)] {
+        let (card_loop_size, door_loop_size) = (1234 % (modulus - 1), 98765 % (modulus - 1));
+        let card_key = mod_pow(subject_number, card_loop_size, modulus);
+        let door_key = mod_pow(subject_number, door_loop_size, modulus);
+        assert_eq!(
+            find_encryption_key_with(card_key, door_key, subject_number, modulus),
+            Some(mod_pow(
+                subject_number,
+                card_loop_size * door_loop_size,
+                modulus
+            ))
+        );
+    }
+
+    // 4 only generates the squares modulo 7, and 3 isn't one
+    assert_eq!(find_encryption_key_with(2, 3, 4, 7), None);
+}
@@ -0,0 +1,430 @@
+use std::{
+    fmt::Write,
+    ops::{Add, Mul, Sub},
+};
+
+/// Whether hexagons have a point or a flat edge at the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Pointy,
+    #[allow(dead_code)]
+    Flat,
+}
+
+impl Orientation {
+    /// The names of the six directions, in the same order as `Axial::DIRECTIONS`.
+    pub fn direction_names(&self) -> [&'static str; 6] {
+        match self {
+            Orientation::Pointy => ["e", "ne", "nw", "w", "sw", "se"],
+            Orientation::Flat => ["se", "ne", "n", "nw", "sw", "s"],
+        }
+    }
+
+    /// Parse a string of directions with no separators, like `esenee`.
+    pub fn parse_directions(&self, input: &str) -> Result<Vec<Axial>, String> {
+        let names = self.direction_names();
+        let mut directions = Vec::new();
+        let mut rest = input.trim();
+        while !rest.is_empty() {
+            // Try two letter names first, so `ne` isn't read as `n` then `e`
+            let (index, name) = names
+                .iter()
+                .enumerate()
+                .filter(|(_, name)| rest.starts_with(**name))
+                .max_by_key(|(_, name)| name.len())
+                .ok_or_else(|| format!("Invalid direction at {:?}", rest))?;
+            directions.push(Axial::DIRECTIONS[index]);
+            rest = &rest[name.len()..];
+        }
+        Ok(directions)
+    }
+}
+
+/// Axial coordinates, where `r` increases to the south.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Axial {
+    pub q: i32,
+    pub r: i32,
+}
+
+/// Cube coordinates, where `q + r + s == 0`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cube {
+    pub q: i32,
+    pub r: i32,
+    pub s: i32,
+}
+
+/// Which rows or columns are shoved over in offset coordinates.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetLayout {
+    /// Pointy hexagons, with odd rows shoved right.
+    OddR,
+    /// Pointy hexagons, with even rows shoved right.
+    EvenR,
+    /// Flat hexagons, with odd columns shoved down.
+    OddQ,
+    /// Flat hexagons, with even columns shoved down.
+    EvenQ,
+}
+
+/// Offset coordinates, which match how a hex grid is usually stored in a 2D array.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Offset {
+    pub col: i32,
+    pub row: i32,
+}
+
+impl Axial {
+    pub const ORIGIN: Axial = Axial::new(0, 0);
+
+    /// The six neighbouring directions, anticlockwise starting from `q + 1`.
+    pub const DIRECTIONS: [Axial; 6] = [
+        Axial::new(1, 0),
+        Axial::new(1, -1),
+        Axial::new(0, -1),
+        Axial::new(-1, 0),
+        Axial::new(-1, 1),
+        Axial::new(0, 1),
+    ];
+
+    pub const fn new(q: i32, r: i32) -> Self {
+        Self { q, r }
+    }
+
+    #[allow(dead_code)]
+    pub fn to_cube(self) -> Cube {
+        Cube {
+            q: self.q,
+            r: self.r,
+            s: -self.q - self.r,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn to_offset(self, layout: OffsetLayout) -> Offset {
+        let Axial { q, r } = self;
+        match layout {
+            OffsetLayout::OddR => Offset {
+                col: q + (r - (r & 1)) / 2,
+                row: r,
+            },
+            OffsetLayout::EvenR => Offset {
+                col: q + (r + (r & 1)) / 2,
+                row: r,
+            },
+            OffsetLayout::OddQ => Offset {
+                col: q,
+                row: r + (q - (q & 1)) / 2,
+            },
+            OffsetLayout::EvenQ => Offset {
+                col: q,
+                row: r + (q + (q & 1)) / 2,
+            },
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn neighbors(self) -> [Axial; 6] {
+        Self::DIRECTIONS.map(|direction| self + direction)
+    }
+
+    #[allow(dead_code)]
+    pub fn distance(self, other: Axial) -> u32 {
+        let Cube { q, r, s } = (self - other).to_cube();
+        (q.unsigned_abs() + r.unsigned_abs() + s.unsigned_abs()) / 2
+    }
+
+    /// The hexagons at exactly the given distance, anticlockwise.
+    #[allow(dead_code)]
+    pub fn ring(self, radius: u32) -> Vec<Axial> {
+        if radius == 0 {
+            return vec![self];
+        }
+        // Start in the last direction, and walk round each side of the ring in turn
+        let mut hex = self + Self::DIRECTIONS[4] * radius as i32;
+        let mut ring = Vec::with_capacity(6 * radius as usize);
+        for direction in Self::DIRECTIONS {
+            for _ in 0..radius {
+                ring.push(hex);
+                hex = hex + direction;
+            }
+        }
+        ring
+    }
+
+    /// The hexagons within the given distance, working outwards ring by ring.
+    #[allow(dead_code)]
+    pub fn spiral(self, radius: u32) -> Vec<Axial> {
+        (0..=radius).flat_map(|r| self.ring(r)).collect()
+    }
+
+    /// The hexagons on a straight line between two hexagons, including both ends.
+    #[allow(dead_code)]
+    pub fn line(self, other: Axial) -> Vec<Axial> {
+        let steps = self.distance(other);
+        if steps == 0 {
+            return vec![self];
+        }
+        // Nudge the line slightly so points exactly on an edge always round the same way
+        let (a, b) = (self.to_cube(), other.to_cube());
+        let lerp =
+            |from: i32, to: i32, t: f64, nudge: f64| from as f64 + nudge + (to - from) as f64 * t;
+        (0..=steps)
+            .map(|step| {
+                let t = step as f64 / steps as f64;
+                Cube::round(
+                    lerp(a.q, b.q, t, 1e-6),
+                    lerp(a.r, b.r, t, 2e-6),
+                    lerp(a.s, b.s, t, -3e-6),
+                )
+                .to_axial()
+            })
+            .collect()
+    }
+
+    /// The centre of the hexagon in pixels, where each hexagon has the given circumradius.
+    pub fn to_pixel(self, orientation: Orientation, size: f64) -> (f64, f64) {
+        let sqrt3 = 3_f64.sqrt();
+        let (q, r) = (self.q as f64, self.r as f64);
+        match orientation {
+            Orientation::Pointy => (size * (sqrt3 * q + sqrt3 / 2.0 * r), size * 1.5 * r),
+            Orientation::Flat => (size * 1.5 * q, size * (sqrt3 / 2.0 * q + sqrt3 * r)),
+        }
+    }
+
+    /// The corners of the hexagon in pixels.
+    pub fn corners(self, orientation: Orientation, size: f64) -> [(f64, f64); 6] {
+        let (x, y) = self.to_pixel(orientation, size);
+        let start = match orientation {
+            Orientation::Pointy => 30.0,
+            Orientation::Flat => 0.0,
+        };
+        [0, 1, 2, 3, 4, 5].map(|corner| {
+            let angle = (start + 60.0 * corner as f64).to_radians();
+            (x + size * angle.cos(), y + size * angle.sin())
+        })
+    }
+}
+
+impl Add for Axial {
+    type Output = Axial;
+
+    fn add(self, other: Axial) -> Axial {
+        Axial::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl Sub for Axial {
+    type Output = Axial;
+
+    fn sub(self, other: Axial) -> Axial {
+        Axial::new(self.q - other.q, self.r - other.r)
+    }
+}
+
+impl Mul<i32> for Axial {
+    type Output = Axial;
+
+    fn mul(self, scale: i32) -> Axial {
+        Axial::new(self.q * scale, self.r * scale)
+    }
+}
+
+#[allow(dead_code)]
+impl Cube {
+    pub fn to_axial(self) -> Axial {
+        Axial::new(self.q, self.r)
+    }
+
+    /// Round fractional cube coordinates to the nearest hexagon.
+    pub fn round(q: f64, r: f64, s: f64) -> Cube {
+        let (mut rq, mut rr, mut rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        // Fix whichever coordinate was rounded the most so they still sum to zero
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        } else {
+            rs = -rq - rr;
+        }
+        Cube {
+            q: rq as i32,
+            r: rr as i32,
+            s: rs as i32,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Offset {
+    pub fn to_axial(self, layout: OffsetLayout) -> Axial {
+        let Offset { col, row } = self;
+        match layout {
+            OffsetLayout::OddR => Axial::new(col - (row - (row & 1)) / 2, row),
+            OffsetLayout::EvenR => Axial::new(col - (row + (row & 1)) / 2, row),
+            OffsetLayout::OddQ => Axial::new(col, row - (col - (col & 1)) / 2),
+            OffsetLayout::EvenQ => Axial::new(col, row - (col + (col & 1)) / 2),
+        }
+    }
+}
+
+/// Format a coordinate to two decimal places, without ever writing `-0.00`.
+fn svg_number(value: f64) -> String {
+    format!("{:.2}", (value * 100.0).round() / 100.0 + 0.0)
+}
+
+/// Draw a set of hexagons as an SVG image, where each has the given circumradius in pixels.
+pub fn render_svg<'a, I: IntoIterator<Item = &'a Axial>>(
+    hexes: I,
+    orientation: Orientation,
+    size: f64,
+) -> String {
+    let polygons = hexes
+        .into_iter()
+        .map(|hex| hex.corners(orientation, size))
+        .collect::<Vec<[(f64, f64); 6]>>();
+    let corners = || polygons.iter().flatten();
+    let min_x = corners().map(|(x, _)| *x).fold(0.0, f64::min);
+    let max_x = corners().map(|(x, _)| *x).fold(0.0, f64::max);
+    let min_y = corners().map(|(_, y)| *y).fold(0.0, f64::min);
+    let max_y = corners().map(|(_, y)| *y).fold(0.0, f64::max);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        svg_number(min_x - size),
+        svg_number(min_y - size),
+        svg_number(max_x - min_x + 2.0 * size),
+        svg_number(max_y - min_y + 2.0 * size)
+    )
+    .unwrap();
+    for polygon in &polygons {
+        let points = polygon
+            .iter()
+            .map(|(x, y)| format!("{},{}", svg_number(*x), svg_number(*y)))
+            .collect::<Vec<String>>()
+            .join(" ");
+        writeln!(
+            svg,
+            r#"  <polygon points="{}" fill="black" stroke="grey"/>"#,
+            points
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[test]
+fn test_parse_directions() {
+    let directions = Orientation::Pointy.parse_directions("esenee").unwrap();
+    assert_eq!(
+        directions,
+        vec![
+            Axial::new(1, 0),
+            Axial::new(0, 1),
+            Axial::new(1, -1),
+            Axial::new(1, 0)
+        ]
+    );
+    let directions = Orientation::Flat.parse_directions("nnes").unwrap();
+    assert_eq!(
+        directions,
+        vec![Axial::new(0, -1), Axial::new(1, -1), Axial::new(0, 1)]
+    );
+    assert!(Orientation::Pointy.parse_directions("nn").is_err());
+}
+
+#[test]
+fn test_conversions() {
+    for hex in Axial::ORIGIN.spiral(4) {
+        assert_eq!(hex.to_cube().to_axial(), hex);
+        let Cube { q, r, s } = hex.to_cube();
+        assert_eq!(q + r + s, 0);
+        for layout in [
+            OffsetLayout::OddR,
+            OffsetLayout::EvenR,
+            OffsetLayout::OddQ,
+            OffsetLayout::EvenQ,
+        ] {
+            assert_eq!(hex.to_offset(layout).to_axial(layout), hex);
+        }
+    }
+    // Odd rows are shoved right, so their neighbours to the south east are in the next column
+    assert_eq!(
+        Axial::new(0, 1).to_offset(OffsetLayout::OddR),
+        Offset { col: 0, row: 1 }
+    );
+    assert_eq!(
+        Axial::new(0, 2).to_offset(OffsetLayout::OddR),
+        Offset { col: 1, row: 2 }
+    );
+    assert_eq!(
+        Axial::new(-1, -1).to_offset(OffsetLayout::OddR),
+        Offset { col: -2, row: -1 }
+    );
+}
+
+#[test]
+fn test_distance_ring_spiral() {
+    let center = Axial::new(2, -1);
+    assert_eq!(center.distance(Axial::new(2, -1)), 0);
+    assert_eq!(center.distance(Axial::new(5, -4)), 3);
+    assert_eq!(center.distance(Axial::new(-1, 0)), 3);
+
+    assert_eq!(center.ring(0), vec![center]);
+    for radius in 1..4 {
+        let ring = center.ring(radius);
+        assert_eq!(ring.len(), 6 * radius as usize);
+        assert!(ring.iter().all(|hex| hex.distance(center) == radius));
+        // Each hexagon in the ring is next to the one before
+        for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+            assert_eq!(a.distance(*b), 1);
+        }
+    }
+    let mut neighbors = center.neighbors().to_vec();
+    neighbors.sort();
+    let mut ring = center.ring(1);
+    ring.sort();
+    assert_eq!(neighbors, ring);
+
+    // 1 + 6 + 12 hexagons
+    assert_eq!(center.spiral(2).len(), 19);
+}
+
+#[test]
+fn test_line() {
+    let line = Axial::new(0, 0).line(Axial::new(3, 0));
+    assert_eq!(
+        line,
+        vec![
+            Axial::new(0, 0),
+            Axial::new(1, 0),
+            Axial::new(2, 0),
+            Axial::new(3, 0)
+        ]
+    );
+    let (a, b) = (Axial::new(-2, 3), Axial::new(4, -1));
+    let line = a.line(b);
+    assert_eq!(line.len() as u32, a.distance(b) + 1);
+    assert_eq!((line[0], line[line.len() - 1]), (a, b));
+    for pair in line.windows(2) {
+        assert_eq!(pair[0].distance(pair[1]), 1);
+    }
+}
+
+#[test]
+fn test_render_svg() {
+    let hexes = [Axial::new(0, 0), Axial::new(1, 0)];
+    let svg = render_svg(&hexes, Orientation::Pointy, 10.0);
+    assert!(svg.starts_with("<svg "));
+    assert_eq!(svg.matches("<polygon").count(), 2);
+    // Pointy hexagons have a corner straight above the centre
+    assert!(svg.contains("0.00,-10.00"));
+}
@@ -1,5 +1,7 @@
 mod days;
+mod hex;
 mod inputs;
+mod modular;
 
 use crate::inputs::get_day_input;
 use days::*;
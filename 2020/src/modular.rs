@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+pub fn mod_mul(a: u64, b: u64, modulus: u64) -> u64 {
+    (a as u128 * b as u128 % modulus as u128) as u64
+}
+
+/// `base` to the power of `exponent`, by repeated squaring.
+pub fn mod_pow(mut base: u64, mut exponent: u64, modulus: u64) -> u64 {
+    let mut result = 1 % modulus;
+    base %= modulus;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mod_mul(result, base, modulus);
+        }
+        base = mod_mul(base, base, modulus);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// The number `x` with `a * x == 1`, if `a` and the modulus are coprime.
+pub fn mod_inverse(a: u64, modulus: u64) -> Option<u64> {
+    // Extended Euclidean algorithm, keeping track of the coefficient of `a`
+    let (mut old_r, mut r) = (a as i128 % modulus as i128, modulus as i128);
+    let (mut old_s, mut s) = (1_i128, 0_i128);
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+    }
+    if old_r != 1 {
+        return None;
+    }
+    Some(old_s.rem_euclid(modulus as i128) as u64)
+}
+
+/// The smallest `x` with `base` to the power of `x` equal to `target`, if there is one, found
+/// with the baby-step giant-step algorithm in `O(sqrt(modulus))` time and space.
+///
+/// The base must be invertible, which it always is for a prime modulus unless it's zero.
+pub fn discrete_log(base: u64, target: u64, modulus: u64) -> Option<u64> {
+    let (base, target) = (base % modulus, target % modulus);
+    let steps = (modulus as f64).sqrt().ceil() as u64;
+
+    // Baby steps: base^j for each j, keeping the smallest j for each value
+    let mut baby_steps = HashMap::with_capacity(steps as usize);
+    let mut value = 1 % modulus;
+    for j in 0..steps {
+        baby_steps.entry(value).or_insert(j);
+        value = mod_mul(value, base, modulus);
+    }
+
+    // Giant steps: target * base^(-i * steps) for each i
+    let giant_step = mod_inverse(mod_pow(base, steps, modulus), modulus)?;
+    let mut value = target;
+    for i in 0..steps {
+        if let Some(j) = baby_steps.get(&value) {
+            return Some(i * steps + j);
+        }
+        value = mod_mul(value, giant_step, modulus);
+    }
+    None
+}
+
+#[test]
+fn test_mod_pow() {
+    assert_eq!(mod_pow(7, 8, 20201227), 5764801);
+    assert_eq!(mod_pow(2, 10, 1000), 24);
+    assert_eq!(mod_pow(5, 0, 13), 1);
+    assert_eq!(mod_pow(5, 0, 1), 0);
+    // Fermat's little theorem, with a modulus too big to multiply in a u64
+    let p = 18_446_744_073_709_551_557;
+    assert_eq!(mod_pow(123_456_789, p - 1, p), 1);
+}
+
+#[test]
+fn test_mod_inverse() {
+    assert_eq!(mod_inverse(3, 11), Some(4));
+    assert_eq!(mod_inverse(10, 17), Some(12));
+    assert_eq!(mod_inverse(6, 9), None);
+    let p = 1_000_000_007;
+    for a in [1, 2, 12345, p - 1] {
+        assert_eq!(mod_mul(a, mod_inverse(a, p).unwrap(), p), 1);
+    }
+}
+
+#[test]
+fn test_discrete_log() {
+    assert_eq!(discrete_log(7, 5764801, 20201227), Some(8));
+    assert_eq!(discrete_log(7, 17807724, 20201227), Some(11));
+    assert_eq!(discrete_log(3, 1, 7), Some(0));
+    // 2 only generates the squares modulo 7
+    assert_eq!(discrete_log(2, 4, 7), Some(2));
+    assert_eq!(discrete_log(2, 3, 7), None);
+
+    let p = 1_000_000_007;
+    for x in [1, 999, 123_456_789, p - 2] {
+        let target = mod_pow(5, x, p);
+        let log = discrete_log(5, target, p).unwrap();
+        assert_eq!(mod_pow(5, log, p), target);
+        assert!(log <= x);
+    }
+}
@@ -1,3 +1,4 @@
+use intervals::{BoxSet, Interval, IntervalBox};
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -10,15 +11,12 @@ use nom::{
 use std::fs;
 
 pub fn part1() -> String {
+    let initialisation_region = IntervalBox::new([Interval::new(-50, 50); 3]);
     let cubes = load_cubes()
         .into_iter()
-        .filter(|c| {
-            c.xmin >= -50
-                && c.xmax <= 50
-                && c.ymin >= -50
-                && c.ymax <= 50
-                && c.zmin >= -50
-                && c.zmax <= 50
+        .filter_map(|c| {
+            let region = c.region.intersection(&initialisation_region)?;
+            Some(Cube { on: c.on, region })
         })
         .collect::<Vec<Cube>>();
     let num_cubes_on = cube_calculator(&cubes);
@@ -31,65 +29,22 @@ pub fn part2() -> String {
     format!("{}", num_cubes_on).to_string()
 }
 
-fn cube_intersection(a: &Cube, b: &Cube) -> Option<Cube> {
-    let xmin = a.xmin.max(b.xmin);
-    let xmax = a.xmax.min(b.xmax);
-    let ymin = a.ymin.max(b.ymin);
-    let ymax = a.ymax.min(b.ymax);
-    let zmin = a.zmin.max(b.zmin);
-    let zmax = a.zmax.min(b.zmax);
-    if xmin > xmax || ymin > ymax || zmin > zmax {
-        None
-    } else {
-        Some(Cube {
-            on: !b.on,
-            xmin,
-            xmax,
-            ymin,
-            ymax,
-            zmin,
-            zmax,
-        })
-    }
-}
-
-fn cube_calculator(cubes: &[Cube]) -> i64 {
-    let mut core_cubes = Vec::new();
+fn cube_calculator(cubes: &[Cube]) -> u64 {
+    let mut lit = BoxSet::new();
     for cube in cubes {
-        let mut to_add = Vec::new();
-        if cube.on {
-            to_add.push(cube.clone());
-        }
-        for existing_cube in &core_cubes {
-            if let Some(intersection) = cube_intersection(cube, existing_cube) {
-                to_add.push(intersection);
-            }
-        }
-        core_cubes.extend(to_add);
-    }
-
-    let mut on_count = 0;
-    for cube in &core_cubes {
-        let cube_size = cube.size();
         if cube.on {
-            on_count += cube_size;
+            lit.insert(cube.region);
         } else {
-            on_count -= cube_size;
+            lit.remove(&cube.region);
         }
     }
-
-    on_count
+    lit.volume()
 }
 
 #[derive(Debug, Clone)]
 struct Cube {
     on: bool,
-    xmin: i64,
-    xmax: i64,
-    ymin: i64,
-    ymax: i64,
-    zmin: i64,
-    zmax: i64,
+    region: IntervalBox<3>,
 }
 
 fn parse_i64(input: &str) -> IResult<&str, i64> {
@@ -106,10 +61,6 @@ fn parse_i64(input: &str) -> IResult<&str, i64> {
 }
 
 impl Cube {
-    fn size(&self) -> i64 {
-        (self.xmax - self.xmin + 1) * (self.ymax - self.ymin + 1) * (self.zmax - self.zmin + 1)
-    }
-
     fn parse(input: &str) -> IResult<&str, Self> {
         map(
             tuple((
@@ -136,12 +87,11 @@ impl Cube {
                     "off" => false,
                     _ => panic!("Failed to parse cube on/off."),
                 },
-                xmin,
-                xmax,
-                ymin,
-                ymax,
-                zmin,
-                zmax,
+                region: IntervalBox::new([
+                    Interval::new(xmin, xmax),
+                    Interval::new(ymin, ymax),
+                    Interval::new(zmin, zmax),
+                ]),
             },
         )(input)
     }
@@ -165,54 +115,22 @@ fn parse_test() {
     let input = "on x=2..47,y=-22..22,z=-23..27";
     let (_, cube) = Cube::parse(input).unwrap();
     assert_eq!(cube.on, true);
-    assert_eq!(cube.xmin, 2);
-    assert_eq!(cube.xmax, 47);
-    assert_eq!(cube.ymin, -22);
-    assert_eq!(cube.ymax, 22);
-    assert_eq!(cube.zmin, -23);
-    assert_eq!(cube.zmax, 27);
+    assert_eq!(cube.region.axes[0], Interval::new(2, 47));
+    assert_eq!(cube.region.axes[1], Interval::new(-22, 22));
+    assert_eq!(cube.region.axes[2], Interval::new(-23, 27));
 }
 
 #[test]
 fn test_1() {
-    let cubes = vec![
-        Cube {
-            on: true,
-            xmin: 10,
-            xmax: 12,
-            ymin: 10,
-            ymax: 12,
-            zmin: 10,
-            zmax: 12,
-        },
-        Cube {
-            on: true,
-            xmin: 11,
-            xmax: 13,
-            ymin: 11,
-            ymax: 13,
-            zmin: 11,
-            zmax: 13,
-        },
-        Cube {
-            on: false,
-            xmin: 9,
-            xmax: 11,
-            ymin: 9,
-            ymax: 11,
-            zmin: 9,
-            zmax: 11,
-        },
-        Cube {
-            on: true,
-            xmin: 10,
-            xmax: 10,
-            ymin: 10,
-            ymax: 10,
-            zmin: 10,
-            zmax: 10,
-        },
-    ];
+    let input = "on x=10..12,y=10..12,z=10..12\non x=11..13,y=11..13,z=11..13\noff x=9..11,y=9..11,z=9..11\non x=10..10,y=10..10,z=10..10\n";
+    let (_, cubes) = parse_cubes(input).unwrap();
     let num_cubes_on = cube_calculator(&cubes);
     assert_eq!(num_cubes_on, 39);
 }
+
+#[test]
+fn test_large_example() {
+    let input = "on x=-5..47,y=-31..22,z=-19..33\non x=-44..5,y=-27..21,z=-14..35\non x=-49..-1,y=-11..42,z=-10..38\non x=-20..34,y=-40..6,z=-44..1\noff x=26..39,y=40..50,z=-2..11\non x=-41..5,y=-41..6,z=-36..8\noff x=-43..-33,y=-45..-28,z=7..25\non x=-33..15,y=-32..19,z=-34..11\noff x=35..47,y=-46..-34,z=-11..5\non x=-14..36,y=-6..44,z=-16..29\non x=-57795..-6158,y=29564..72030,z=20435..90618\non x=36731..105352,y=-21140..28532,z=16094..90401\non x=30999..107136,y=-53464..15513,z=8553..71215\non x=13528..83982,y=-99403..-27377,z=-24141..23996\non x=-72682..-12347,y=18159..111354,z=7391..80950\non x=-1060..80757,y=-65301..-20884,z=-103788..-16709\non x=-83015..-9461,y=-72160..-8347,z=-81239..-26856\non x=-52752..22273,y=-49450..9096,z=54442..119054\non x=-29982..40483,y=-108474..-28371,z=-24328..38471\non x=-4958..62750,y=40422..118853,z=-7672..65583\non x=55694..108686,y=-43367..46958,z=-26781..48729\non x=-98497..-18186,y=-63569..3412,z=1232..88485\non x=-726..56291,y=-62629..13224,z=18033..85226\non x=-110886..-34664,y=-81338..-8658,z=8914..63723\non x=-55829..24974,y=-16897..54165,z=-121762..-28058\non x=-65152..-11147,y=22489..91432,z=-58782..1780\non x=-120100..-32970,y=-46592..27473,z=-11695..61039\non x=-18631..37533,y=-124565..-50804,z=-35667..28308\non x=-57817..18248,y=49321..117703,z=5745..55881\non x=14781..98692,y=-1341..70827,z=15753..70151\non x=-34419..55919,y=-19626..40991,z=39015..114138\non x=-60785..11593,y=-56135..2999,z=-95368..-26915\non x=-32178..58085,y=17647..101866,z=-91405..-8878\non x=-53655..12091,y=50097..105568,z=-75335..-4862\non x=-111166..-40997,y=-71714..2688,z=5609..50954\non x=-16602..70118,y=-98693..-44401,z=5197..76897\non x=16383..101554,y=4615..83635,z=-44907..18747\noff x=-95822..-15171,y=-19987..48940,z=10804..104439\non x=-89813..-14614,y=16069..88491,z=-3297..45228\non x=41075..99376,y=-20427..49978,z=-52012..13762\non x=-21330..50085,y=-17944..62733,z=-112280..-30197\non x=-16478..35915,y=36008..118594,z=-7885..47086\noff x=-98156..-27851,y=-49952..43171,z=-99005..-8456\noff x=2032..69770,y=-71013..4824,z=7471..94418\non x=43670..120875,y=-42068..12382,z=-24787..38892\noff x=37514..111226,y=-45862..25743,z=-16714..54663\noff x=25699..97951,y=-30668..59918,z=-15349..69697\noff x=-44271..17935,y=-9516..60759,z=49131..112598\non x=-61695..-5813,y=40978..94975,z=8655..80240\noff x=-101086..-9439,y=-7088..67543,z=33935..83858\noff x=18020..114017,y=-48931..32606,z=21474..89843\noff x=-77139..10506,y=-89994..-18797,z=-80..59318\noff x=8476..79288,y=-75520..11602,z=-96624..-24783\non x=-47488..-1262,y=24338..100707,z=16292..72967\noff x=-84341..13987,y=2429..92914,z=-90671..-1318\noff x=-37810..49457,y=-71013..-7894,z=-105357..-13188\noff x=-27365..46395,y=31009..98017,z=15428..76570\noff x=-70369..-16548,y=22648..78696,z=-1892..86821\non x=-53470..21291,y=-120233..-33476,z=-44150..38147\noff x=-93533..-4276,y=-16170..68771,z=-104985..-24507\n";
+    let (_, cubes) = parse_cubes(input).unwrap();
+    assert_eq!(cube_calculator(&cubes), 2758514936282235);
+}
@@ -38,6 +38,8 @@ enum FileTree {
     },
 }
 
+/// Lists the tree with one entry per line. The alternate form `{:#}` also gives each
+/// directory's total size.
 impl fmt::Display for FileTree {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -48,10 +50,19 @@ impl fmt::Display for FileTree {
                 ..
             } => {
                 let indent = vec![' '; *level * 2].iter().collect::<String>();
-                writeln!(f, "{}- {} (dir)", indent, name)?;
-                children.iter().fold(Ok(()), |result, child_tree| {
-                    result.and_then(|_| write!(f, "{}", child_tree.borrow()))
-                })
+                if f.alternate() {
+                    writeln!(f, "{}- {} (dir, size={})", indent, name, self.size())?;
+                } else {
+                    writeln!(f, "{}- {} (dir)", indent, name)?;
+                }
+                for child in children {
+                    if f.alternate() {
+                        write!(f, "{:#}", child.borrow())?;
+                    } else {
+                        write!(f, "{}", child.borrow())?;
+                    }
+                }
+                Ok(())
             }
             FileTree::File { name, size, level } => {
                 let indent = vec![' '; *level * 2].iter().collect::<String>();
@@ -111,6 +122,38 @@ impl FileTree {
         }
     }
 
+    fn from_commands(commands: &[Command]) -> Self {
+        let root_node = FileSystem::from_commands(commands).root;
+        Rc::try_unwrap(root_node)
+            .unwrap()
+            .replace(FileTree::new_root())
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            FileTree::Directory { name, .. } => name,
+            FileTree::File { name, .. } => name,
+        }
+    }
+
+    /// Total size of a file, or of everything within a directory.
+    fn size(&self) -> u64 {
+        match self {
+            FileTree::Directory { children, .. } => {
+                children.iter().map(|child| child.borrow().size()).sum()
+            }
+            FileTree::File { size, .. } => *size,
+        }
+    }
+}
+
+/// A filesystem built by replaying a terminal session.
+#[derive(Debug)]
+struct FileSystem {
+    root: Rc<RefCell<FileTree>>,
+}
+
+impl FileSystem {
     fn from_commands(commands: &[Command]) -> Self {
         let root_node = Rc::new(RefCell::new(FileTree::new_root()));
         let mut current_node = root_node.clone();
@@ -163,14 +206,94 @@ impl FileTree {
                                 level: current_level + 1,
                             },
                         };
-                        current_node.borrow_mut().add_child(child);
+                        // Listing the same directory again shouldn't duplicate its contents
+                        if current_node.borrow().find_child(child.name()).is_none() {
+                            current_node.borrow_mut().add_child(child);
+                        }
                     }
                 }
             }
         }
-        Rc::try_unwrap(root_node)
-            .unwrap()
-            .replace(FileTree::new_root())
+        FileSystem { root: root_node }
+    }
+
+    /// Find the file or directory at the given path, relative to the root.
+    fn lookup(&self, path: &str) -> Option<Rc<RefCell<FileTree>>> {
+        let mut current_node = self.root.clone();
+        for component in path.split('/') {
+            let next_node = match component {
+                "" | "." => continue,
+                ".." => match &*current_node.borrow() {
+                    FileTree::Directory { parent, .. } => parent
+                        .as_ref()
+                        .map(|parent| parent.upgrade().unwrap())
+                        .unwrap_or_else(|| self.root.clone()),
+                    FileTree::File { .. } => return None,
+                },
+                name => match &*current_node.borrow() {
+                    FileTree::Directory { .. } => current_node.borrow().find_child(name)?,
+                    FileTree::File { .. } => return None,
+                },
+            };
+            current_node = next_node;
+        }
+        Some(current_node)
+    }
+
+    /// The path and total size of the directory at `path` and every directory beneath it,
+    /// like `du`.
+    fn du(&self, path: &str) -> Option<Vec<(String, u64)>> {
+        fn visit(node: &FileTree, path: String, directories: &mut Vec<(String, u64)>) {
+            if let FileTree::Directory { children, .. } = node {
+                directories.push((path.clone(), node.size()));
+                for child in children {
+                    let child = child.borrow();
+                    let child_path = format!("{}/{}", path.trim_end_matches('/'), child.name());
+                    visit(&child, child_path, directories);
+                }
+            }
+        }
+
+        let node = self.lookup(path)?;
+        let node = node.borrow();
+        if !matches!(*node, FileTree::Directory { .. }) {
+            return None;
+        }
+        let path = format!(
+            "/{}",
+            path.split('/')
+                .filter(|c| !c.is_empty())
+                .collect::<Vec<&str>>()
+                .join("/")
+        );
+        let mut directories = Vec::new();
+        visit(&node, path, &mut directories);
+        Some(directories)
+    }
+
+    /// The smallest directory which, if deleted, would leave at least `required_free_space`
+    /// free on a disk of the given capacity. Returns `None` if there's already enough free
+    /// space, or if not even deleting everything would free enough.
+    fn smallest_directory_to_free(
+        &self,
+        capacity: u64,
+        required_free_space: u64,
+    ) -> Option<(String, u64)> {
+        let used_space = self.root.borrow().size();
+        let space_to_free = (used_space + required_free_space).saturating_sub(capacity);
+        if space_to_free == 0 || used_space < space_to_free {
+            return None;
+        }
+        self.du("/")?
+            .into_iter()
+            .filter(|(_, size)| *size >= space_to_free)
+            .min_by_key(|(_, size)| *size)
+    }
+}
+
+impl fmt::Display for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#}", self.root.borrow())
     }
 }
 
@@ -190,19 +313,12 @@ pub fn part1() -> String {
 pub fn part2() -> String {
     let contents = get_input_file_contents();
     let commands = parse_commands(&contents);
-    let tree = FileTree::from_commands(&commands);
-    let directories = get_all_directory_sizes(&tree);
+    let file_system = FileSystem::from_commands(&commands);
 
     let total_space = 70000000;
     let required_free_space = 30000000;
-    let current_free_space = total_space - directories[0].1;
-    let space_to_free = required_free_space - current_free_space;
-
-    let directory_to_delete_size = directories
-        .iter()
-        .map(|(_, size)| *size)
-        .filter(|s| s >= &space_to_free)
-        .min()
+    let (_, directory_to_delete_size) = file_system
+        .smallest_directory_to_free(total_space, required_free_space)
         .unwrap();
 
     format!("{}", directory_to_delete_size)
@@ -426,3 +542,92 @@ fn test_get_all_directory_sizes() {
     assert_eq!(directories[2], ("e".to_string(), 584));
     assert_eq!(directories[3], ("d".to_string(), 24933642));
 }
+
+#[test]
+fn test_file_system_lookup() {
+    let contents = "$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst\n$ cd e\n$ ls\n584 i\n$ cd ..\n$ cd ..\n$ cd d\n$ ls\n4060174 j\n8033020 d.log\n5626152 d.ext\n7214296 k\n";
+    let commands = parse_commands(contents);
+    let file_system = FileSystem::from_commands(&commands);
+
+    assert_eq!(file_system.lookup("/").unwrap().borrow().name(), "/");
+    assert_eq!(file_system.lookup("/a/e").unwrap().borrow().size(), 584);
+    assert_eq!(file_system.lookup("a/e/i").unwrap().borrow().size(), 584);
+    assert_eq!(
+        file_system.lookup("/a/e/../../d").unwrap().borrow().name(),
+        "d"
+    );
+    assert_eq!(file_system.lookup("/d/k").unwrap().borrow().size(), 7214296);
+    assert!(file_system.lookup("/x").is_none());
+    assert!(file_system.lookup("/b.txt/x").is_none());
+}
+
+#[test]
+fn test_file_system_display() {
+    let contents = "$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst\n$ cd e\n$ ls\n584 i\n$ cd ..\n$ cd ..\n$ cd d\n$ ls\n4060174 j\n8033020 d.log\n5626152 d.ext\n7214296 k\n";
+    let commands = parse_commands(contents);
+    let file_system = FileSystem::from_commands(&commands);
+    assert_eq!(file_system.to_string(), "- / (dir, size=48381165)\n  - a (dir, size=94853)\n    - e (dir, size=584)\n      - i (file, size=584)\n    - f (file, size=29116)\n    - g (file, size=2557)\n    - h.lst (file, size=62596)\n  - b.txt (file, size=14848514)\n  - c.dat (file, size=8504156)\n  - d (dir, size=24933642)\n    - j (file, size=4060174)\n    - d.log (file, size=8033020)\n    - d.ext (file, size=5626152)\n    - k (file, size=7214296)\n");
+}
+
+#[test]
+fn test_file_system_du() {
+    let contents = "$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst\n$ cd e\n$ ls\n584 i\n$ cd ..\n$ cd ..\n$ cd d\n$ ls\n4060174 j\n8033020 d.log\n5626152 d.ext\n7214296 k\n";
+    let commands = parse_commands(contents);
+    let file_system = FileSystem::from_commands(&commands);
+
+    assert_eq!(
+        file_system.du("/"),
+        Some(vec![
+            ("/".to_string(), 48381165),
+            ("/a".to_string(), 94853),
+            ("/a/e".to_string(), 584),
+            ("/d".to_string(), 24933642),
+        ])
+    );
+    assert_eq!(
+        file_system.du("/a/"),
+        Some(vec![("/a".to_string(), 94853), ("/a/e".to_string(), 584)])
+    );
+    assert_eq!(file_system.du("/b.txt"), None);
+    assert_eq!(file_system.du("/x"), None);
+}
+
+#[test]
+fn test_smallest_directory_to_free() {
+    let contents = "$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst\n$ cd e\n$ ls\n584 i\n$ cd ..\n$ cd ..\n$ cd d\n$ ls\n4060174 j\n8033020 d.log\n5626152 d.ext\n7214296 k\n";
+    let commands = parse_commands(contents);
+    let file_system = FileSystem::from_commands(&commands);
+
+    assert_eq!(
+        file_system.smallest_directory_to_free(70000000, 30000000),
+        Some(("/d".to_string(), 24933642))
+    );
+    assert_eq!(
+        file_system.smallest_directory_to_free(48381165, 500),
+        Some(("/a/e".to_string(), 584))
+    );
+    assert_eq!(
+        file_system.smallest_directory_to_free(48381165, 1000),
+        Some(("/a".to_string(), 94853))
+    );
+    assert_eq!(
+        file_system.smallest_directory_to_free(100000000, 30000000),
+        None
+    );
+    assert_eq!(
+        file_system.smallest_directory_to_free(40000000, 50000000),
+        None
+    );
+}
+
+#[test]
+fn test_file_system_repeated_commands() {
+    // Listing a directory twice, and returning to `/` partway through
+    let contents = "$ cd /\n$ ls\ndir a\n100 b\n$ cd a\n$ ls\n200 c\n$ cd /\n$ ls\ndir a\n100 b\n$ cd a\n$ ls\n200 c\n";
+    let commands = parse_commands(contents);
+    let file_system = FileSystem::from_commands(&commands);
+    assert_eq!(
+        file_system.to_string(),
+        "- / (dir, size=300)\n  - a (dir, size=200)\n    - c (file, size=200)\n  - b (file, size=100)\n"
+    );
+}
@@ -14,7 +14,8 @@ pub fn part2() -> String {
     let instructions = parse_instructions(&contents);
     let mut cpu = Cpu::new(instructions);
     cpu.run_program();
-    format!("\n{}", cpu.draw_screen())
+    let screen = cpu.draw_screen();
+    ocr::recognise_str(&screen).unwrap_or_else(|| format!("\n{}", screen))
 }
 
 fn get_input_file_contents() -> String {
@@ -22,22 +23,23 @@ fn get_input_file_contents() -> String {
 }
 
 #[derive(Debug)]
-struct Cpu {
+struct Cpu<I: CpuInstruction = Instruction> {
     x: i64,
     x_history: Vec<i64>,
     signal_strengths: Vec<i64>,
     cycle: u64,
-    instructions: Vec<Instruction>,
+    instructions: Vec<I>,
     instruction_counter: usize,
-    executing_addx: bool,
+    /// Number of cycles the current instruction has been executing for.
+    instruction_cycles: u64,
     pixels: Vec<bool>,
 }
 
-impl Cpu {
+impl<I: CpuInstruction> Cpu<I> {
     const ROWS: usize = 6;
     const COLS: usize = 40;
 
-    fn new(instructions: Vec<Instruction>) -> Self {
+    fn new(instructions: Vec<I>) -> Self {
         Self {
             x: 1,
             x_history: Vec::new(),
@@ -45,7 +47,7 @@ impl Cpu {
             cycle: 1,
             instructions,
             instruction_counter: 0,
-            executing_addx: false,
+            instruction_cycles: 0,
             pixels: vec![false; Self::ROWS * Self::COLS],
         }
     }
@@ -91,43 +93,40 @@ impl Cpu {
             return;
         }
         let instruction = self.instructions[self.instruction_counter];
-        match instruction {
-            Instruction::Addx(v) => {
-                if !self.executing_addx {
-                    // First cycle of addx instruction
-                    self.executing_addx = true;
-                    self.x_history.push(self.x);
-                    self.record_signal_strength();
-                    self.draw_pixel();
-                } else {
-                    // Second cycle of addx instruction
-                    self.executing_addx = false;
-                    self.x_history.push(self.x);
-                    self.record_signal_strength();
-                    self.draw_pixel();
-                    self.x += v;
-                    self.instruction_counter += 1;
-                }
-            }
-            Instruction::Noop => {
-                // Do nothing
-                self.x_history.push(self.x);
-                self.record_signal_strength();
-                self.draw_pixel();
-                self.instruction_counter += 1;
-            }
+        self.x_history.push(self.x);
+        self.record_signal_strength();
+        self.draw_pixel();
+
+        self.instruction_cycles += 1;
+        if self.instruction_cycles >= instruction.cycles() {
+            // Last cycle of the instruction
+            instruction.execute(&mut self.x);
+            self.instruction_cycles = 0;
+            self.instruction_counter += 1;
         }
         self.cycle += 1;
     }
 }
 
+/// An instruction the CPU can execute. The instruction set can be extended by implementing
+/// this for a type which wraps `Instruction`.
+trait CpuInstruction: Copy {
+    fn parse(input: &str) -> Option<Self>;
+
+    /// Number of cycles the instruction takes to complete.
+    fn cycles(&self) -> u64;
+
+    /// Update the `x` register at the end of the instruction's last cycle.
+    fn execute(&self, x: &mut i64);
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum Instruction {
     Addx(i64),
     Noop,
 }
 
-impl Instruction {
+impl CpuInstruction for Instruction {
     fn parse(input: &str) -> Option<Self> {
         let parts = input.split(' ').collect::<Vec<&str>>();
         if parts.len() == 1 && parts[0] == "noop" {
@@ -141,15 +140,33 @@ impl Instruction {
             None
         }
     }
+
+    fn cycles(&self) -> u64 {
+        match self {
+            Instruction::Addx(_) => 2,
+            Instruction::Noop => 1,
+        }
+    }
+
+    fn execute(&self, x: &mut i64) {
+        match self {
+            Instruction::Addx(v) => *x += v,
+            Instruction::Noop => {}
+        }
+    }
 }
 
 fn parse_instructions(contents: &str) -> Vec<Instruction> {
+    parse_program(contents)
+}
+
+fn parse_program<I: CpuInstruction>(contents: &str) -> Vec<I> {
     let mut instructions = Vec::new();
     for line in contents.split('\n') {
         if line.is_empty() {
             continue;
         }
-        instructions.push(Instruction::parse(line).expect("Failed to parse instruction"));
+        instructions.push(I::parse(line).expect("Failed to parse instruction"));
     }
     instructions
 }
@@ -198,3 +215,42 @@ fn test_screen_output() {
     let screen = cpu.draw_screen();
     assert_eq!(screen, "##..##..##..##..##..##..##..##..##..##..\n###...###...###...###...###...###...###.\n####....####....####....####....####....\n#####.....#####.....#####.....#####.....\n######......######......######......####\n#######.......#######.......#######.....\n");
 }
+
+#[test]
+fn test_extended_instruction_set() {
+    #[derive(Debug, Clone, Copy)]
+    enum ExtendedInstruction {
+        Base(Instruction),
+        Mulx(i64),
+    }
+
+    impl CpuInstruction for ExtendedInstruction {
+        fn parse(input: &str) -> Option<Self> {
+            match input.split_once(' ') {
+                Some(("mulx", v)) => Some(Self::Mulx(v.parse().ok()?)),
+                _ => Instruction::parse(input).map(Self::Base),
+            }
+        }
+
+        fn cycles(&self) -> u64 {
+            match self {
+                Self::Base(instruction) => instruction.cycles(),
+                Self::Mulx(_) => 3,
+            }
+        }
+
+        fn execute(&self, x: &mut i64) {
+            match self {
+                Self::Base(instruction) => instruction.execute(x),
+                Self::Mulx(v) => *x *= v,
+            }
+        }
+    }
+
+    let contents = "noop\naddx 3\nmulx 5\naddx -5\n";
+    let instructions = parse_program::<ExtendedInstruction>(contents);
+    let mut cpu = Cpu::new(instructions);
+    cpu.run_program();
+    assert_eq!(cpu.x_history, vec![1, 1, 1, 4, 4, 4, 20, 20]);
+    assert_eq!(cpu.x, 15);
+}
@@ -1,19 +1,25 @@
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{alphanumeric1, digit1},
-    combinator::{eof, map, opt},
-    multi::{many_till, separated_list0},
-    sequence::tuple,
+    bytes::complete::{tag, take_until},
+    character::complete::{char, digit1, one_of, space0},
+    combinator::{all_consuming, eof, map, opt},
+    multi::{fold_many0, many_till, separated_list0},
+    sequence::{delimited, pair, preceded, tuple},
     IResult,
 };
-use std::{collections::HashMap, fs};
+use num::Integer;
+use num::{BigInt, Zero};
+use std::{
+    fmt, fs,
+    ops::{Add, Mul, Sub},
+};
 
 pub fn part1() -> String {
     let contents = get_input_file_contents();
-    let mut monkeys = parse_monkeys(&contents);
+    let worry_reduction = DivideBy(3);
+    let mut monkeys = with_worry_levels(parse_monkeys(&contents), &worry_reduction);
     for _ in 0..20 {
-        run_round(&mut monkeys, true);
+        run_round(&mut monkeys, &worry_reduction);
     }
     let monkey_business = calc_monkey_business(&mut monkeys);
     format!("{}", monkey_business)
@@ -21,9 +27,11 @@ pub fn part1() -> String {
 
 pub fn part2() -> String {
     let contents = get_input_file_contents();
-    let mut monkeys = parse_monkeys(&contents);
+    let monkeys = parse_monkeys(&contents);
+    let worry_reduction = CommonModulus::new(&monkeys);
+    let mut monkeys = with_worry_levels(monkeys, &worry_reduction);
     for _ in 0..10000 {
-        run_round(&mut monkeys, false);
+        run_round(&mut monkeys, &worry_reduction);
     }
     let monkey_business = calc_monkey_business(&mut monkeys);
     format!("{}", monkey_business)
@@ -33,118 +41,241 @@ fn get_input_file_contents() -> String {
     fs::read_to_string("inputs/input11").expect("Failed to open input file")
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Operation {
-    Addition,
-    Multiplication,
+/// An arithmetic expression in terms of the old worry level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expression {
+    Old,
+    Number(u64),
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Operand {
-    Number(u32),
-    InputValue,
+impl Expression {
+    /// Parse an expression made up of `old`, numbers, `+`, `-`, `*` and parentheses.
+    fn parse(input: &str) -> IResult<&str, Self> {
+        let (input, first) = Self::parse_term(input)?;
+        fold_many0(
+            pair(delimited(space0, one_of("+-"), space0), Self::parse_term),
+            move || first.clone(),
+            |lhs, (op, rhs)| match op {
+                '+' => Self::Add(Box::new(lhs), Box::new(rhs)),
+                '-' => Self::Subtract(Box::new(lhs), Box::new(rhs)),
+                _ => unreachable!(),
+            },
+        )(input)
+    }
+
+    fn parse_term(input: &str) -> IResult<&str, Self> {
+        let (input, first) = Self::parse_factor(input)?;
+        fold_many0(
+            preceded(delimited(space0, char('*'), space0), Self::parse_factor),
+            move || first.clone(),
+            |lhs, rhs| Self::Multiply(Box::new(lhs), Box::new(rhs)),
+        )(input)
+    }
+
+    fn parse_factor(input: &str) -> IResult<&str, Self> {
+        alt((
+            map(tag("old"), |_| Self::Old),
+            map(digit1, |num: &str| {
+                Self::Number(num.parse::<u64>().expect("Failed to parse number"))
+            }),
+            delimited(
+                pair(char('('), space0),
+                Self::parse,
+                pair(space0, char(')')),
+            ),
+        ))(input)
+    }
+
+    /// Evaluate the expression, using `number` to convert literals to the worry level type.
+    fn evaluate<T, F>(&self, old: &T, number: &F) -> T
+    where
+        T: Clone + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+        F: Fn(u64) -> T,
+    {
+        match self {
+            Self::Old => old.clone(),
+            Self::Number(num) => number(*num),
+            Self::Add(lhs, rhs) => lhs.evaluate(old, number) + rhs.evaluate(old, number),
+            Self::Subtract(lhs, rhs) => lhs.evaluate(old, number) - rhs.evaluate(old, number),
+            Self::Multiply(lhs, rhs) => lhs.evaluate(old, number) * rhs.evaluate(old, number),
+        }
+    }
 }
 
-#[derive(Debug)]
-struct Modulo {
-    u32_value: u32,
-    u32_is_valid: bool,
-    modulos_are_valid: bool,
-    values: HashMap<u32, u32>,
-}
-
-impl Modulo {
-    fn new(value: u32, modulos: &[u32]) -> Self {
-        let mut values = HashMap::new();
-        for modulo in modulos {
-            values.insert(*modulo, value % modulo);
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Old => write!(f, "old"),
+            Self::Number(num) => write!(f, "{}", num),
+            Self::Add(lhs, rhs) => write!(f, "({} + {})", lhs, rhs),
+            Self::Subtract(lhs, rhs) => write!(f, "({} - {})", lhs, rhs),
+            Self::Multiply(lhs, rhs) => write!(f, "({} * {})", lhs, rhs),
         }
+    }
+}
+
+/// A strategy for keeping worry levels manageable as monkeys inspect items.
+trait WorryReduction {
+    type Level: Clone + fmt::Debug;
+
+    fn level(&self, value: u64) -> Self::Level;
+
+    /// The new worry level after a monkey inspects an item and we're relieved it's undamaged.
+    fn inspect(&self, operation: &Expression, old: &Self::Level) -> Self::Level;
+
+    fn divisible_by(&self, level: &Self::Level, divisor: u64) -> bool;
+}
+
+/// Divide the worry level by a fixed amount after each inspection, rounding down.
+///
+/// Levels are signed so that subtraction can take them below zero.
+#[derive(Debug)]
+struct DivideBy(u64);
+
+impl WorryReduction for DivideBy {
+    type Level = i128;
+
+    fn level(&self, value: u64) -> i128 {
+        value as i128
+    }
+
+    fn inspect(&self, operation: &Expression, old: &i128) -> i128 {
+        operation
+            .evaluate(old, &|num| num as i128)
+            .div_euclid(self.0 as i128)
+    }
+
+    fn divisible_by(&self, level: &i128, divisor: u64) -> bool {
+        level.rem_euclid(divisor as i128) == 0
+    }
+}
+
+/// Keep worry levels modulo the lowest common multiple of all monkeys' divisors, which
+/// preserves every monkey's divisibility test.
+#[derive(Debug)]
+struct CommonModulus(u64);
+
+impl CommonModulus {
+    fn new<L>(monkeys: &[Monkey<L>]) -> Self {
+        Self(monkeys.iter().fold(1, |acc, m| acc.lcm(&m.modulo)))
+    }
+}
+
+/// A worry level modulo some number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Residue {
+    value: u64,
+    modulus: u64,
+}
+
+impl Residue {
+    fn new(value: u64, modulus: u64) -> Self {
         Self {
-            u32_value: value,
-            u32_is_valid: true,
-            modulos_are_valid: true,
-            values,
+            value: value % modulus,
+            modulus,
         }
     }
+}
+
+impl Add for Residue {
+    type Output = Self;
 
-    fn add_modulo(&mut self, modulo: u32, value: u32) {
-        self.values.insert(modulo, value);
+    fn add(self, rhs: Self) -> Self {
+        Self::new(
+            ((self.value as u128 + rhs.value as u128) % self.modulus as u128) as u64,
+            self.modulus,
+        )
     }
+}
 
-    fn divisible_by(&self, modulo: u32) -> bool {
-        if !self.values.contains_key(&modulo) {
-            panic!("Unsupported modulo");
-        }
-        if self.u32_is_valid {
-            self.u32_value % modulo == 0
-        } else if self.modulos_are_valid {
-            self.values[&modulo] == 0
-        } else {
-            panic!("Both u32 and modulo approaches are invalid");
-        }
+impl Sub for Residue {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(
+            ((self.value as u128 + self.modulus as u128 - rhs.value as u128) % self.modulus as u128)
+                as u64,
+            self.modulus,
+        )
     }
+}
 
-    fn add(&mut self, operand: u32) {
-        for (modulo, value) in self.values.iter_mut() {
-            let new_value = *value + operand;
-            *value = new_value % modulo;
-        }
-        let result = self.u32_value.overflowing_add(operand);
-        self.u32_value = result.0;
-        if result.1 {
-            self.u32_is_valid = false;
-        }
+impl Mul for Residue {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            ((self.value as u128 * rhs.value as u128) % self.modulus as u128) as u64,
+            self.modulus,
+        )
     }
+}
 
-    fn multiply(&mut self, operand: u32) {
-        for (modulo, value) in self.values.iter_mut() {
-            let new_value = *value * operand;
-            *value = new_value % modulo;
-        }
-        let result = self.u32_value.overflowing_mul(operand);
-        self.u32_value = result.0;
-        if result.1 {
-            self.u32_is_valid = false;
-        }
+impl WorryReduction for CommonModulus {
+    type Level = Residue;
+
+    fn level(&self, value: u64) -> Residue {
+        Residue::new(value, self.0)
     }
 
-    fn multiply_self(&mut self) {
-        for (modulo, value) in self.values.iter_mut() {
-            let new_value = *value * *value;
-            *value = new_value % modulo;
-        }
-        let result = self.u32_value.overflowing_mul(self.u32_value);
-        self.u32_value = result.0;
-        if result.1 {
-            self.u32_is_valid = false;
-        }
+    fn inspect(&self, operation: &Expression, old: &Residue) -> Residue {
+        operation.evaluate(old, &|num| self.level(num))
     }
 
-    fn divide(&mut self, operand: u32) {
-        for (modulo, value) in self.values.iter_mut() {
-            let new_value = *value / operand;
-            *value = new_value % modulo;
-        }
-        self.modulos_are_valid = false;
-        let result = self.u32_value.overflowing_div(operand);
-        self.u32_value = result.0;
-        if result.1 {
-            self.u32_is_valid = false;
-        }
+    fn divisible_by(&self, level: &Residue, divisor: u64) -> bool {
+        assert_eq!(
+            self.0 % divisor,
+            0,
+            "Divisor {} not in common modulus",
+            divisor
+        );
+        level.value.is_multiple_of(divisor)
     }
 }
 
+/// Don't reduce worry levels at all, and keep them exactly.
+#[allow(dead_code)]
 #[derive(Debug)]
-struct Monkey {
-    items: Vec<Modulo>,
-    operation: Operation,
-    operand: Operand,
-    modulo: u32,
+struct Unbounded;
+
+impl WorryReduction for Unbounded {
+    type Level = BigInt;
+
+    fn level(&self, value: u64) -> BigInt {
+        BigInt::from(value)
+    }
+
+    fn inspect(&self, operation: &Expression, old: &BigInt) -> BigInt {
+        operation.evaluate(old, &BigInt::from)
+    }
+
+    fn divisible_by(&self, level: &BigInt, divisor: u64) -> bool {
+        (level % BigInt::from(divisor)).is_zero()
+    }
+}
+
+#[derive(Debug)]
+struct Monkey<L = u64> {
+    items: Vec<L>,
+    operation: Expression,
+    modulo: u64,
     true_monkey: usize,
     false_monkey: usize,
     items_inspected_count: usize,
 }
 
+/// A single item being inspected and thrown by a monkey.
+#[derive(Debug, PartialEq, Eq)]
+struct Inspection<L> {
+    monkey: usize,
+    old: L,
+    new: L,
+    thrown_to: usize,
+}
+
 impl Monkey {
     fn parse(input: &str) -> IResult<&str, Self> {
         map(
@@ -153,10 +284,8 @@ impl Monkey {
                 digit1::<&str, _>,
                 tag(":\n  Starting items: "),
                 separated_list0(tag(", "), digit1),
-                tag("\n  Operation: new = old "),
-                alt((tag("+"), tag("*"))),
-                tag(" "),
-                alphanumeric1::<&str, _>,
+                tag("\n  Operation: new = "),
+                take_until("\n"),
                 tag("\n  Test: divisible by "),
                 digit1::<&str, _>,
                 tag("\n    If true: throw to monkey "),
@@ -166,46 +295,17 @@ impl Monkey {
                 tag("\n"),
                 opt(tag("\n")),
             )),
-            |(
-                _,
-                _,
-                _,
-                items,
-                _,
-                operation,
-                _,
-                operand,
-                _,
-                modulo,
-                _,
-                true_monkey,
-                _,
-                false_monkey,
-                _,
-                _,
-            )| {
-                let modulo = modulo.parse::<u32>().expect("Failed to parse modulo");
+            |(_, _, _, items, _, operation, _, modulo, _, true_monkey, _, false_monkey, _, _)| {
+                let (_, operation) =
+                    all_consuming(delimited(space0, Expression::parse, space0))(operation)
+                        .expect("Failed to parse operation");
                 Self {
                     items: items
                         .iter()
-                        .map(|i| i.parse::<u32>().expect("Failed to parse item"))
-                        .map(|i| Modulo::new(i, &[modulo]))
-                        .collect::<Vec<Modulo>>(),
-                    operation: match operation {
-                        "+" => Operation::Addition,
-                        "*" => Operation::Multiplication,
-                        _ => unreachable!(),
-                    },
-                    operand: {
-                        if operand == "old" {
-                            Operand::InputValue
-                        } else {
-                            Operand::Number(
-                                operand.parse::<u32>().expect("Failed to parse operand"),
-                            )
-                        }
-                    },
-                    modulo,
+                        .map(|i| i.parse::<u64>().expect("Failed to parse item"))
+                        .collect::<Vec<u64>>(),
+                    operation,
+                    modulo: modulo.parse::<u64>().expect("Failed to parse modulo"),
                     true_monkey: true_monkey
                         .parse::<usize>()
                         .expect("Failed to parse true monkey"),
@@ -217,66 +317,79 @@ impl Monkey {
             },
         )(input)
     }
+}
 
-    fn take_turn(&mut self, reduce_worry: bool) -> HashMap<usize, Vec<Modulo>> {
-        let mut thrown_items: HashMap<usize, Vec<Modulo>> = HashMap::new();
-        for mut item in self.items.drain(0..) {
+impl<L: Clone> Monkey<L> {
+    fn take_turn<W>(&mut self, index: usize, worry_reduction: &W) -> Vec<Inspection<L>>
+    where
+        W: WorryReduction<Level = L>,
+    {
+        let mut inspections = Vec::new();
+        for item in self.items.drain(0..) {
             self.items_inspected_count += 1;
-            match self.operation {
-                Operation::Addition => {
-                    let operand_value = match self.operand {
-                        Operand::Number(num) => num,
-                        _ => panic!("Can't add value to itself"),
-                    };
-                    item.add(operand_value)
-                }
-                Operation::Multiplication => match self.operand {
-                    Operand::Number(num) => item.multiply(num),
-                    Operand::InputValue => item.multiply_self(),
-                },
-            }
-            if reduce_worry {
-                item.divide(3);
-            }
-            if item.divisible_by(self.modulo) {
-                let entry = thrown_items.entry(self.true_monkey).or_default();
-                entry.push(item);
+            let new = worry_reduction.inspect(&self.operation, &item);
+            let thrown_to = if worry_reduction.divisible_by(&new, self.modulo) {
+                self.true_monkey
             } else {
-                let entry = thrown_items.entry(self.false_monkey).or_default();
-                entry.push(item);
-            }
+                self.false_monkey
+            };
+            inspections.push(Inspection {
+                monkey: index,
+                old: item,
+                new,
+                thrown_to,
+            });
         }
-        thrown_items
+        inspections
     }
 }
 
 fn parse_monkeys(input: &str) -> Vec<Monkey> {
-    let (_, mut monkeys) = map(many_till(Monkey::parse, eof), |(monkey, _)| monkey)(input)
+    let (_, monkeys) = map(many_till(Monkey::parse, eof), |(monkey, _)| monkey)(input)
         .expect("Failed to parse monkeys");
-    let modulos = monkeys.iter().map(|m| m.modulo).collect::<Vec<u32>>();
-    for monkey in monkeys.iter_mut() {
-        for item in monkey.items.iter_mut() {
-            for modulo in &modulos {
-                item.add_modulo(*modulo, item.u32_value % modulo);
-            }
-        }
-    }
     monkeys
 }
 
-fn run_round(monkeys: &mut [Monkey], reduce_worry: bool) {
-    let mut i = 0;
-    while i < monkeys.len() {
-        let monkey = &mut monkeys[i];
-        let mut thrown_items = monkey.take_turn(reduce_worry);
-        for (j, items) in thrown_items.drain() {
-            monkeys[j].items.extend(items);
+fn with_worry_levels<W: WorryReduction>(
+    monkeys: Vec<Monkey>,
+    worry_reduction: &W,
+) -> Vec<Monkey<W::Level>> {
+    monkeys
+        .into_iter()
+        .map(|monkey| Monkey {
+            items: monkey
+                .items
+                .into_iter()
+                .map(|item| worry_reduction.level(item))
+                .collect(),
+            operation: monkey.operation,
+            modulo: monkey.modulo,
+            true_monkey: monkey.true_monkey,
+            false_monkey: monkey.false_monkey,
+            items_inspected_count: monkey.items_inspected_count,
+        })
+        .collect()
+}
+
+/// Run a round, and return every inspection made in order.
+fn run_round<W: WorryReduction>(
+    monkeys: &mut [Monkey<W::Level>],
+    worry_reduction: &W,
+) -> Vec<Inspection<W::Level>> {
+    let mut round_inspections = Vec::new();
+    for i in 0..monkeys.len() {
+        let inspections = monkeys[i].take_turn(i, worry_reduction);
+        for inspection in inspections.iter() {
+            monkeys[inspection.thrown_to]
+                .items
+                .push(inspection.new.clone());
         }
-        i += 1;
+        round_inspections.extend(inspections);
     }
+    round_inspections
 }
 
-fn calc_monkey_business(monkeys: &mut [Monkey]) -> usize {
+fn calc_monkey_business<L>(monkeys: &mut [Monkey<L>]) -> usize {
     monkeys.sort_by_key(|m| m.items_inspected_count);
     monkeys[monkeys.len() - 1].items_inspected_count
         * monkeys[monkeys.len() - 2].items_inspected_count
@@ -285,81 +398,97 @@ fn calc_monkey_business(monkeys: &mut [Monkey]) -> usize {
 #[test]
 fn test_parse_monkeys() {
     let contents = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n\nMonkey 1:\n  Starting items: 54, 65, 75, 74\n  Operation: new = old + 6\n  Test: divisible by 19\n    If true: throw to monkey 2\n    If false: throw to monkey 0\n\nMonkey 2:\n  Starting items: 79, 60, 97\n  Operation: new = old * old\n  Test: divisible by 13\n    If true: throw to monkey 1\n    If false: throw to monkey 3\n\nMonkey 3:\n  Starting items: 74\n  Operation: new = old + 3\n  Test: divisible by 17\n    If true: throw to monkey 0\n    If false: throw to monkey 1\n";
-    let monkeys = parse_monkeys(&contents);
+    let monkeys = parse_monkeys(contents);
     assert_eq!(monkeys.len(), 4);
 
+    assert_eq!(monkeys[0].items, vec![79, 98]);
     assert_eq!(
-        monkeys[0]
-            .items
-            .iter()
-            .filter(|i| i.u32_is_valid)
-            .map(|i| i.u32_value)
-            .collect::<Vec<u32>>(),
-        vec![79, 98]
+        monkeys[0].operation,
+        Expression::Multiply(Box::new(Expression::Old), Box::new(Expression::Number(19)))
     );
-    assert_eq!(monkeys[0].operation, Operation::Multiplication);
-    assert_eq!(monkeys[0].operand, Operand::Number(19));
     assert_eq!(monkeys[0].modulo, 23);
     assert_eq!(monkeys[0].true_monkey, 2);
     assert_eq!(monkeys[0].false_monkey, 3);
+    assert_eq!(
+        monkeys[2].operation,
+        Expression::Multiply(Box::new(Expression::Old), Box::new(Expression::Old))
+    );
+}
+
+#[test]
+fn test_parse_expression() {
+    let (rest, expression) = Expression::parse("old * 19").unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(expression.to_string(), "(old * 19)");
+
+    let (rest, expression) = Expression::parse("2 + old * (old - 3) * 5 - 1").unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(
+        expression.to_string(),
+        "((2 + ((old * (old - 3)) * 5)) - 1)"
+    );
+    assert_eq!(expression.evaluate(&10, &|num| num), 2 + 10 * 7 * 5 - 1);
+
+    let (rest, expression) = Expression::parse("old+old\n").unwrap();
+    assert_eq!(rest, "\n");
+    assert_eq!(expression.evaluate(&21, &|num| num), 42);
 }
 
 #[test]
 fn test_take_turn() {
     let contents = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n\nMonkey 1:\n  Starting items: 54, 65, 75, 74\n  Operation: new = old + 6\n  Test: divisible by 19\n    If true: throw to monkey 2\n    If false: throw to monkey 0\n\nMonkey 2:\n  Starting items: 79, 60, 97\n  Operation: new = old * old\n  Test: divisible by 13\n    If true: throw to monkey 1\n    If false: throw to monkey 3\n\nMonkey 3:\n  Starting items: 74\n  Operation: new = old + 3\n  Test: divisible by 17\n    If true: throw to monkey 0\n    If false: throw to monkey 1\n";
-    let mut monkeys = parse_monkeys(&contents);
-    let thrown_items = monkeys[0].take_turn(true);
+    let mut monkeys = with_worry_levels(parse_monkeys(contents), &DivideBy(3));
+    let inspections = monkeys[0].take_turn(0, &DivideBy(3));
 
     assert!(monkeys[0].items.is_empty());
-    assert_eq!(thrown_items.len(), 1);
-    assert!(thrown_items.contains_key(&3));
-    assert_eq!(thrown_items[&3].len(), 2);
     assert_eq!(
-        thrown_items[&3]
-            .iter()
-            .filter(|i| i.u32_is_valid)
-            .map(|i| i.u32_value)
-            .collect::<Vec<u32>>(),
-        vec![500, 620]
+        inspections,
+        vec![
+            Inspection {
+                monkey: 0,
+                old: 79,
+                new: 500,
+                thrown_to: 3
+            },
+            Inspection {
+                monkey: 0,
+                old: 98,
+                new: 620,
+                thrown_to: 3
+            },
+        ]
     );
 }
 
 #[test]
 fn test_run_round() {
     let contents = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n\nMonkey 1:\n  Starting items: 54, 65, 75, 74\n  Operation: new = old + 6\n  Test: divisible by 19\n    If true: throw to monkey 2\n    If false: throw to monkey 0\n\nMonkey 2:\n  Starting items: 79, 60, 97\n  Operation: new = old * old\n  Test: divisible by 13\n    If true: throw to monkey 1\n    If false: throw to monkey 3\n\nMonkey 3:\n  Starting items: 74\n  Operation: new = old + 3\n  Test: divisible by 17\n    If true: throw to monkey 0\n    If false: throw to monkey 1\n";
-    let mut monkeys = parse_monkeys(&contents);
-    run_round(&mut monkeys, true);
+    let mut monkeys = with_worry_levels(parse_monkeys(contents), &DivideBy(3));
+    let inspections = run_round(&mut monkeys, &DivideBy(3));
 
-    assert_eq!(monkeys[0].items.len(), 4);
-    assert_eq!(
-        monkeys[0]
-            .items
-            .iter()
-            .filter(|i| i.u32_is_valid)
-            .map(|i| i.u32_value)
-            .collect::<Vec<u32>>(),
-        vec![20, 23, 27, 26]
-    );
-    assert_eq!(monkeys[1].items.len(), 6);
-    assert_eq!(
-        monkeys[1]
-            .items
-            .iter()
-            .filter(|i| i.u32_is_valid)
-            .map(|i| i.u32_value)
-            .collect::<Vec<u32>>(),
-        vec![2080, 25, 167, 207, 401, 1046]
-    );
+    assert_eq!(monkeys[0].items, vec![20, 23, 27, 26]);
+    assert_eq!(monkeys[1].items, vec![2080, 25, 167, 207, 401, 1046]);
     assert!(monkeys[2].items.is_empty());
     assert!(monkeys[3].items.is_empty());
+
+    assert_eq!(inspections.len(), 14);
+    assert_eq!(
+        inspections[6],
+        Inspection {
+            monkey: 2,
+            old: 79,
+            new: 2080,
+            thrown_to: 1
+        }
+    );
 }
 
 #[test]
 fn test_items_inspected_count() {
     let contents = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n\nMonkey 1:\n  Starting items: 54, 65, 75, 74\n  Operation: new = old + 6\n  Test: divisible by 19\n    If true: throw to monkey 2\n    If false: throw to monkey 0\n\nMonkey 2:\n  Starting items: 79, 60, 97\n  Operation: new = old * old\n  Test: divisible by 13\n    If true: throw to monkey 1\n    If false: throw to monkey 3\n\nMonkey 3:\n  Starting items: 74\n  Operation: new = old + 3\n  Test: divisible by 17\n    If true: throw to monkey 0\n    If false: throw to monkey 1\n";
-    let mut monkeys = parse_monkeys(&contents);
+    let mut monkeys = with_worry_levels(parse_monkeys(contents), &DivideBy(3));
     for _ in 0..20 {
-        run_round(&mut monkeys, true);
+        run_round(&mut monkeys, &DivideBy(3));
     }
     assert_eq!(monkeys[0].items_inspected_count, 101);
     assert_eq!(monkeys[1].items_inspected_count, 95);
@@ -370,16 +499,18 @@ fn test_items_inspected_count() {
 #[test]
 fn test_items_inspected_count_stages() {
     let contents = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n\nMonkey 1:\n  Starting items: 54, 65, 75, 74\n  Operation: new = old + 6\n  Test: divisible by 19\n    If true: throw to monkey 2\n    If false: throw to monkey 0\n\nMonkey 2:\n  Starting items: 79, 60, 97\n  Operation: new = old * old\n  Test: divisible by 13\n    If true: throw to monkey 1\n    If false: throw to monkey 3\n\nMonkey 3:\n  Starting items: 74\n  Operation: new = old + 3\n  Test: divisible by 17\n    If true: throw to monkey 0\n    If false: throw to monkey 1\n";
-    let mut monkeys = parse_monkeys(&contents);
+    let monkeys = parse_monkeys(contents);
+    let worry_reduction = CommonModulus::new(&monkeys);
+    let mut monkeys = with_worry_levels(monkeys, &worry_reduction);
 
-    run_round(&mut monkeys, false);
+    run_round(&mut monkeys, &worry_reduction);
     assert_eq!(monkeys[0].items_inspected_count, 2);
     assert_eq!(monkeys[1].items_inspected_count, 4);
     assert_eq!(monkeys[2].items_inspected_count, 3);
     assert_eq!(monkeys[3].items_inspected_count, 6);
 
     for _ in 0..19 {
-        run_round(&mut monkeys, false);
+        run_round(&mut monkeys, &worry_reduction);
     }
     assert_eq!(monkeys[0].items_inspected_count, 99);
     assert_eq!(monkeys[1].items_inspected_count, 97);
@@ -387,43 +518,103 @@ fn test_items_inspected_count_stages() {
     assert_eq!(monkeys[3].items_inspected_count, 103);
 }
 
+#[test]
+fn test_unbounded_matches_common_modulus() {
+    let contents = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n\nMonkey 1:\n  Starting items: 54, 65, 75, 74\n  Operation: new = old + 6\n  Test: divisible by 19\n    If true: throw to monkey 2\n    If false: throw to monkey 0\n\nMonkey 2:\n  Starting items: 79, 60, 97\n  Operation: new = old * old\n  Test: divisible by 13\n    If true: throw to monkey 1\n    If false: throw to monkey 3\n\nMonkey 3:\n  Starting items: 74\n  Operation: new = old + 3\n  Test: divisible by 17\n    If true: throw to monkey 0\n    If false: throw to monkey 1\n";
+    let monkeys = parse_monkeys(contents);
+    let common_modulus = CommonModulus::new(&monkeys);
+    let mut modulo_monkeys = with_worry_levels(monkeys, &common_modulus);
+    let mut unbounded_monkeys = with_worry_levels(parse_monkeys(contents), &Unbounded);
+
+    for _ in 0..10 {
+        let modulo_inspections = run_round(&mut modulo_monkeys, &common_modulus);
+        let unbounded_inspections = run_round(&mut unbounded_monkeys, &Unbounded);
+        assert_eq!(modulo_inspections.len(), unbounded_inspections.len());
+        for (a, b) in modulo_inspections.iter().zip(unbounded_inspections.iter()) {
+            assert_eq!(a.thrown_to, b.thrown_to);
+            assert_eq!(
+                BigInt::from(a.new.value),
+                &b.new % BigInt::from(common_modulus.0)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_what_if_operation() {
+    // The example, with monkey 3's operation replaced by something more complex
+    let contents = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n\nMonkey 1:\n  Starting items: 54, 65, 75, 74\n  Operation: new = old + 6\n  Test: divisible by 19\n    If true: throw to monkey 2\n    If false: throw to monkey 0\n\nMonkey 2:\n  Starting items: 79, 60, 97\n  Operation: new = old * old\n  Test: divisible by 13\n    If true: throw to monkey 1\n    If false: throw to monkey 3\n\nMonkey 3:\n  Starting items: 74\n  Operation: new = old + 3\n  Test: divisible by 17\n    If true: throw to monkey 0\n    If false: throw to monkey 1\n";
+    let contents = contents.replace("new = old + 3", "new = (old + 3) * (old - 1)");
+    let monkeys = parse_monkeys(&contents);
+    let common_modulus = CommonModulus::new(&monkeys);
+    let mut modulo_monkeys = with_worry_levels(monkeys, &common_modulus);
+    let mut unbounded_monkeys = with_worry_levels(parse_monkeys(&contents), &Unbounded);
+
+    for _ in 0..5 {
+        run_round(&mut modulo_monkeys, &common_modulus);
+        run_round(&mut unbounded_monkeys, &Unbounded);
+    }
+    for (a, b) in modulo_monkeys.iter().zip(unbounded_monkeys.iter()) {
+        assert_eq!(a.items_inspected_count, b.items_inspected_count);
+    }
+}
+
 #[test]
 fn test_monkey_business() {
     let contents = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n\nMonkey 1:\n  Starting items: 54, 65, 75, 74\n  Operation: new = old + 6\n  Test: divisible by 19\n    If true: throw to monkey 2\n    If false: throw to monkey 0\n\nMonkey 2:\n  Starting items: 79, 60, 97\n  Operation: new = old * old\n  Test: divisible by 13\n    If true: throw to monkey 1\n    If false: throw to monkey 3\n\nMonkey 3:\n  Starting items: 74\n  Operation: new = old + 3\n  Test: divisible by 17\n    If true: throw to monkey 0\n    If false: throw to monkey 1\n";
-    let mut monkeys = parse_monkeys(&contents);
+    let mut monkeys = with_worry_levels(parse_monkeys(contents), &DivideBy(3));
     for _ in 0..20 {
-        run_round(&mut monkeys, true);
+        run_round(&mut monkeys, &DivideBy(3));
     }
     let monkey_business = calc_monkey_business(&mut monkeys);
     assert_eq!(monkey_business, 10605);
 }
 
 #[test]
-fn test_modulo() {
-    let modulos = vec![3, 7, 11];
-    let mut a = Modulo::new(9, &modulos);
-
-    assert!(a.divisible_by(3));
-    assert!(!a.divisible_by(7));
-    assert!(!a.divisible_by(11));
-
-    a.add(2);
-    assert!(!a.divisible_by(3));
-    assert!(!a.divisible_by(7));
-    assert!(a.divisible_by(11));
+fn test_residue() {
+    let a = Residue::new(9, 3 * 7 * 11);
+    let divisible_by = |r: Residue, d: u64| r.value.is_multiple_of(d);
+
+    assert!(divisible_by(a, 3));
+    assert!(!divisible_by(a, 7));
+    assert!(!divisible_by(a, 11));
+
+    let a = a + Residue::new(2, 3 * 7 * 11);
+    assert!(!divisible_by(a, 3));
+    assert!(!divisible_by(a, 7));
+    assert!(divisible_by(a, 11));
+
+    let a = a * Residue::new(7, 3 * 7 * 11);
+    assert!(!divisible_by(a, 3));
+    assert!(divisible_by(a, 7));
+    assert!(divisible_by(a, 11));
+
+    let a = a - Residue::new(78, 3 * 7 * 11);
+    assert_eq!(a.value, 231 - 1);
+
+    // Big enough that adding the modulus would overflow a u64
+    let modulus = u64::MAX - 4;
+    let a = Residue::new(3, modulus) - Residue::new(modulus - 2, modulus);
+    assert_eq!(a.value, 5);
+}
 
-    a.multiply(7);
-    assert!(!a.divisible_by(3));
-    assert!(a.divisible_by(7));
-    assert!(a.divisible_by(11));
+#[test]
+fn test_divide_by_below_zero() {
+    let (_, expression) = Expression::parse("old - 10").unwrap();
+    assert_eq!(DivideBy(3).inspect(&expression, &3), -3);
+    assert_eq!(DivideBy(3).inspect(&expression, &16), 2);
+    assert!(DivideBy(3).divisible_by(&-3, 3));
+    assert!(!DivideBy(3).divisible_by(&-2, 3));
 }
 
 #[test]
 fn test_worrisome_monkey_business() {
     let contents = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n\nMonkey 1:\n  Starting items: 54, 65, 75, 74\n  Operation: new = old + 6\n  Test: divisible by 19\n    If true: throw to monkey 2\n    If false: throw to monkey 0\n\nMonkey 2:\n  Starting items: 79, 60, 97\n  Operation: new = old * old\n  Test: divisible by 13\n    If true: throw to monkey 1\n    If false: throw to monkey 3\n\nMonkey 3:\n  Starting items: 74\n  Operation: new = old + 3\n  Test: divisible by 17\n    If true: throw to monkey 0\n    If false: throw to monkey 1\n";
-    let mut monkeys = parse_monkeys(&contents);
+    let monkeys = parse_monkeys(contents);
+    let worry_reduction = CommonModulus::new(&monkeys);
+    let mut monkeys = with_worry_levels(monkeys, &worry_reduction);
     for _ in 0..10000 {
-        run_round(&mut monkeys, false);
+        run_round(&mut monkeys, &worry_reduction);
     }
     let monkey_business = calc_monkey_business(&mut monkeys);
     assert_eq!(monkey_business, 2713310158);
@@ -1,3 +1,4 @@
+use intervals::{Interval, IntervalSet};
 use nom::{
     bytes::complete::tag,
     character::complete::{digit1, newline},
@@ -90,28 +91,17 @@ impl Sensor {
         self.position.manhatten_dist(&self.nearest_beacon)
     }
 
-    fn row_coverage(&self, y: i32) -> Option<(i32, i32)> {
-        // The range of x coordinates (inclusive) of the given row that this beacon can see
+    fn row_coverage(&self, y: i32) -> Option<Interval> {
+        // The range of x coordinates of the given row that this beacon can see
         let distance_to_beacon = self.distance_to_beacon() as i32;
         let distance_to_row = (y - self.position.y).abs();
-        let range = (
-            self.position.x - distance_to_beacon + distance_to_row,
-            self.position.x + distance_to_beacon - distance_to_row,
-        );
-        if range.1 >= range.0 {
-            Some(range)
-        } else {
-            None
-        }
+        Interval::checked(
+            (self.position.x - distance_to_beacon + distance_to_row).into(),
+            (self.position.x + distance_to_beacon - distance_to_row).into(),
+        )
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum RangePoint {
-    Start(i32),
-    End(i32),
-}
-
 fn parse_sensors(contents: &str) -> Vec<Sensor> {
     let (rest, sensors) =
         terminated(separated_list1(newline, Sensor::parse), opt(newline))(contents)
@@ -120,101 +110,27 @@ fn parse_sensors(contents: &str) -> Vec<Sensor> {
     sensors
 }
 
-fn combine_ranges(ranges: &[(i32, i32)]) -> Vec<(i32, i32)> {
-    let mut points = Vec::new();
-    for (start, end) in ranges {
-        points.push(RangePoint::Start(*start));
-        points.push(RangePoint::End(*end));
-    }
-    points.sort_by_key(|p| match p {
-        RangePoint::Start(val) => (*val, 0),
-        RangePoint::End(val) => (*val, 1),
-    });
-
-    let mut disjoint_ranges: Vec<(i32, i32)> = Vec::new();
-    let mut current_start = None;
-    let mut range_count = 0;
-    for point in points {
-        match point {
-            RangePoint::Start(start) => {
-                range_count += 1;
-                if let Some(last_range) = disjoint_ranges.last() {
-                    if last_range.1 == start - 1 {
-                        // End of previous range is start of next range, so combine them
-                        current_start = Some(last_range.0);
-                        disjoint_ranges.pop();
-                    } else if current_start.is_none() {
-                        current_start = Some(start);
-                    }
-                } else if current_start.is_none() {
-                    current_start = Some(start);
-                }
-            }
-            RangePoint::End(end) => {
-                range_count -= 1;
-                if range_count == 0 {
-                    disjoint_ranges.push((current_start.unwrap(), end));
-                    current_start = None;
-                }
-            }
-        }
-    }
-    disjoint_ranges
-}
-
-fn measure_ranges(ranges: &[(i32, i32)]) -> u32 {
-    let disjoint_ranges = combine_ranges(ranges);
-    disjoint_ranges
-        .iter()
-        .map(|(start, end)| (end - start + 1) as u32)
-        .sum::<u32>()
-}
-
-fn row_coverage_ranges(y: i32, sensors: &[Sensor]) -> Vec<(i32, i32)> {
-    sensors
-        .iter()
-        .filter_map(|s| s.row_coverage(y))
-        .collect::<Vec<(i32, i32)>>()
+fn row_coverage_ranges(y: i32, sensors: &[Sensor]) -> IntervalSet {
+    sensors.iter().filter_map(|s| s.row_coverage(y)).collect()
 }
 
-fn row_coverage(y: i32, sensors: &[Sensor]) -> u32 {
-    let coverage_ranges = row_coverage_ranges(y, sensors);
-    let ranges_size = measure_ranges(&coverage_ranges);
+fn row_coverage(y: i32, sensors: &[Sensor]) -> u64 {
+    let coverage = row_coverage_ranges(y, sensors);
     let beacon_locations = sensors
         .iter()
         .filter(|s| s.nearest_beacon.y == y)
         .map(|s| s.nearest_beacon.y)
         .collect::<HashSet<i32>>();
-    ranges_size - beacon_locations.len() as u32
-}
-
-fn range_overlap(a: (i32, i32), b: (i32, i32)) -> Option<(i32, i32)> {
-    let start = a.0.max(b.0);
-    let end = a.1.min(b.1);
-    if start <= end {
-        Some((start, end))
-    } else {
-        None
-    }
+    coverage.len() - beacon_locations.len() as u64
 }
 
 fn beacon_location(max_coords: i32, sensors: &[Sensor]) -> Point {
+    let search_area = IntervalSet::from(Interval::new(0, max_coords.into()));
     for y in 0..max_coords {
-        let ranges = row_coverage_ranges(y, sensors);
-        let disjoint_ranges = combine_ranges(&ranges);
-        let mut restricted_ranges = Vec::new();
-        for range in &disjoint_ranges {
-            let restricted_range = range_overlap((0, max_coords), *range);
-            if let Some(restricted_range) = restricted_range {
-                restricted_ranges.push(restricted_range);
-            }
-        }
-        if restricted_ranges.len() == 1 && restricted_ranges[0] == (0, max_coords) {
-            continue;
-        }
-        if !restricted_ranges.is_empty() {
-            assert_eq!(restricted_ranges.len(), 2);
-            return Point::new(restricted_ranges[0].1 + 1, y);
+        let uncovered = search_area.difference(&row_coverage_ranges(y, sensors));
+        if let Some(gap) = uncovered.intervals().first() {
+            assert_eq!(uncovered.len(), 1);
+            return Point::new(gap.start as i32, y);
         }
     }
     panic!("Failed to locate beacon");
@@ -303,7 +219,7 @@ fn test_sensor_row_coverage() {
     let sensor = &sensors[0];
     let coverage = sensor.row_coverage(10);
     assert!(coverage.is_some());
-    assert_eq!(coverage.unwrap(), (2, 14));
+    assert_eq!(coverage.unwrap(), Interval::new(2, 14));
 }
 
 #[test]
@@ -315,17 +231,25 @@ fn test_row_coverage() {
 }
 
 #[test]
-fn test_measure_ranges() {
-    let ranges = vec![(-2, 0), (-1, 3), (5, 7)];
-    let result = measure_ranges(&ranges);
-    assert_eq!(result, 9);
+fn test_interval_set_len() {
+    let ranges = [(-2, 0), (-1, 3), (5, 7)];
+    let result = ranges
+        .into_iter()
+        .map(|(start, end)| Interval::new(start, end))
+        .collect::<IntervalSet>();
+    assert_eq!(result.len(), 9);
 }
 
 #[test]
-fn test_range_overlap() {
-    assert_eq!(range_overlap((-2, 24), (0, 20)), Some((0, 20)));
-    assert_eq!(range_overlap((-2, 10), (11, 20)), None);
-    assert_eq!(range_overlap((-2, 10), (0, 20)), Some((0, 10)));
+fn test_interval_intersection() {
+    let overlap = |a: (i64, i64), b: (i64, i64)| {
+        Interval::new(a.0, a.1)
+            .intersection(&Interval::new(b.0, b.1))
+            .map(|i| (i.start, i.end))
+    };
+    assert_eq!(overlap((-2, 24), (0, 20)), Some((0, 20)));
+    assert_eq!(overlap((-2, 10), (11, 20)), None);
+    assert_eq!(overlap((-2, 10), (0, 20)), Some((0, 10)));
 }
 
 #[test]
@@ -1,21 +1,17 @@
-use std::{
-    collections::{HashMap, HashSet},
-    fs,
-};
+use std::{collections::HashMap, fs};
 
 pub fn part1() -> String {
     let contents = get_input_file_contents();
     let volcano = Volcano::parse(&contents);
-    let most_pressure_released = find_most_pressure_released(State::new(&volcano), &volcano);
-    format!("{}", most_pressure_released)
+    let plan = find_best_plan(&volcano, 1, 30);
+    format!("{}", plan.pressure_released)
 }
 
 pub fn part2() -> String {
     let contents = get_input_file_contents();
     let volcano = Volcano::parse(&contents);
-    let most_pressure_released =
-        find_most_pressure_released_with_elephant(StateWithElephant::new(&volcano), &volcano);
-    format!("{}", most_pressure_released)
+    let plan = find_best_plan(&volcano, 2, 26);
+    format!("{}", plan.pressure_released)
 }
 
 fn get_input_file_contents() -> String {
@@ -152,296 +148,169 @@ impl Volcano {
         path
     }
 
-    fn shortest_path(&self, start: usize, end: usize) -> Vec<usize> {
-        self.shortest_paths[&(start, end)].clone()
-    }
-
     fn shortest_path_length(&self, start: usize, end: usize) -> u32 {
         self.shortest_paths[&(start, end)].len() as u32 - 1
     }
 }
 
-fn calc_possible_pressure_releases(
-    current_valve: usize,
-    open_valves: &HashSet<usize>,
-    time_remaining: u32,
-    volcano: &Volcano,
-) -> Vec<u32> {
-    let mut possible_pressure_releases = Vec::with_capacity(volcano.flow_rates.len());
-    for (valve, flow_rate) in volcano.flow_rates.iter().enumerate() {
-        if *flow_rate == 0 {
-            possible_pressure_releases.push(0);
-            continue;
-        }
-        if open_valves.contains(&valve) {
-            possible_pressure_releases.push(0);
-            continue;
-        }
-        let shortest_path = volcano.shortest_path_length(current_valve, valve);
-        if shortest_path >= time_remaining {
-            possible_pressure_releases.push(0);
-            continue;
-        }
-        let possible_pressure_release = (time_remaining - shortest_path - 1) * flow_rate;
-        possible_pressure_releases.push(possible_pressure_release);
-    }
-    possible_pressure_releases
+/// The valves with nonzero flow rate, and the travel times between them.
+#[derive(Debug)]
+struct ValveGraph {
+    /// Index in the volcano of each valve worth opening.
+    valves: Vec<usize>,
+    flow_rates: Vec<u32>,
+    /// Minutes to travel between valves, where the last row and column are the initial valve.
+    distances: Vec<Vec<u32>>,
 }
 
-#[derive(Debug, Clone)]
-struct State {
-    time_remaining: u32,
-    current_valve: usize,
-    open_valves: HashSet<usize>,
+/// The best way for a group of agents to release pressure.
+#[derive(Debug, PartialEq, Eq)]
+struct Plan {
     pressure_released: u32,
+    /// For each agent, the valves it opens in order, and the minute at the end of which each
+    /// is opened.
+    routes: Vec<Vec<(usize, u32)>>,
 }
 
-impl State {
-    fn new(volcano: &Volcano) -> Self {
-        Self {
-            time_remaining: 30,
-            current_valve: volcano.initial_valve,
-            open_valves: HashSet::new(),
-            pressure_released: 0,
-        }
-    }
-}
-
-fn find_most_pressure_released(state: State, volcano: &Volcano) -> u32 {
-    let possible_pressure_releases = calc_possible_pressure_releases(
-        state.current_valve,
-        &state.open_valves,
-        state.time_remaining,
-        volcano,
-    );
+/// The most pressure a single agent can release by opening exactly a given set of valves,
+/// and the route it takes to do so.
+type SubsetBest = Option<(u32, Vec<(usize, u32)>)>;
 
-    let mut best_result = None;
-    for (possible_valve, pressure_release) in possible_pressure_releases.iter().enumerate() {
-        if *pressure_release == 0 {
-            continue;
-        }
+impl ValveGraph {
+    fn new(volcano: &Volcano) -> Self {
+        let valves = (0..volcano.flow_rates.len())
+            .filter(|valve| volcano.flow_rates[*valve] > 0)
+            .collect::<Vec<usize>>();
+        assert!(valves.len() < 32, "Too many valves to track in a bitmask");
+        let flow_rates = valves
+            .iter()
+            .map(|valve| volcano.flow_rates[*valve])
+            .collect();
 
-        let mut new_state = state.clone();
-        new_state.time_remaining -=
-            volcano.shortest_path_length(new_state.current_valve, possible_valve) + 1;
-        new_state.current_valve = possible_valve;
-        new_state.pressure_released += pressure_release;
-        new_state.open_valves.insert(possible_valve);
+        let positions = valves
+            .iter()
+            .copied()
+            .chain(std::iter::once(volcano.initial_valve))
+            .collect::<Vec<usize>>();
+        let distances = positions
+            .iter()
+            .map(|start| {
+                positions
+                    .iter()
+                    .map(|end| volcano.shortest_path_length(*start, *end))
+                    .collect()
+            })
+            .collect();
 
-        let result = find_most_pressure_released(new_state, volcano);
-        if best_result.is_none() || result > best_result.unwrap() {
-            best_result = Some(result);
+        Self {
+            valves,
+            flow_rates,
+            distances,
         }
     }
-    best_result.unwrap_or(state.pressure_released)
-}
-
-#[derive(Debug, Clone)]
-struct StateWithElephant {
-    time_remaining: u32,
-    current_valve: usize,
-    current_path: Vec<usize>,
-    current_valve_to_open: Option<usize>,
-    elephant_valve: usize,
-    elephant_path: Vec<usize>,
-    elephant_valve_to_open: Option<usize>,
-    open_valves: HashSet<usize>,
-    pressure_released: u32,
-}
 
-impl StateWithElephant {
-    fn new(volcano: &Volcano) -> Self {
-        Self {
-            time_remaining: 26,
-            current_valve: volcano.initial_valve,
-            current_path: Vec::new(),
-            current_valve_to_open: None,
-            elephant_valve: volcano.initial_valve,
-            elephant_path: Vec::new(),
-            elephant_valve_to_open: None,
-            open_valves: HashSet::new(),
-            pressure_released: 0,
-        }
+    /// Find the best result for each set of valves a single agent could open in time.
+    fn best_per_subset(&self, time: u32) -> Vec<SubsetBest> {
+        let mut best = vec![None; 1 << self.valves.len()];
+        let mut route = Vec::new();
+        self.visit(self.valves.len(), 0, time, 0, time, &mut route, &mut best);
+        best
     }
-}
 
-fn find_most_pressure_released_with_elephant(state: StateWithElephant, volcano: &Volcano) -> u32 {
-    if (state.current_path.is_empty() && state.current_valve_to_open.is_none())
-        || (state.elephant_path.is_empty() && state.elephant_valve_to_open.is_none())
-    {
-        // Either us or the elephant have nothing to do, so we need to explore choices
-        let our_choices = if state.current_path.is_empty() && state.current_valve_to_open.is_none()
+    #[allow(clippy::too_many_arguments)]
+    fn visit(
+        &self,
+        position: usize,
+        open_valves: usize,
+        time_remaining: u32,
+        pressure_released: u32,
+        time: u32,
+        route: &mut Vec<(usize, u32)>,
+        best: &mut [SubsetBest],
+    ) {
+        if best[open_valves]
+            .as_ref()
+            .is_none_or(|(pressure, _)| pressure_released > *pressure)
         {
-            calc_possible_pressure_releases(
-                state.current_valve,
-                &state.open_valves,
-                state.time_remaining,
-                volcano,
-            )
-        } else {
-            Vec::new()
-        };
-        let elephant_choices =
-            if state.elephant_path.is_empty() && state.elephant_valve_to_open.is_none() {
-                calc_possible_pressure_releases(
-                    state.elephant_valve,
-                    &state.open_valves,
-                    state.time_remaining,
-                    volcano,
-                )
-            } else {
-                Vec::new()
-            };
-
-        // Remove choices that won't release any pressure
-        let our_choices = our_choices
-            .iter()
-            .enumerate()
-            .filter(|(_, pressure_release)| **pressure_release > 0)
-            .map(|(valve, pressure_release)| (valve, *pressure_release))
-            .collect::<Vec<(usize, u32)>>();
-        let elephant_choices = elephant_choices
-            .iter()
-            .enumerate()
-            .filter(|(_, pressure_release)| **pressure_release > 0)
-            .map(|(valve, pressure_release)| (valve, *pressure_release))
-            .collect::<Vec<(usize, u32)>>();
-
-        if our_choices.is_empty() && elephant_choices.is_empty() {
-            // We're done
-            state.pressure_released
-        } else if our_choices.is_empty() {
-            // Elephant choices only
-            let mut best_result = None;
-            for (possible_valve, pressure_release) in elephant_choices.iter() {
-                if *pressure_release == 0 {
-                    continue;
-                }
-
-                let mut new_state = state.clone();
-                new_state.time_remaining -= 1;
-
-                // Move elephant one step along the path to the chosen valve
-                let mut path = volcano.shortest_path(state.elephant_valve, *possible_valve);
-                path.remove(0);
-                new_state.elephant_valve = path.remove(0);
-                new_state.pressure_released += pressure_release;
-                new_state.open_valves.insert(*possible_valve);
-                new_state.elephant_valve_to_open = Some(*possible_valve);
-                new_state.elephant_path = path;
-
-                if !state.current_path.is_empty() {
-                    new_state.current_valve = new_state.current_path.remove(0);
-                } else if let Some(current_valve_to_open) = state.current_valve_to_open {
-                    assert_eq!(state.current_valve, current_valve_to_open);
-                    new_state.current_valve_to_open = None;
-                }
+            best[open_valves] = Some((pressure_released, route.clone()));
+        }
 
-                let result = find_most_pressure_released_with_elephant(new_state, volcano);
-                if best_result.is_none() || result > best_result.unwrap() {
-                    best_result = Some(result);
-                }
+        for valve in 0..self.valves.len() {
+            if open_valves & (1 << valve) != 0 {
+                continue;
             }
-            best_result.unwrap_or(state.pressure_released)
-        } else if elephant_choices.is_empty() {
-            // Our choices only
-            let mut best_result = None;
-            for (possible_valve, pressure_release) in our_choices.iter() {
-                if *pressure_release == 0 {
-                    continue;
-                }
-
-                let mut new_state = state.clone();
-                new_state.time_remaining -= 1;
-
-                // Move us one step along the path to the chosen valve
-                let mut path = volcano.shortest_path(state.current_valve, *possible_valve);
-                path.remove(0);
-                new_state.current_valve = path.remove(0);
-                new_state.pressure_released += pressure_release;
-                new_state.open_valves.insert(*possible_valve);
-                new_state.current_valve_to_open = Some(*possible_valve);
-                new_state.current_path = path;
-
-                if !state.elephant_path.is_empty() {
-                    new_state.elephant_valve = new_state.elephant_path.remove(0);
-                } else if let Some(elephant_valve_to_open) = state.elephant_valve_to_open {
-                    assert_eq!(state.elephant_valve, elephant_valve_to_open);
-                    new_state.elephant_valve_to_open = None;
-                }
-
-                let result = find_most_pressure_released_with_elephant(new_state, volcano);
-                if best_result.is_none() || result > best_result.unwrap() {
-                    best_result = Some(result);
-                }
+            // Travel to the valve, then spend a minute opening it
+            let cost = self.distances[position][valve] + 1;
+            if cost >= time_remaining {
+                continue;
             }
-            best_result.unwrap_or(state.pressure_released)
-        } else {
-            // Both have choices, need to combine them to get all combinations of choices
-            let mut choices = Vec::new();
-            for (our_possible_valve, our_pressure_release) in our_choices.iter() {
-                for (elephant_possible_valve, elephant_pressure_release) in our_choices.iter() {
-                    if *our_possible_valve == *elephant_possible_valve {
-                        // Can't both turn on the same valve
-                        continue;
+            let time_remaining = time_remaining - cost;
+            route.push((self.valves[valve], time - time_remaining));
+            self.visit(
+                valve,
+                open_valves | (1 << valve),
+                time_remaining,
+                pressure_released + time_remaining * self.flow_rates[valve],
+                time,
+                route,
+                best,
+            );
+            route.pop();
+        }
+    }
+}
+
+/// Find the most pressure that can be released by some number of agents working together
+/// for the given number of minutes, all starting at the initial valve.
+fn find_best_plan(volcano: &Volcano, agents: usize, time: u32) -> Plan {
+    let graph = ValveGraph::new(volcano);
+    let best = graph.best_per_subset(time);
+    let full_set = (1 << graph.valves.len()) - 1;
+
+    // most_released[k][set] is the most pressure k agents can release by opening valves only
+    // from the given set, and choices[k][set] is the set opened by the kth agent to do so.
+    let mut most_released = vec![vec![0; full_set + 1]];
+    let mut choices = vec![vec![0; full_set + 1]];
+    for k in 1..=agents {
+        let mut released = vec![0; full_set + 1];
+        let mut chosen = vec![0; full_set + 1];
+        for set in 0..=full_set {
+            // Iterate over all subsets of the set
+            let mut subset = set;
+            loop {
+                if let Some((pressure, _)) = &best[subset] {
+                    let total = pressure + most_released[k - 1][set ^ subset];
+                    if total > released[set] {
+                        released[set] = total;
+                        chosen[set] = subset;
                     }
-                    choices.push((
-                        (*our_possible_valve, *our_pressure_release),
-                        (*elephant_possible_valve, *elephant_pressure_release),
-                    ));
                 }
-            }
-
-            let mut best_result = None;
-            for (our_choice, elephant_choice) in choices.iter() {
-                let mut new_state = state.clone();
-                new_state.time_remaining -= 1;
-
-                // Move us one step along the path to the chosen valve
-                let mut path = volcano.shortest_path(state.current_valve, our_choice.0);
-                path.remove(0);
-                new_state.current_valve = path.remove(0);
-                new_state.pressure_released += our_choice.1;
-                new_state.open_valves.insert(our_choice.0);
-                new_state.current_valve_to_open = Some(our_choice.0);
-                new_state.current_path = path;
-
-                // Move elephant one step along the path to the chosen valve
-                let mut path = volcano.shortest_path(state.elephant_valve, elephant_choice.0);
-                path.remove(0);
-                new_state.elephant_valve = path.remove(0);
-                new_state.pressure_released += elephant_choice.1;
-                new_state.open_valves.insert(elephant_choice.0);
-                new_state.elephant_valve_to_open = Some(elephant_choice.0);
-                new_state.elephant_path = path;
-
-                let result = find_most_pressure_released_with_elephant(new_state, volcano);
-                if best_result.is_none() || result > best_result.unwrap() {
-                    best_result = Some(result);
+                if subset == 0 {
+                    break;
                 }
+                subset = (subset - 1) & set;
             }
-            best_result.unwrap_or(state.pressure_released)
-        }
-    } else {
-        // Both us and the elephant are doing something, so just continue on
-        let mut new_state = state.clone();
-        new_state.time_remaining -= 1;
-
-        if !state.current_path.is_empty() {
-            new_state.current_valve = new_state.current_path.remove(0);
-        } else if let Some(current_valve_to_open) = state.current_valve_to_open {
-            assert_eq!(state.current_valve, current_valve_to_open);
-            new_state.current_valve_to_open = None;
         }
+        most_released.push(released);
+        choices.push(chosen);
+    }
 
-        if !state.elephant_path.is_empty() {
-            new_state.elephant_valve = new_state.elephant_path.remove(0);
-        } else if let Some(elephant_valve_to_open) = state.elephant_valve_to_open {
-            assert_eq!(state.elephant_valve, elephant_valve_to_open);
-            new_state.elephant_valve_to_open = None;
-        }
+    let mut routes = Vec::with_capacity(agents);
+    let mut set = full_set;
+    for k in (1..=agents).rev() {
+        let subset = choices[k][set];
+        routes.push(
+            best[subset]
+                .as_ref()
+                .map(|(_, route)| route.clone())
+                .unwrap_or_default(),
+        );
+        set ^= subset;
+    }
 
-        find_most_pressure_released_with_elephant(new_state, volcano)
+    Plan {
+        pressure_released: most_released[agents][full_set],
+        routes,
     }
 }
 
@@ -505,15 +374,87 @@ fn test_calc_shortest_path() {
 fn test_find_most_pressure_released() {
     let contents = "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB\nValve BB has flow rate=13; tunnels lead to valves CC, AA\nValve CC has flow rate=2; tunnels lead to valves DD, BB\nValve DD has flow rate=20; tunnels lead to valves CC, AA, EE\nValve EE has flow rate=3; tunnels lead to valves FF, DD\nValve FF has flow rate=0; tunnels lead to valves EE, GG\nValve GG has flow rate=0; tunnels lead to valves FF, HH\nValve HH has flow rate=22; tunnel leads to valve GG\nValve II has flow rate=0; tunnels lead to valves AA, JJ\nValve JJ has flow rate=21; tunnel leads to valve II\n";
     let volcano = Volcano::parse(contents);
-    let most_pressure_released = find_most_pressure_released(State::new(&volcano), &volcano);
-    assert_eq!(most_pressure_released, 1651);
+    let plan = find_best_plan(&volcano, 1, 30);
+    assert_eq!(plan.pressure_released, 1651);
+    // DD, BB, JJ, HH, EE, CC
+    assert_eq!(
+        plan.routes,
+        vec![vec![(3, 2), (1, 5), (9, 9), (7, 17), (4, 21), (2, 24)]]
+    );
 }
 
 #[test]
 fn test_find_most_pressure_released_with_elephant() {
     let contents = "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB\nValve BB has flow rate=13; tunnels lead to valves CC, AA\nValve CC has flow rate=2; tunnels lead to valves DD, BB\nValve DD has flow rate=20; tunnels lead to valves CC, AA, EE\nValve EE has flow rate=3; tunnels lead to valves FF, DD\nValve FF has flow rate=0; tunnels lead to valves EE, GG\nValve GG has flow rate=0; tunnels lead to valves FF, HH\nValve HH has flow rate=22; tunnel leads to valve GG\nValve II has flow rate=0; tunnels lead to valves AA, JJ\nValve JJ has flow rate=21; tunnel leads to valve II\n";
     let volcano = Volcano::parse(contents);
-    let most_pressure_released =
-        find_most_pressure_released_with_elephant(StateWithElephant::new(&volcano), &volcano);
-    assert_eq!(most_pressure_released, 1707);
+    let plan = find_best_plan(&volcano, 2, 26);
+    assert_eq!(plan.pressure_released, 1707);
+    // One agent opens JJ, BB, CC, and the other DD, HH, EE
+    let mut routes = plan.routes.clone();
+    routes.sort();
+    assert_eq!(
+        routes,
+        vec![vec![(3, 2), (7, 7), (4, 11)], vec![(9, 3), (1, 7), (2, 9)],]
+    );
+}
+
+#[test]
+fn test_find_most_pressure_released_with_two_elephants() {
+    let contents = "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB\nValve BB has flow rate=13; tunnels lead to valves CC, AA\nValve CC has flow rate=2; tunnels lead to valves DD, BB\nValve DD has flow rate=20; tunnels lead to valves CC, AA, EE\nValve EE has flow rate=3; tunnels lead to valves FF, DD\nValve FF has flow rate=0; tunnels lead to valves EE, GG\nValve GG has flow rate=0; tunnels lead to valves FF, HH\nValve HH has flow rate=22; tunnel leads to valve GG\nValve II has flow rate=0; tunnels lead to valves AA, JJ\nValve JJ has flow rate=21; tunnel leads to valve II\n";
+    let volcano = Volcano::parse(contents);
+    let plan = find_best_plan(&volcano, 3, 26);
+    assert_eq!(plan.pressure_released, 1794);
+    assert!(plan.pressure_released >= find_best_plan(&volcano, 2, 26).pressure_released);
+    assert_eq!(plan.routes.len(), 3);
+    for (i, route) in plan.routes.iter().enumerate() {
+        for other in &plan.routes[i + 1..] {
+            assert!(route
+                .iter()
+                .all(|(valve, _)| other.iter().all(|(other_valve, _)| valve != other_valve)));
+        }
+    }
+}
+
+#[test]
+fn test_find_best_plan_agents_and_time() {
+    let contents = "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB\nValve BB has flow rate=13; tunnels lead to valves CC, AA\nValve CC has flow rate=2; tunnels lead to valves DD, BB\nValve DD has flow rate=20; tunnels lead to valves CC, AA, EE\nValve EE has flow rate=3; tunnels lead to valves FF, DD\nValve FF has flow rate=0; tunnels lead to valves EE, GG\nValve GG has flow rate=0; tunnels lead to valves FF, HH\nValve HH has flow rate=22; tunnel leads to valve GG\nValve II has flow rate=0; tunnels lead to valves AA, JJ\nValve JJ has flow rate=21; tunnel leads to valve II\n";
+    let volcano = Volcano::parse(contents);
+
+    // Not enough time to open anything
+    let plan = find_best_plan(&volcano, 1, 1);
+    assert_eq!(plan.pressure_released, 0);
+    assert_eq!(plan.routes, vec![vec![]]);
+
+    // Only time to open one of the valves next to AA, so more agents open more of them
+    let plan = find_best_plan(&volcano, 1, 3);
+    assert_eq!(plan.pressure_released, 20);
+    let plan = find_best_plan(&volcano, 3, 3);
+    assert_eq!(plan.pressure_released, 20 + 13);
+
+    // Routes must be consistent with the total, never open the same valve twice, and
+    // with more agents we can never do worse
+    let mut previous = 0;
+    for agents in 1..=4 {
+        let plan = find_best_plan(&volcano, agents, 26);
+        assert_eq!(plan.routes.len(), agents);
+        let opened = plan
+            .routes
+            .iter()
+            .flatten()
+            .map(|(valve, _)| *valve)
+            .collect::<Vec<usize>>();
+        let mut unique = opened.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(opened.len(), unique.len());
+        let total = plan
+            .routes
+            .iter()
+            .flatten()
+            .map(|(valve, minute)| volcano.flow_rates[*valve] * (26 - minute))
+            .sum::<u32>();
+        assert_eq!(total, plan.pressure_released);
+        assert!(plan.pressure_released >= previous);
+        previous = plan.pressure_released;
+    }
 }
@@ -1,19 +1,16 @@
-use std::{
-    collections::{HashMap, HashSet},
-    fmt, fs,
-};
+use std::{collections::HashMap, fmt, fs};
 
 pub fn part1() -> String {
     let contents = get_input_file_contents();
     let pattern = parse_pattern(&contents);
-    let height = run_simulation(pattern, 2022);
+    let height = run_simulation(Chamber::standard(), pattern, 2022);
     format!("{}", height)
 }
 
 pub fn part2() -> String {
     let contents = get_input_file_contents();
     let pattern = parse_pattern(&contents);
-    let height = run_simulation(pattern, 1000000000000);
+    let height = run_simulation(Chamber::standard(), pattern, 1000000000000);
     format!("{}", height)
 }
 
@@ -27,63 +24,130 @@ enum Push {
     Right,
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
-enum RockType {
-    Horizontal,
-    Plus,
-    Corner,
-    Vertical,
-    Square,
+/// The rocks from the puzzle, in the order they fall.
+const STANDARD_SHAPES: &str = "\
+####
+
+.#.
+###
+.#.
+
+..#
+..#
+###
+
+#
+#
+#
+#
+
+##
+##";
+
+/// The shape of a rock, as a bitmask per row from the bottom up, where bit `x` is column `x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Shape {
+    width: u32,
+    rows: Vec<u64>,
+}
+
+impl Shape {
+    /// Parse a shape drawn with `#` for rock and `.` for empty space.
+    fn parse(input: &str) -> Self {
+        let lines = input.lines().collect::<Vec<&str>>();
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as u32;
+        assert!(width > 0, "Rock shapes can't be empty");
+        assert!(width <= 64, "Rock rows must fit in a u64");
+        let rows = lines
+            .iter()
+            .rev()
+            .map(|line| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, ch)| *ch == '#')
+                    .fold(0, |row, (x, _)| row | 1 << x)
+            })
+            .collect();
+        Self { width, rows }
+    }
+
+    fn height(&self) -> u64 {
+        self.rows.len() as u64
+    }
+
+    fn contains(&self, x: u32, y: u64) -> bool {
+        self.rows
+            .get(y as usize)
+            .is_some_and(|row| row & (1 << x) != 0)
+    }
 }
 
-lazy_static! {
-    static ref ROCK_SIZES: HashMap<RockType, (u32, u32)> = {
-        let mut m = HashMap::new();
-        m.insert(RockType::Horizontal, (4, 1));
-        m.insert(RockType::Plus, (3, 3));
-        m.insert(RockType::Corner, (3, 3));
-        m.insert(RockType::Vertical, (1, 4));
-        m.insert(RockType::Square, (2, 2));
-        m
-    };
-    static ref ROCK_SHAPES: HashMap<RockType, Vec<(u32, u32)>> = {
-        let mut m = HashMap::new();
-        m.insert(RockType::Horizontal, vec![(0, 0), (1, 0), (2, 0), (3, 0)]);
-        m.insert(RockType::Plus, vec![(0, 1), (1, 0), (1, 1), (1, 2), (2, 1)]);
-        m.insert(
-            RockType::Corner,
-            vec![(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)],
+/// The width of the chamber and the rocks that fall into it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Chamber {
+    width: u32,
+    shapes: Vec<Shape>,
+}
+
+impl Chamber {
+    /// Configure a chamber from the shapes of its rocks, separated by blank lines.
+    fn parse(width: u32, spec: &str) -> Self {
+        assert!(
+            (1..=64).contains(&width),
+            "Chamber rows must be between 1 and 64 wide, not {}",
+            width
         );
-        m.insert(RockType::Vertical, vec![(0, 0), (0, 1), (0, 2), (0, 3)]);
-        m.insert(RockType::Square, vec![(0, 0), (1, 0), (0, 1), (1, 1)]);
-        m
-    };
+        let shapes = spec
+            .split("\n\n")
+            .filter(|shape| !shape.trim().is_empty())
+            .map(Shape::parse)
+            .collect::<Vec<Shape>>();
+        assert!(
+            shapes.iter().all(|shape| shape.width + 2 <= width),
+            "Rocks must fit in the chamber where they appear"
+        );
+        Self { width, shapes }
+    }
+
+    fn standard() -> Self {
+        Self::parse(7, STANDARD_SHAPES)
+    }
+
+    fn full_row(&self) -> u64 {
+        u64::MAX >> (64 - self.width)
+    }
 }
 
 #[derive(Debug, Clone)]
 struct Rock {
-    ty: RockType,
+    shape: usize,
     x: u32,
-    y: u32,
+    y: u64,
 }
 
 #[derive(Debug)]
 struct Room {
+    chamber: Chamber,
     falling_rock: Option<Rock>,
-    stationary_rock_points: HashSet<(u32, u32)>,
+    /// Stationary rock, as a bitmask per row from the bottom of the kept rows up.
+    rows: Vec<u64>,
+    /// How many rows have been trimmed from the bottom of the tower.
+    height_offset: u64,
     rocks_dropped_count: usize,
-    next_rock_type: RockType,
+    next_shape: usize,
     pattern: Vec<Push>,
     pattern_index: usize,
 }
 
 impl Room {
-    fn new(pattern: Vec<Push>) -> Self {
+    fn new(chamber: Chamber, pattern: Vec<Push>) -> Self {
         Self {
+            chamber,
             falling_rock: None,
-            stationary_rock_points: HashSet::new(),
+            rows: Vec::new(),
+            height_offset: 0,
             rocks_dropped_count: 0,
-            next_rock_type: RockType::Horizontal,
+            next_shape: 0,
             pattern,
             pattern_index: 0,
         }
@@ -94,165 +158,169 @@ impl Room {
             panic!("Only one rock can be falling at a time");
         }
 
-        let ty = self.next_rock_type;
-        self.next_rock_type = match self.next_rock_type {
-            RockType::Horizontal => RockType::Plus,
-            RockType::Plus => RockType::Corner,
-            RockType::Corner => RockType::Vertical,
-            RockType::Vertical => RockType::Square,
-            RockType::Square => RockType::Horizontal,
-        };
+        let shape = self.next_shape;
+        self.next_shape = (self.next_shape + 1) % self.chamber.shapes.len();
         let x = 2;
         let y = self.height() + 3;
-        self.falling_rock = Some(Rock { ty, x, y });
+        self.falling_rock = Some(Rock { shape, x, y });
     }
 
     fn drop_rock(&mut self) {
-        if self.falling_rock.is_none() {
-            panic!("Can't drop a rock that isn't falling")
-        }
-        let falling_rock = self.falling_rock.as_mut().unwrap();
-        let (rock_width, _) = ROCK_SIZES[&falling_rock.ty];
+        self.drop_rock_with(|_| {});
+    }
+
+    /// Drop the falling rock, calling `observe` after each time it moves or tries to.
+    fn drop_rock_with<F: FnMut(&Room)>(&mut self, mut observe: F) {
+        let mut rock = self
+            .falling_rock
+            .clone()
+            .expect("Can't drop a rock that isn't falling");
 
         loop {
             // Being pushed by a jet of hot gas
-            let push = &self.pattern[self.pattern_index];
-            match push {
-                Push::Left => {
-                    if falling_rock.x > 0 {
-                        let mut new_rock = falling_rock.clone();
-                        new_rock.x -= 1;
-                        if !check_for_collision(&new_rock, &self.stationary_rock_points) {
-                            falling_rock.x -= 1;
-                        }
-                    }
-                }
-                Push::Right => {
-                    if falling_rock.x + rock_width - 1 < 6 {
-                        let mut new_rock = falling_rock.clone();
-                        new_rock.x += 1;
-                        if !check_for_collision(&new_rock, &self.stationary_rock_points) {
-                            falling_rock.x += 1;
-                        }
-                    }
-                }
+            let mut pushed = rock.clone();
+            match self.pattern[self.pattern_index] {
+                Push::Left => pushed.x = pushed.x.wrapping_sub(1),
+                Push::Right => pushed.x += 1,
             }
-            self.pattern_index += 1;
-            if self.pattern_index == self.pattern.len() {
-                self.pattern_index = 0;
+            if !self.collides(&pushed) {
+                rock = pushed;
             }
+            self.pattern_index = (self.pattern_index + 1) % self.pattern.len();
+            self.falling_rock = Some(rock.clone());
+            observe(self);
 
             // Falling one unit down
-            if falling_rock.y == 0 {
+            if rock.y == 0 {
                 // On the floor
                 break;
-            } else {
-                let mut new_rock = falling_rock.clone();
-                new_rock.y -= 1;
-                if check_for_collision(&new_rock, &self.stationary_rock_points) {
-                    // Collided with stationary rock
-                    break;
-                } else {
-                    falling_rock.y -= 1;
-                }
             }
+            let mut fallen = rock.clone();
+            fallen.y -= 1;
+            if self.collides(&fallen) {
+                // Collided with stationary rock
+                break;
+            }
+            rock = fallen;
+            self.falling_rock = Some(rock.clone());
+            observe(self);
         }
         self.solidify_falling_rock();
+        observe(self);
+    }
+
+    /// The stationary rock in a row, where trimmed rows count as solid.
+    fn row(&self, y: u64) -> u64 {
+        if y < self.height_offset {
+            self.chamber.full_row()
+        } else {
+            let index = (y - self.height_offset) as usize;
+            self.rows.get(index).copied().unwrap_or(0)
+        }
+    }
+
+    fn collides(&self, rock: &Rock) -> bool {
+        let shape = &self.chamber.shapes[rock.shape];
+        if rock.x > self.chamber.width - shape.width {
+            // Hit a wall
+            return true;
+        }
+        shape
+            .rows
+            .iter()
+            .zip(rock.y..)
+            .any(|(row, y)| (row << rock.x) & self.row(y) != 0)
     }
 
     fn solidify_falling_rock(&mut self) {
-        let falling_rock = self
+        let rock = self
             .falling_rock
-            .as_ref()
+            .take()
             .expect("Can't solidify a rock that isn't falling");
-        for (x, y) in &ROCK_SHAPES[&falling_rock.ty] {
-            self.stationary_rock_points
-                .insert((x + falling_rock.x, y + falling_rock.y));
+        let shape = &self.chamber.shapes[rock.shape];
+        let top = rock.y + shape.height() - self.height_offset;
+        if top as usize > self.rows.len() {
+            self.rows.resize(top as usize, 0);
+        }
+        for (row, y) in shape.rows.iter().zip(rock.y - self.height_offset..) {
+            self.rows[y as usize] |= row << rock.x;
         }
-        self.falling_rock = None;
         self.rocks_dropped_count += 1;
 
-        // We don't need to remember the full tower
-        let height = self.height();
-        self.stationary_rock_points
-            .retain(|(_, y)| *y >= height.saturating_sub(100));
+        // We don't need to remember any of the tower that no rock can reach
+        let trimmed = self.rows.len() - self.surface().len();
+        self.rows.drain(..trimmed);
+        self.height_offset += trimmed as u64;
     }
 
-    fn height(&self) -> u32 {
-        if self.stationary_rock_points.is_empty() {
-            0
-        } else {
-            self.stationary_rock_points
-                .iter()
-                .map(|(_, y)| *y)
-                .max()
-                .unwrap()
-                + 1
+    /// The empty space a falling rock could get into, as a bitmask per row from the top of the
+    /// tower down to the lowest row with any.
+    ///
+    /// Rocks only move sideways and down, so every cell a rock ever covers is reached that way
+    /// from above the tower. Anything below the last of these rows is as good as solid.
+    fn surface(&self) -> Vec<u64> {
+        let full_row = self.chamber.full_row();
+        let mut surface = Vec::new();
+        let mut reachable = full_row;
+        for row in self.rows.iter().rev() {
+            let empty = !row & full_row;
+            reachable &= empty;
+            loop {
+                let spread = (reachable | reachable << 1 | reachable >> 1) & empty;
+                if spread == reachable {
+                    break;
+                }
+                reachable = spread;
+            }
+            if reachable == 0 {
+                break;
+            }
+            surface.push(reachable);
         }
+        surface
     }
 
-    fn state_hash(&self) -> (RockType, usize, Vec<(u32, u32)>) {
-        let min_y = self
-            .stationary_rock_points
-            .iter()
-            .map(|(_, y)| *y)
-            .min()
-            .unwrap_or(0);
-        let mut rock_points = self
-            .stationary_rock_points
-            .iter()
-            .map(|(x, y)| (*x, *y - min_y))
-            .collect::<Vec<(u32, u32)>>();
-        rock_points.sort();
-        (
-            self.falling_rock.as_ref().unwrap().ty,
-            self.pattern_index,
-            rock_points,
-        )
+    fn height(&self) -> u64 {
+        self.height_offset + self.rows.len() as u64
+    }
+
+    fn state_hash(&self) -> (usize, usize, Vec<u64>) {
+        (self.next_shape, self.pattern_index, self.surface())
     }
 }
 
 impl fmt::Display for Room {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let max_y = self.height();
-        for y in (0..=max_y + 5).rev() {
+        let top = self
+            .falling_rock
+            .as_ref()
+            .map(|rock| rock.y + self.chamber.shapes[rock.shape].height())
+            .unwrap_or(0)
+            .max(self.height());
+        for y in (self.height_offset..top).rev() {
             write!(f, "|")?;
-            for x in 0..7 {
-                if self.stationary_rock_points.contains(&(x, y)) {
+            for x in 0..self.chamber.width {
+                let in_falling_rock = self.falling_rock.as_ref().is_some_and(|rock| {
+                    x >= rock.x
+                        && y >= rock.y
+                        && self.chamber.shapes[rock.shape].contains(x - rock.x, y - rock.y)
+                });
+                if in_falling_rock {
+                    write!(f, "@")?;
+                } else if self.row(y) & (1 << x) != 0 {
                     write!(f, "#")?;
-                } else if let Some(falling_rock) = self.falling_rock.as_ref() {
-                    let mut in_falling_rock = false;
-                    for point in &ROCK_SHAPES[&falling_rock.ty] {
-                        if x == point.0 + falling_rock.x && y == point.1 + falling_rock.y {
-                            write!(f, "@")?;
-                            in_falling_rock = true;
-                            break;
-                        }
-                    }
-                    if !in_falling_rock {
-                        write!(f, ".")?;
-                    }
                 } else {
                     write!(f, ".")?;
                 }
             }
             writeln!(f, "|")?;
         }
-        writeln!(f, "+-------+")?;
-        Ok(())
+        // The floor, or a break where the bottom of the tower has been trimmed
+        let floor = if self.height_offset == 0 { "-" } else { "~" };
+        writeln!(f, "+{}+", floor.repeat(self.chamber.width as usize))
     }
 }
 
-fn check_for_collision(rock: &Rock, stationary_rock_points: &HashSet<(u32, u32)>) -> bool {
-    for (x, y) in &ROCK_SHAPES[&rock.ty] {
-        let point = (x + rock.x, y + rock.y);
-        if stationary_rock_points.contains(&point) {
-            return true;
-        }
-    }
-    false
-}
-
 fn parse_pattern(contents: &str) -> Vec<Push> {
     contents
         .chars()
@@ -264,25 +332,39 @@ fn parse_pattern(contents: &str) -> Vec<Push> {
         .collect::<Vec<Push>>()
 }
 
-fn run_simulation(pattern: Vec<Push>, num_rocks: u64) -> u64 {
+/// Drop the first few rocks into a chamber, calling `observe` at every step along the way.
+#[allow(dead_code)]
+fn replay<F: FnMut(&Room)>(chamber: Chamber, pattern: Vec<Push>, num_rocks: usize, mut observe: F) {
+    let mut room = Room::new(chamber, pattern);
+    for _ in 0..num_rocks {
+        room.release_rock();
+        observe(&room);
+        room.drop_rock_with(&mut observe);
+    }
+}
+
+fn run_simulation(chamber: Chamber, pattern: Vec<Push>, num_rocks: u64) -> u64 {
     let mut unique_states = HashMap::new();
     let mut found_repeat_state = false;
     let mut total_rocks_dropped_count: u64 = 0;
     let mut height_from_repeats: u64 = 0;
-    let mut room = Room::new(pattern);
-    loop {
+    let mut room = Room::new(chamber, pattern);
+    while total_rocks_dropped_count < num_rocks {
         room.release_rock();
 
         if !found_repeat_state {
             let state = room.state_hash();
-            if unique_states.contains_key(&state) {
+            if let Some((previous_rocks_dropped_count, previous_height)) = unique_states.get(&state)
+            {
                 found_repeat_state = true;
-                let (previous_rocks_dropped_count, previous_height) = unique_states[&state];
-                let repeat_height = (room.height() - previous_height) as u64;
+                let repeat_height = room.height() - previous_height;
                 let repeat_rocks = (room.rocks_dropped_count - previous_rocks_dropped_count) as u64;
-                while total_rocks_dropped_count + repeat_rocks <= num_rocks {
-                    total_rocks_dropped_count += repeat_rocks;
-                    height_from_repeats += repeat_height;
+                let repeats = (num_rocks - total_rocks_dropped_count) / repeat_rocks;
+                total_rocks_dropped_count += repeats * repeat_rocks;
+                height_from_repeats += repeats * repeat_height;
+                if total_rocks_dropped_count == num_rocks {
+                    room.falling_rock = None;
+                    break;
                 }
             } else {
                 unique_states.insert(state, (room.rocks_dropped_count, room.height()));
@@ -290,13 +372,9 @@ fn run_simulation(pattern: Vec<Push>, num_rocks: u64) -> u64 {
         }
 
         room.drop_rock();
-
         total_rocks_dropped_count += 1;
-        if total_rocks_dropped_count == num_rocks {
-            break;
-        }
     }
-    room.height() as u64 + height_from_repeats
+    room.height() + height_from_repeats
 }
 
 #[test]
@@ -320,7 +398,7 @@ fn test_parse_pattern() {
 fn test_run_simulation() {
     let contents = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
     let pattern = parse_pattern(contents);
-    let height = run_simulation(pattern, 2022);
+    let height = run_simulation(Chamber::standard(), pattern, 2022);
     assert_eq!(height, 3068);
 }
 
@@ -328,6 +406,106 @@ fn test_run_simulation() {
 fn test_run_simulation_big() {
     let contents = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
     let pattern = parse_pattern(contents);
-    let height = run_simulation(pattern, 1000000000000);
+    let height = run_simulation(Chamber::standard(), pattern, 1000000000000);
     assert_eq!(height, 1514285714288);
 }
+
+#[test]
+fn test_parse_chamber() {
+    let chamber = Chamber::standard();
+    assert_eq!(chamber.width, 7);
+    assert_eq!(chamber.shapes.len(), 5);
+    assert_eq!(
+        chamber.shapes[2],
+        Shape {
+            width: 3,
+            rows: vec![0b111, 0b100, 0b100],
+        }
+    );
+    assert_eq!(chamber.full_row(), 0b1111111);
+
+    let chamber = Chamber::parse(4, "##\n\n#.\n.#\n");
+    assert_eq!(chamber.width, 4);
+    assert_eq!(
+        chamber.shapes,
+        vec![
+            Shape {
+                width: 2,
+                rows: vec![0b11],
+            },
+            Shape {
+                width: 2,
+                rows: vec![0b10, 0b01],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_replay() {
+    let contents = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
+    let pattern = parse_pattern(contents);
+    let mut frames = Vec::new();
+    replay(Chamber::standard(), pattern, 2, |room| {
+        frames.push(room.to_string())
+    });
+    assert_eq!(
+        frames[0],
+        "|..@@@@.|\n|.......|\n|.......|\n|.......|\n+-------+\n"
+    );
+    // Pushed right, then falls
+    assert_eq!(
+        frames[1],
+        "|...@@@@|\n|.......|\n|.......|\n|.......|\n+-------+\n"
+    );
+    assert_eq!(frames[2], "|...@@@@|\n|.......|\n|.......|\n+-------+\n");
+    assert_eq!(
+        frames.last().unwrap(),
+        "|...#...|\n|..###..|\n|...#...|\n|..####.|\n+-------+\n"
+    );
+}
+
+#[test]
+fn test_run_simulation_configured() {
+    // A wide rock pushed against the right wall stacks up without moving
+    let chamber = Chamber::parse(5, "###");
+    let pattern = parse_pattern(">");
+    assert_eq!(run_simulation(chamber, pattern, 1000), 1000);
+
+    // Squares in a chamber just wide enough for two side by side, spawned at x = 2
+    let chamber = Chamber::parse(4, "##\n##");
+    let pattern = parse_pattern("<");
+    assert_eq!(run_simulation(chamber.clone(), pattern, 3), 6);
+    let pattern = parse_pattern(">");
+    assert_eq!(run_simulation(chamber, pattern, 3), 6);
+}
+
+#[test]
+fn test_deep_shaft() {
+    // Wide rocks pushed to the left leave a shaft down the right of the tower, and then a
+    // small rock pushed right falls all the way down it
+    let stacked = 250;
+    let spec = format!("{}#", "#####\n\n".repeat(stacked));
+    let chamber = Chamber::parse(7, &spec);
+    let pattern = parse_pattern(&format!("{}{}", "<".repeat(4 * stacked), ">".repeat(300)));
+    let mut lowest = u64::MAX;
+    replay(chamber, pattern, stacked + 1, |room| {
+        if let Some(rock) = &room.falling_rock {
+            lowest = lowest.min(rock.y);
+        }
+    });
+    assert_eq!(lowest, 0);
+}
+
+#[test]
+fn test_surface() {
+    let mut room = Room::new(Chamber::standard(), parse_pattern(">"));
+    room.rows = vec![0b1111111, 0b1111110, 0b1100011, 0b1111011, 0b0000000];
+    // The hole in the second row is sealed off, so only the top three rows are reachable
+    assert_eq!(room.surface(), vec![0b1111111, 0b0000100, 0b0011100]);
+
+    room.release_rock();
+    room.drop_rock();
+    assert_eq!(room.height_offset, 2);
+    assert_eq!(room.row(1), Chamber::standard().full_row());
+}
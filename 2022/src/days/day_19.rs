@@ -6,10 +6,7 @@ use nom::{
     sequence::{terminated, tuple},
     IResult,
 };
-use std::{
-    collections::{HashMap, HashSet, VecDeque},
-    fs,
-};
+use std::{collections::HashMap, fs};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
@@ -39,45 +36,36 @@ enum Resource {
     Geode = 3,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct State {
-    resources: [u32; 4],
-    robots: [u32; 4],
+/// A robot whose construction starts in a given minute, counting from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Build {
     minute: u32,
+    robot: Resource,
 }
 
-impl State {
-    fn new() -> Self {
-        let resources = [0, 0, 0, 0];
-        let robots = [1, 0, 0, 0];
-        Self {
-            resources,
-            robots,
-            minute: 1,
-        }
-    }
+/// The robots to build to open the most geodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Schedule {
+    geodes: u32,
+    builds: Vec<Build>,
+}
 
-    fn robot_build_options(&self, blueprint: &Blueprint) -> Vec<Option<Resource>> {
-        let mut choices = vec![None];
-        for robot_type in Resource::iter() {
-            let mut can_afford = true;
-            for (resource, cost) in &blueprint.robot_costs[&robot_type] {
-                if *cost > self.resources[*resource as usize] {
-                    can_afford = false;
-                }
-            }
-            if can_afford {
-                choices.push(Some(robot_type))
-            }
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for build in &self.builds {
+            writeln!(f, "Minute {}: build {:?} robot", build.minute, build.robot)?;
         }
-        choices
+        write!(f, "{} geodes opened", self.geodes)
     }
+}
 
-    fn mine_resources(&mut self) {
-        for resource in Resource::iter() {
-            self.resources[resource as usize] += self.robots[resource as usize];
-        }
-    }
+/// The state of the search, where geodes are counted as soon as a geode robot is built.
+#[derive(Debug, Clone)]
+struct State {
+    time_remaining: u32,
+    resources: [u32; 4],
+    robots: [u32; 4],
+    geodes: u32,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -152,69 +140,175 @@ impl Blueprint {
         )(input)
     }
 
-    fn quality_level(&self, time_allowed: u32) -> u32 {
-        self.id * self.calc_most_geodes_opened(time_allowed)
+    /// The cost of each type of robot, indexed by robot type then resource.
+    fn cost_table(&self) -> [[u32; 4]; 4] {
+        let mut costs = [[0; 4]; 4];
+        for (robot, robot_costs) in &self.robot_costs {
+            for (resource, cost) in robot_costs {
+                costs[*robot as usize][*resource as usize] = *cost;
+            }
+        }
+        costs
     }
 
-    fn calc_most_geodes_opened(&self, time_allowed: u32) -> u32 {
-        calc_most_geodes_opened(time_allowed, self)
-    }
-}
+    /// Find a schedule of robots to build that opens the most geodes in the time allowed.
+    fn best_schedule(&self, time_allowed: u32) -> Schedule {
+        let costs = self.cost_table();
+        // We can only spend so much of each resource per minute, so there's no point having
+        // more robots than that. There's no limit on geode robots.
+        let mut max_robots = [u32::MAX; 4];
+        for resource in [Resource::Ore, Resource::Clay, Resource::Obsidian] {
+            max_robots[resource as usize] = costs
+                .iter()
+                .map(|cost| cost[resource as usize])
+                .max()
+                .unwrap();
+        }
 
-fn calc_most_geodes_opened(time_allowed: u32, blueprint: &Blueprint) -> u32 {
-    let mut states_seen = HashSet::new();
+        let state = State {
+            time_remaining: time_allowed,
+            resources: [0; 4],
+            robots: [1, 0, 0, 0],
+            geodes: 0,
+        };
+        let mut best = Schedule {
+            geodes: 0,
+            builds: Vec::new(),
+        };
+        let mut builds = Vec::new();
+        search(
+            &state,
+            time_allowed,
+            &costs,
+            &max_robots,
+            &mut builds,
+            &mut best,
+        );
+        best
+    }
 
-    let mut queue = VecDeque::new();
-    queue.push_back(State::new());
+    /// Follow a schedule, returning the geodes opened, or `None` if a robot can't be built.
+    #[allow(dead_code)]
+    fn run_schedule(&self, time_allowed: u32, builds: &[Build]) -> Option<u32> {
+        let costs = self.cost_table();
+        let mut resources = [0; 4];
+        let mut robots = [1, 0, 0, 0];
+        let mut builds = builds.iter().peekable();
+        for minute in 1..=time_allowed {
+            let mut built = None;
+            if let Some(build) = builds.next_if(|build| build.minute == minute) {
+                let cost = costs[build.robot as usize];
+                if (0..4).any(|i| resources[i] < cost[i]) {
+                    return None;
+                }
+                (0..4).for_each(|i| resources[i] -= cost[i]);
+                built = Some(build.robot);
+            }
+            (0..4).for_each(|i| resources[i] += robots[i]);
+            if let Some(robot) = built {
+                robots[robot as usize] += 1;
+            }
+        }
+        // Every build must happen within the time allowed
+        match builds.next() {
+            Some(_) => None,
+            None => Some(resources[Resource::Geode as usize]),
+        }
+    }
+}
 
-    let mut best_result = None;
-    while !queue.is_empty() {
-        let mut state = queue.pop_back().unwrap();
+/// Search by choosing which robot to build next and skipping ahead to when it's affordable.
+fn search(
+    state: &State,
+    time_allowed: u32,
+    costs: &[[u32; 4]; 4],
+    max_robots: &[u32; 4],
+    builds: &mut Vec<Build>,
+    best: &mut Schedule,
+) {
+    if state.geodes > best.geodes {
+        best.geodes = state.geodes;
+        best.builds = builds.clone();
+    }
+    if geode_upper_bound(state, costs) <= best.geodes {
+        return;
+    }
 
-        // Ignore this branch if we've seen it before
-        if states_seen.contains(&state) {
+    // Try geode robots first, since they're most likely to lead to a good result
+    for robot in (0..4).rev() {
+        if state.robots[robot] >= max_robots[robot] {
             continue;
-        } else {
-            states_seen.insert(state.clone());
         }
 
-        // Have to spend resources to build a robot before we mine resources for this minute
-        let robot_build_options = state.robot_build_options(blueprint);
-
-        state.mine_resources();
-
-        // Time is up
-        if state.minute == time_allowed {
-            let result = state.resources[Resource::Geode as usize];
-            if best_result.is_none() || result > best_result.unwrap() {
-                best_result = Some(result);
+        // Wait until we've mined enough to afford this robot
+        let mut wait = 0;
+        let mut affordable = true;
+        for (resource, cost) in costs[robot].iter().enumerate() {
+            let shortfall = cost.saturating_sub(state.resources[resource]);
+            if shortfall == 0 {
+                continue;
+            }
+            if state.robots[resource] == 0 {
+                affordable = false;
+                break;
             }
+            wait = wait.max(shortfall.div_ceil(state.robots[resource]));
+        }
+        // A robot finished in the last minute can't mine anything
+        if !affordable || wait + 1 >= state.time_remaining {
             continue;
         }
 
-        // Ignore this branch if we can't beat the best result so far
-        let time_remaining = time_allowed + 1 - state.minute;
-        let best_possible = state.resources[Resource::Geode as usize]
-            + time_remaining * (state.robots[Resource::Geode as usize] + (time_remaining / 2));
-        if best_possible < best_result.unwrap_or(0) {
-            continue;
+        let mut new_state = state.clone();
+        new_state.time_remaining -= wait + 1;
+        for (resource, cost) in costs[robot].iter().enumerate() {
+            new_state.resources[resource] =
+                state.resources[resource] + state.robots[resource] * (wait + 1) - cost;
+        }
+        if robot == Resource::Geode as usize {
+            new_state.geodes += new_state.time_remaining;
+        } else {
+            new_state.robots[robot] += 1;
         }
 
-        // Explore each option at this state (either do nothing, or build a robot we can afford)
-        for robot_build_option in robot_build_options {
-            let mut new_state = state.clone();
-            new_state.minute += 1;
-            if let Some(robot_build_option) = robot_build_option.as_ref() {
-                new_state.robots[*robot_build_option as usize] += 1;
-                for (resource, cost) in &blueprint.robot_costs[robot_build_option] {
-                    new_state.resources[*resource as usize] -= cost;
-                }
-            }
-            queue.push_back(new_state);
+        builds.push(Build {
+            minute: time_allowed - state.time_remaining + wait + 1,
+            robot: Resource::iter().nth(robot).unwrap(),
+        });
+        search(&new_state, time_allowed, costs, max_robots, builds, best);
+        builds.pop();
+    }
+}
+
+/// An upper bound on the geodes that can be opened, found by pretending ore and clay are free,
+/// and that an obsidian robot can be built every minute alongside any geode robot.
+fn geode_upper_bound(state: &State, costs: &[[u32; 4]; 4]) -> u32 {
+    let obsidian_cost = costs[Resource::Geode as usize][Resource::Obsidian as usize];
+    let mut obsidian = state.resources[Resource::Obsidian as usize];
+    let mut geodes = state.geodes;
+    let obsidian_robots = state.robots[Resource::Obsidian as usize]..;
+    for (obsidian_robots, time_remaining) in obsidian_robots.zip((1..=state.time_remaining).rev()) {
+        if obsidian >= obsidian_cost {
+            obsidian -= obsidian_cost;
+            geodes += time_remaining - 1;
         }
+        obsidian += obsidian_robots;
     }
+    geodes
+}
 
-    best_result.unwrap()
+/// Find the best schedule for each blueprint, using a thread per blueprint.
+fn best_schedules(time_allowed: u32, blueprints: &[Blueprint]) -> Vec<Schedule> {
+    std::thread::scope(|scope| {
+        let handles = blueprints
+            .iter()
+            .map(|blueprint| scope.spawn(move || blueprint.best_schedule(time_allowed)))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Blueprint search panicked"))
+            .collect()
+    })
 }
 
 fn parse_blueprints(contents: &str) -> Vec<Blueprint> {
@@ -228,16 +322,16 @@ fn parse_blueprints(contents: &str) -> Vec<Blueprint> {
 fn calc_total_quality_level(time_allowed: u32, blueprints: &[Blueprint]) -> u32 {
     blueprints
         .iter()
-        .map(|b| b.quality_level(time_allowed))
+        .zip(best_schedules(time_allowed, blueprints))
+        .map(|(blueprint, schedule)| blueprint.id * schedule.geodes)
         .sum::<u32>()
 }
 
 fn calc_most_geodes_product(time_allowed: u32, blueprints: &[Blueprint]) -> u32 {
-    blueprints
+    best_schedules(time_allowed, blueprints)
         .iter()
-        .map(|b| b.calc_most_geodes_opened(time_allowed))
-        .reduce(|a, b| a * b)
-        .unwrap()
+        .map(|schedule| schedule.geodes)
+        .product::<u32>()
 }
 
 #[test]
@@ -313,19 +407,22 @@ fn test_parse_blueprints() {
 }
 
 #[test]
-fn test_calc_most_geodes_opened_short() {
+fn test_most_geodes_short() {
     let contents = "Blueprint 1: Each ore robot costs 4 ore. Each clay robot costs 2 ore. Each obsidian robot costs 3 ore and 14 clay. Each geode robot costs 2 ore and 7 obsidian.\nBlueprint 2: Each ore robot costs 2 ore. Each clay robot costs 3 ore. Each obsidian robot costs 3 ore and 8 clay. Each geode robot costs 3 ore and 12 obsidian.";
     let blueprints = parse_blueprints(contents);
-    assert_eq!(blueprints[0].calc_most_geodes_opened(24), 9);
-    assert_eq!(blueprints[1].calc_most_geodes_opened(24), 12);
+    assert_eq!(blueprints[0].best_schedule(24).geodes, 9);
+    assert_eq!(blueprints[1].best_schedule(24).geodes, 12);
 }
 
 #[test]
 fn test_quality_level() {
     let contents = "Blueprint 1: Each ore robot costs 4 ore. Each clay robot costs 2 ore. Each obsidian robot costs 3 ore and 14 clay. Each geode robot costs 2 ore and 7 obsidian.\nBlueprint 2: Each ore robot costs 2 ore. Each clay robot costs 3 ore. Each obsidian robot costs 3 ore and 8 clay. Each geode robot costs 3 ore and 12 obsidian.";
     let blueprints = parse_blueprints(contents);
-    assert_eq!(blueprints[0].calc_most_geodes_opened(24), 9);
-    assert_eq!(blueprints[1].quality_level(24), 24);
+    assert_eq!(blueprints[0].best_schedule(24).geodes, 9);
+    assert_eq!(
+        blueprints[1].id * blueprints[1].best_schedule(24).geodes,
+        24
+    );
 }
 
 #[test]
@@ -336,11 +433,11 @@ fn test_calc_total_quality_level() {
 }
 
 #[test]
-fn test_calc_most_geodes_opened_long() {
+fn test_most_geodes_long() {
     let contents = "Blueprint 1: Each ore robot costs 4 ore. Each clay robot costs 2 ore. Each obsidian robot costs 3 ore and 14 clay. Each geode robot costs 2 ore and 7 obsidian.\nBlueprint 2: Each ore robot costs 2 ore. Each clay robot costs 3 ore. Each obsidian robot costs 3 ore and 8 clay. Each geode robot costs 3 ore and 12 obsidian.";
     let blueprints = parse_blueprints(contents);
-    assert_eq!(blueprints[0].calc_most_geodes_opened(32), 56);
-    assert_eq!(blueprints[1].calc_most_geodes_opened(32), 62);
+    assert_eq!(blueprints[0].best_schedule(32).geodes, 56);
+    assert_eq!(blueprints[1].best_schedule(32).geodes, 62);
 }
 
 #[test]
@@ -349,3 +446,37 @@ fn test_calc_most_geodes_product() {
     let blueprints = parse_blueprints(contents);
     assert_eq!(calc_most_geodes_product(32, &blueprints), 3472);
 }
+
+#[test]
+fn test_best_schedule() {
+    let contents = "Blueprint 1: Each ore robot costs 4 ore. Each clay robot costs 2 ore. Each obsidian robot costs 3 ore and 14 clay. Each geode robot costs 2 ore and 7 obsidian.\nBlueprint 2: Each ore robot costs 2 ore. Each clay robot costs 3 ore. Each obsidian robot costs 3 ore and 8 clay. Each geode robot costs 3 ore and 12 obsidian.";
+    let blueprints = parse_blueprints(contents);
+    for (blueprint, time_allowed) in blueprints.iter().flat_map(|b| [(b, 24), (b, 32)]) {
+        let schedule = blueprint.best_schedule(time_allowed);
+        assert_eq!(
+            blueprint.run_schedule(time_allowed, &schedule.builds),
+            Some(schedule.geodes)
+        );
+    }
+
+    // The schedule from the puzzle description
+    let builds = [
+        (3, Resource::Clay),
+        (5, Resource::Clay),
+        (7, Resource::Clay),
+        (11, Resource::Obsidian),
+        (12, Resource::Clay),
+        (15, Resource::Obsidian),
+        (18, Resource::Geode),
+        (21, Resource::Geode),
+    ]
+    .map(|(minute, robot)| Build { minute, robot });
+    assert_eq!(blueprints[0].run_schedule(24, &builds), Some(9));
+    // Can't build the first clay robot any sooner
+    let mut early = builds;
+    early[0].minute = 2;
+    assert_eq!(blueprints[0].run_schedule(24, &early), None);
+
+    // Not enough time to build anything useful
+    assert_eq!(blueprints[0].best_schedule(3).builds, vec![]);
+}
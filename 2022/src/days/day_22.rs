@@ -29,7 +29,7 @@ fn get_input_file_contents() -> String {
     fs::read_to_string("inputs/input22").expect("Failed to open input file")
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Direction {
     Up,
     Down,
@@ -38,6 +38,22 @@ enum Direction {
 }
 
 impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    fn index(&self) -> usize {
+        match self {
+            Direction::Up => 0,
+            Direction::Down => 1,
+            Direction::Left => 2,
+            Direction::Right => 3,
+        }
+    }
+
     fn points(&self) -> u32 {
         match self {
             Direction::Up => 3,
@@ -214,20 +230,156 @@ impl fmt::Display for Board {
     }
 }
 
+/// A 3D integer unit vector.
+type Vec3 = [i32; 3];
+
+fn neg(v: Vec3) -> Vec3 {
+    [-v[0], -v[1], -v[2]]
+}
+
+/// One face of the cube, with its orientation once the net has been folded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Face {
+    /// Position of the face's top-left tile in the map.
+    origin: (usize, usize),
+    /// Outward normal of the face.
+    normal: Vec3,
+    /// Direction of increasing column on the face.
+    right: Vec3,
+    /// Direction of increasing row on the face.
+    down: Vec3,
+}
+
+impl Face {
+    /// The 3D direction of travel when moving in `direction` on this face.
+    fn direction_vector(&self, direction: Direction) -> Vec3 {
+        match direction {
+            Direction::Up => neg(self.down),
+            Direction::Down => self.down,
+            Direction::Left => neg(self.right),
+            Direction::Right => self.right,
+        }
+    }
+
+    /// The face's orientation after folding its net neighbour in `direction` onto it.
+    fn fold(&self, direction: Direction, origin: (usize, usize)) -> Face {
+        let (normal, right, down) = match direction {
+            Direction::Up => (neg(self.down), self.right, self.normal),
+            Direction::Down => (self.down, self.right, neg(self.normal)),
+            Direction::Left => (neg(self.right), self.normal, self.down),
+            Direction::Right => (self.right, neg(self.normal), self.down),
+        };
+        Face {
+            origin,
+            normal,
+            right,
+            down,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Cube {
     sides: [Array<Tile, Ix2>; 6],
+    faces: [Face; 6],
+    /// For each face and direction of travel off its edge, the face moved onto and the new
+    /// direction of travel.
+    edges: [[(usize, Direction); 4]; 6],
     instructions: Vec<Instruction>,
     current_position: (usize, usize, usize),
     current_direction: Direction,
 }
 
 impl Cube {
-    fn new(sides: [Array<Tile, Ix2>; 6], instructions: Vec<Instruction>) -> Self {
+    /// Fold the given map, which must be one of the 11 nets of a cube, into a cube.
+    fn new(map: Array<Tile, Ix2>, instructions: Vec<Instruction>) -> Self {
+        let tile_count = map.iter().filter(|tile| **tile != Tile::Blank).count();
+        let face_size = ((tile_count / 6) as f64).sqrt().round() as usize;
+        assert_eq!(
+            face_size * face_size * 6,
+            tile_count,
+            "map is not a cube net"
+        );
+
+        let map_shape = map.shape();
+        let mut origins = Vec::new();
+        for row in (0..map_shape[0]).step_by(face_size) {
+            for col in (0..map_shape[1]).step_by(face_size) {
+                if map[[row, col]] != Tile::Blank {
+                    origins.push((row, col));
+                }
+            }
+        }
+        assert_eq!(origins.len(), 6, "map is not a cube net");
+
+        // Fold the net by walking outwards from the first face
+        let mut faces: [Option<Face>; 6] = [None; 6];
+        faces[0] = Some(Face {
+            origin: origins[0],
+            normal: [0, 0, 1],
+            right: [1, 0, 0],
+            down: [0, 1, 0],
+        });
+        let mut to_visit = vec![0];
+        while let Some(i) = to_visit.pop() {
+            let face = faces[i].unwrap();
+            let (row, col) = face.origin;
+            for (direction, neighbour_origin) in [
+                (Direction::Up, row.checked_sub(face_size).map(|r| (r, col))),
+                (Direction::Down, Some((row + face_size, col))),
+                (
+                    Direction::Left,
+                    col.checked_sub(face_size).map(|c| (row, c)),
+                ),
+                (Direction::Right, Some((row, col + face_size))),
+            ] {
+                let Some(j) = neighbour_origin.and_then(|o| origins.iter().position(|x| *x == o))
+                else {
+                    continue;
+                };
+                if faces[j].is_none() {
+                    faces[j] = Some(face.fold(direction, origins[j]));
+                    to_visit.push(j);
+                }
+            }
+        }
+        let faces = faces.map(|face| face.expect("map is not a connected cube net"));
+
+        let mut edges = [[(0, Direction::Up); 4]; 6];
+        for (i, face) in faces.iter().enumerate() {
+            for direction in Direction::ALL {
+                let travel = face.direction_vector(direction);
+                let j = faces
+                    .iter()
+                    .position(|f| f.normal == travel)
+                    .expect("map is not a cube net");
+                // Having crossed the edge we're moving away from the face we left
+                let new_direction = Direction::ALL
+                    .into_iter()
+                    .find(|d| faces[j].direction_vector(*d) == neg(face.normal))
+                    .unwrap();
+                edges[i][direction.index()] = (j, new_direction);
+            }
+        }
+
+        let sides = faces.map(|face| {
+            let (row, col) = face.origin;
+            map.slice(s![row..row + face_size, col..col + face_size])
+                .to_owned()
+        });
+
+        let initial_col = sides[0]
+            .row(0)
+            .iter()
+            .position(|tile| *tile == Tile::Empty)
+            .expect("could not find initial location");
+
         Self {
             sides,
+            faces,
+            edges,
             instructions,
-            current_position: (0, 0, 0),
+            current_position: (0, 0, initial_col),
             current_direction: Direction::Right,
         }
     }
@@ -250,367 +402,59 @@ impl Cube {
         }
     }
 
+    /// The position and direction after taking one step, ignoring walls.
+    fn step(
+        &self,
+        (side, row, col): (usize, usize, usize),
+        direction: Direction,
+    ) -> ((usize, usize, usize), Direction) {
+        let last = self.sides[0].shape()[0] - 1;
+        match direction {
+            Direction::Up if row > 0 => return ((side, row - 1, col), direction),
+            Direction::Down if row < last => return ((side, row + 1, col), direction),
+            Direction::Left if col > 0 => return ((side, row, col - 1), direction),
+            Direction::Right if col < last => return ((side, row, col + 1), direction),
+            _ => {}
+        }
+
+        // Position along the edge being left, measured clockwise around the face. The
+        // shared edge runs the other way around the face being entered.
+        let offset = match direction {
+            Direction::Up => col,
+            Direction::Right => row,
+            Direction::Down => last - col,
+            Direction::Left => last - row,
+        };
+        let (new_side, new_direction) = self.edges[side][direction.index()];
+        let offset = last - offset;
+        let (new_row, new_col) = match new_direction {
+            // Entering through the bottom edge
+            Direction::Up => (last, last - offset),
+            // Entering through the top edge
+            Direction::Down => (0, offset),
+            // Entering through the right edge
+            Direction::Left => (offset, last),
+            // Entering through the left edge
+            Direction::Right => (last - offset, 0),
+        };
+        ((new_side, new_row, new_col), new_direction)
+    }
+
     fn follow_instructions(&mut self) {
-        let side_shape = self.sides[0].shape();
-        let side_rows = side_shape[0];
-        let side_cols = side_shape[1];
         for instruction in &self.instructions {
             match instruction {
                 Instruction::TurnLeft => Self::turn_left(&mut self.current_direction),
                 Instruction::TurnRight => Self::turn_right(&mut self.current_direction),
                 Instruction::Forward(steps) => {
                     for _ in 0..*steps {
-                        let (current_side, current_row, current_col) = self.current_position;
-                        let (mut new_side, mut new_row, mut new_col) = self.current_position;
-                        let mut new_direction = self.current_direction;
-
-                        if side_rows == 4 {
-                            // Example
-                            //       000
-                            //       000
-                            //       000
-                            // 111222333
-                            // 111222333
-                            // 111222333
-                            //       444555
-                            //       444555
-                            //       444555
-                            match self.current_direction {
-                                Direction::Up => {
-                                    if current_row == 0 {
-                                        match current_side {
-                                            0 => {
-                                                new_side = 1;
-                                                new_col = side_cols - 1 - current_col;
-                                                new_direction = Direction::Down;
-                                            }
-                                            1 => {
-                                                new_side = 0;
-                                                new_col = side_cols - 1 - current_col;
-                                                new_direction = Direction::Down;
-                                            }
-                                            2 => {
-                                                new_side = 0;
-                                                new_col = 0;
-                                                new_row = current_col;
-                                                new_direction = Direction::Right;
-                                            }
-                                            3 => {
-                                                new_side = 0;
-                                                new_row = side_rows - 1;
-                                            }
-                                            4 => {
-                                                new_side = 3;
-                                                new_row = side_rows - 1;
-                                            }
-                                            5 => {
-                                                new_side = 3;
-                                                new_col = side_cols - 1;
-                                                new_row = side_rows - 1 - current_col;
-                                                new_direction = Direction::Left;
-                                            }
-                                            _ => unreachable!(),
-                                        }
-                                    } else {
-                                        new_row -= 1;
-                                    }
-                                }
-                                Direction::Down => {
-                                    if current_row == side_rows - 1 {
-                                        match current_side {
-                                            0 => {
-                                                new_side = 3;
-                                                new_row = 0;
-                                            }
-                                            1 => {
-                                                new_side = 4;
-                                                new_col = side_cols - 1 - current_col;
-                                                new_direction = Direction::Up;
-                                            }
-                                            2 => {
-                                                new_side = 4;
-                                                new_row = current_col;
-                                                new_col = 0;
-                                                new_direction = Direction::Right;
-                                            }
-                                            3 => {
-                                                new_side = 4;
-                                                new_row = 0;
-                                            }
-                                            4 => {
-                                                new_side = 1;
-                                                new_col = side_cols - 1 - current_col;
-                                                new_direction = Direction::Up;
-                                            }
-                                            5 => {
-                                                new_side = 1;
-                                                new_row = current_col;
-                                                new_col = 0;
-                                                new_direction = Direction::Right;
-                                            }
-                                            _ => unreachable!(),
-                                        }
-                                    } else {
-                                        new_row += 1;
-                                    }
-                                }
-                                Direction::Left => {
-                                    if current_col == 0 {
-                                        match current_side {
-                                            0 => {
-                                                new_side = 2;
-                                                new_row = 0;
-                                                new_col = current_row;
-                                                new_direction = Direction::Down;
-                                            }
-                                            1 => {
-                                                new_side = 5;
-                                                new_row = side_rows - 1;
-                                                new_col = side_cols - 1 - current_row;
-                                                new_direction = Direction::Up;
-                                            }
-                                            2 => {
-                                                new_side = 1;
-                                                new_col = side_cols - 1;
-                                            }
-                                            3 => {
-                                                new_side = 2;
-                                                new_col = side_cols - 1;
-                                            }
-                                            4 => {
-                                                new_side = 2;
-                                                new_row = side_rows - 1;
-                                                new_col = side_cols - 1 - current_row;
-                                                new_direction = Direction::Up;
-                                            }
-                                            5 => {
-                                                new_side = 4;
-                                                new_col = side_cols - 1;
-                                            }
-                                            _ => unreachable!(),
-                                        }
-                                    } else {
-                                        new_col -= 1;
-                                    }
-                                }
-                                Direction::Right => {
-                                    if current_col == side_cols - 1 {
-                                        match current_side {
-                                            0 => {
-                                                new_side = 5;
-                                                new_row = side_rows - 1 - current_row;
-                                                new_col = side_cols - 1;
-                                                new_direction = Direction::Left;
-                                            }
-                                            1 => {
-                                                new_side = 2;
-                                                new_col = 0;
-                                            }
-                                            2 => {
-                                                new_side = 3;
-                                                new_col = 0;
-                                            }
-                                            3 => {
-                                                new_side = 5;
-                                                new_row = 0;
-                                                new_col = side_cols - 1 - current_row;
-                                                new_direction = Direction::Down;
-                                            }
-                                            4 => {
-                                                new_side = 5;
-                                                new_col = 0;
-                                            }
-                                            5 => {
-                                                new_side = 0;
-                                                new_row = side_rows - 1 - current_row;
-                                                new_col = side_cols - 1;
-                                                new_direction = Direction::Left;
-                                            }
-                                            _ => unreachable!(),
-                                        }
-                                    } else {
-                                        new_col += 1;
-                                    }
-                                }
-                            }
-                        } else {
-                            // Input
-                            //    000111
-                            //    000111
-                            //    000111
-                            //    222
-                            //    222
-                            //    222
-                            // 333444
-                            // 333444
-                            // 333444
-                            // 555
-                            // 555
-                            // 555
-                            match self.current_direction {
-                                Direction::Up => {
-                                    if current_row == 0 {
-                                        match current_side {
-                                            0 => {
-                                                new_side = 5;
-                                                new_row = current_col;
-                                                new_col = 0;
-                                                new_direction = Direction::Right;
-                                            }
-                                            1 => {
-                                                new_side = 5;
-                                                new_row = side_rows - 1;
-                                            }
-                                            2 => {
-                                                new_side = 0;
-                                                new_row = side_rows - 1;
-                                            }
-                                            3 => {
-                                                new_side = 2;
-                                                new_col = 0;
-                                                new_row = current_col;
-                                                new_direction = Direction::Right;
-                                            }
-                                            4 => {
-                                                new_side = 2;
-                                                new_row = side_rows - 1;
-                                            }
-                                            5 => {
-                                                new_side = 3;
-                                                new_row = side_rows - 1;
-                                            }
-                                            _ => unreachable!(),
-                                        }
-                                    } else {
-                                        new_row -= 1;
-                                    }
-                                }
-                                Direction::Down => {
-                                    if current_row == side_rows - 1 {
-                                        match current_side {
-                                            0 => {
-                                                new_side = 2;
-                                                new_row = 0;
-                                            }
-                                            1 => {
-                                                new_side = 2;
-                                                new_col = side_cols - 1;
-                                                new_row = current_col;
-                                                new_direction = Direction::Left;
-                                            }
-                                            2 => {
-                                                new_side = 4;
-                                                new_row = 0;
-                                            }
-                                            3 => {
-                                                new_side = 5;
-                                                new_row = 0;
-                                            }
-                                            4 => {
-                                                new_side = 5;
-                                                new_col = side_cols - 1;
-                                                new_row = current_col;
-                                                new_direction = Direction::Left;
-                                            }
-                                            5 => {
-                                                new_side = 1;
-                                                new_row = 0;
-                                            }
-                                            _ => unreachable!(),
-                                        }
-                                    } else {
-                                        new_row += 1;
-                                    }
-                                }
-                                Direction::Left => {
-                                    if current_col == 0 {
-                                        match current_side {
-                                            0 => {
-                                                new_side = 3;
-                                                new_col = 0;
-                                                new_row = side_rows - 1 - current_row;
-                                                new_direction = Direction::Right;
-                                            }
-                                            1 => {
-                                                new_side = 0;
-                                                new_col = side_cols - 1;
-                                            }
-                                            2 => {
-                                                new_side = 3;
-                                                new_row = 0;
-                                                new_col = current_row;
-                                                new_direction = Direction::Down;
-                                            }
-                                            3 => {
-                                                new_side = 0;
-                                                new_row = side_rows - 1 - current_row;
-                                                new_col = 0;
-                                                new_direction = Direction::Right;
-                                            }
-                                            4 => {
-                                                new_side = 3;
-                                                new_col = side_cols - 1;
-                                            }
-                                            5 => {
-                                                new_side = 0;
-                                                new_row = 0;
-                                                new_col = current_row;
-                                                new_direction = Direction::Down;
-                                            }
-                                            _ => unreachable!(),
-                                        }
-                                    } else {
-                                        new_col -= 1;
-                                    }
-                                }
-                                Direction::Right => {
-                                    if current_col == side_cols - 1 {
-                                        match current_side {
-                                            0 => {
-                                                new_side = 1;
-                                                new_col = 0;
-                                            }
-                                            1 => {
-                                                new_side = 4;
-                                                new_row = side_rows - 1 - current_row;
-                                                new_col = side_cols - 1;
-                                                new_direction = Direction::Left;
-                                            }
-                                            2 => {
-                                                new_side = 1;
-                                                new_row = side_rows - 1;
-                                                new_col = current_row;
-                                                new_direction = Direction::Up;
-                                            }
-                                            3 => {
-                                                new_side = 4;
-                                                new_col = 0;
-                                            }
-                                            4 => {
-                                                new_side = 1;
-                                                new_col = side_cols - 1;
-                                                new_row = side_rows - 1 - current_row;
-                                                new_direction = Direction::Left;
-                                            }
-                                            5 => {
-                                                new_side = 4;
-                                                new_row = side_rows - 1;
-                                                new_col = current_row;
-                                                new_direction = Direction::Up;
-                                            }
-                                            _ => unreachable!(),
-                                        }
-                                    } else {
-                                        new_col += 1;
-                                    }
-                                }
-                            }
-                        }
-
-                        if self.sides[new_side][[new_row, new_col]] != Tile::Wall {
-                            self.current_position = (new_side, new_row, new_col);
-                            self.current_direction = new_direction;
+                        let (new_position, new_direction) =
+                            self.step(self.current_position, self.current_direction);
+                        let (new_side, new_row, new_col) = new_position;
+                        if self.sides[new_side][[new_row, new_col]] == Tile::Wall {
+                            break;
                         }
+                        self.current_position = new_position;
+                        self.current_direction = new_direction;
                     }
                 }
             }
@@ -618,38 +462,10 @@ impl Cube {
     }
 
     fn points(&self) -> u32 {
-        let side_shape = self.sides[0].shape();
         let (current_side, side_row, side_col) = self.current_position;
-        let (current_row, current_col) = if side_shape[0] == 4 {
-            // Example
-            //   0
-            // 123
-            //   45
-            match current_side {
-                0 => (side_row, 2 * side_shape[1] + side_col),
-                1 => (side_shape[0] + side_row, side_col),
-                2 => (side_shape[0] + side_row, side_shape[1] + side_col),
-                3 => (side_shape[0] + side_row, 2 * side_shape[1] + side_col),
-                4 => (2 * side_shape[0] + side_row, 2 * side_shape[1] + side_col),
-                5 => (2 * side_shape[0] + side_row, 3 * side_shape[1] + side_col),
-                _ => unreachable!(),
-            }
-        } else {
-            // Input
-            //  01
-            //  2
-            // 34
-            // 5
-            match current_side {
-                0 => (side_row, side_shape[1] + side_col),
-                1 => (side_row, 2 * side_shape[1] + side_col),
-                2 => (side_shape[0] + side_row, side_shape[1] + side_col),
-                3 => (2 * side_shape[0] + side_row, side_col),
-                4 => (2 * side_shape[0] + side_row, side_shape[1] + side_col),
-                5 => (3 * side_shape[0] + side_row, side_col),
-                _ => unreachable!(),
-            }
-        };
+        let (origin_row, origin_col) = self.faces[current_side].origin;
+        let current_row = origin_row + side_row;
+        let current_col = origin_col + side_col;
 
         1000 * (current_row as u32 + 1)
             + 4 * (current_col as u32 + 1)
@@ -768,64 +584,7 @@ fn parse_board(contents: &str) -> Board {
 fn parse_cube(contents: &str) -> Cube {
     let (rest, map) = parse_map(contents).expect("failed to parse map");
     let (_, instructions) = parse_instructions(rest).expect("failed to parse instructions");
-
-    let map_shape = map.shape();
-    let (side_rows, side_cols) = if map_shape[0] == 12 {
-        // Example
-        (map_shape[0] / 3, map_shape[1] / 4)
-    } else {
-        // Input
-        (map_shape[0] / 4, map_shape[1] / 3)
-    };
-
-    let sides = if side_rows == 4 {
-        // Example
-        //   1
-        // 234
-        //   56
-        [
-            map.slice(s![0..side_rows, 2 * side_cols..3 * side_cols])
-                .to_owned(),
-            map.slice(s![side_rows..2 * side_rows, 0..side_cols])
-                .to_owned(),
-            map.slice(s![side_rows..2 * side_rows, side_cols..2 * side_cols])
-                .to_owned(),
-            map.slice(s![side_rows..2 * side_rows, 2 * side_cols..3 * side_cols])
-                .to_owned(),
-            map.slice(s![
-                2 * side_rows..3 * side_rows,
-                2 * side_cols..3 * side_cols
-            ])
-            .to_owned(),
-            map.slice(s![
-                2 * side_rows..3 * side_rows,
-                3 * side_cols..4 * side_cols
-            ])
-            .to_owned(),
-        ]
-    } else {
-        // Input
-        //  01
-        //  2
-        // 34
-        // 5
-        [
-            map.slice(s![0..side_rows, side_cols..2 * side_cols])
-                .to_owned(),
-            map.slice(s![0..side_rows, 2 * side_cols..3 * side_cols])
-                .to_owned(),
-            map.slice(s![side_rows..2 * side_rows, side_cols..2 * side_cols])
-                .to_owned(),
-            map.slice(s![2 * side_rows..3 * side_rows, 0..side_cols])
-                .to_owned(),
-            map.slice(s![2 * side_rows..3 * side_rows, side_cols..2 * side_cols])
-                .to_owned(),
-            map.slice(s![3 * side_rows..4 * side_rows, 0..side_cols])
-                .to_owned(),
-        ]
-    };
-
-    Cube::new(sides, instructions)
+    Cube::new(map, instructions)
 }
 
 #[test]
@@ -930,6 +689,110 @@ fn test_follow_instructions_cube() {
     assert_eq!(cube.points(), 5031);
 }
 
+#[test]
+fn test_fold_cube_nets() {
+    // The 11 nets of a cube, with `#` marking a face
+    let nets = [
+        "#...\n####\n#...",
+        "#...\n####\n.#..",
+        "#...\n####\n..#.",
+        "#...\n####\n...#",
+        ".#..\n####\n.#..",
+        ".#..\n####\n..#.",
+        "##..\n.###\n.#..",
+        "##..\n.###\n..#.",
+        "##..\n.###\n...#",
+        "##..\n.##.\n..##",
+        "###..\n..###",
+    ];
+
+    let net_map = |net: &str, face_size: usize| {
+        let rows = net.lines().count();
+        let cols = net.lines().map(|line| line.len()).max().unwrap();
+        let mut map = Array::<Tile, Ix2>::default((rows * face_size, cols * face_size).f());
+        for (net_row, line) in net.lines().enumerate() {
+            for (net_col, ch) in line.chars().enumerate() {
+                if ch == '#' {
+                    map.slice_mut(s![
+                        net_row * face_size..(net_row + 1) * face_size,
+                        net_col * face_size..(net_col + 1) * face_size
+                    ])
+                    .fill(Tile::Empty);
+                }
+            }
+        }
+        map
+    };
+
+    for net in nets {
+        for face_size in 1..=4 {
+            let cube = Cube::new(net_map(net, face_size), Vec::new());
+
+            let mut normals = cube.faces.map(|face| face.normal).to_vec();
+            normals.sort();
+            normals.dedup();
+            assert_eq!(normals.len(), 6, "{net}");
+
+            for side in 0..6 {
+                for row in 0..face_size {
+                    for col in 0..face_size {
+                        for direction in Direction::ALL {
+                            let start = ((side, row, col), direction);
+
+                            // Going straight takes us around the cube and back to the start
+                            let mut state = start;
+                            for _ in 0..4 * face_size {
+                                state = cube.step(state.0, state.1);
+                            }
+                            assert_eq!(state, start, "{net}");
+
+                            // Turning around after a step takes us back to where we were
+                            let (position, new_direction) = cube.step(start.0, start.1);
+                            let mut reverse = new_direction;
+                            Cube::turn_left(&mut reverse);
+                            Cube::turn_left(&mut reverse);
+                            let (position, _) = cube.step(position, reverse);
+                            assert_eq!(position, start.0, "{net}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_fold_cube_example_edges() {
+    let contents = "        ...#\n        .#..\n        #...\n        ....\n...#.......#\n........#...\n..#....#....\n..........#.\n        ...#....\n        .....#..\n        .#......\n        ......#.\n\n10R5L5R10L4R5L5\n";
+    let cube = parse_cube(contents);
+
+    //   0
+    // 123
+    //   45
+    assert_eq!(
+        cube.faces.map(|face| face.origin),
+        [(0, 8), (4, 0), (4, 4), (4, 8), (8, 8), (8, 12)]
+    );
+    assert_eq!(
+        cube.edges[0],
+        [
+            (1, Direction::Down),
+            (3, Direction::Down),
+            (2, Direction::Down),
+            (5, Direction::Left)
+        ]
+    );
+    // Worked example from the puzzle description
+    assert_eq!(
+        cube.step((3, 1, 3), Direction::Right),
+        ((5, 0, 2), Direction::Down)
+    );
+    assert_eq!(
+        cube.step((4, 3, 2), Direction::Down),
+        ((1, 3, 1), Direction::Up)
+    );
+}
+
 // #[test]
 // fn test_rotate_matrix() {
 //     {
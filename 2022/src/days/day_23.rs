@@ -1,4 +1,5 @@
-use std::{collections::HashMap, fmt, fs};
+use automaton::{Automaton, Cell, Grid, Movement, Moves, Neighbourhood, Neighbours, SparseGrid};
+use std::{fmt, fs};
 
 pub fn part1() -> String {
     let contents = get_input_file_contents();
@@ -21,17 +22,8 @@ fn get_input_file_contents() -> String {
     fs::read_to_string("inputs/input23").expect("Failed to open input file")
 }
 
-#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq)]
-struct Point {
-    row: i32,
-    col: i32,
-}
-
-impl Point {
-    fn new(row: i32, col: i32) -> Self {
-        Self { row, col }
-    }
-}
+/// A position as `[row, col]`.
+type Point = Cell<2>;
 
 #[derive(Debug, PartialEq)]
 enum Direction {
@@ -41,136 +33,100 @@ enum Direction {
     East,
 }
 
+impl Direction {
+    /// The offset to move one step in this direction.
+    fn offset(&self) -> Point {
+        match self {
+            Direction::North => [-1, 0],
+            Direction::South => [1, 0],
+            Direction::West => [0, -1],
+            Direction::East => [0, 1],
+        }
+    }
+
+    /// The offsets that must be empty to move in this direction, including the diagonals.
+    fn clearance(&self) -> [Point; 3] {
+        let [row, col] = self.offset();
+        if row == 0 {
+            [[-1, col], [0, col], [1, col]]
+        } else {
+            [[row, -1], [row, 0], [row, 1]]
+        }
+    }
+}
+
+/// Elves move one step in the first direction with room to, so long as anyone is nearby.
 #[derive(Debug)]
-struct Map {
-    elves: Vec<Point>,
+struct Elves {
     directions: [Direction; 4],
 }
 
+impl Movement<2> for Elves {
+    fn propose<G: Grid<2>>(&self, neighbours: &Neighbours<2, G>) -> Option<Point> {
+        if neighbours.count() == 0 {
+            return None;
+        }
+        let [row, col] = neighbours.cell();
+        self.directions
+            .iter()
+            .find(|direction| neighbours.count_at(&direction.clearance()) == 0)
+            .map(|direction| {
+                let [d_row, d_col] = direction.offset();
+                [row + d_row, col + d_col]
+            })
+    }
+
+    fn advance(&mut self) {
+        self.directions.rotate_left(1);
+    }
+}
+
+#[derive(Debug)]
+struct Map {
+    elves: Automaton<2, SparseGrid<2>, Moves<Elves>>,
+}
+
 impl Map {
     fn parse(contents: &str) -> Self {
         let mut elves = Vec::new();
         for (row, line) in contents.lines().enumerate() {
             for (col, ch) in line.chars().enumerate() {
                 if ch == '#' {
-                    elves.push(Point::new(row as i32, col as i32));
+                    elves.push([row as i32, col as i32]);
                 }
             }
         }
-        Self {
-            elves,
+        let rule = Moves(Elves {
             directions: [
                 Direction::North,
                 Direction::South,
                 Direction::West,
                 Direction::East,
             ],
+        });
+        Self {
+            elves: Automaton::new(elves, Neighbourhood::Moore, rule),
         }
     }
 
-    fn next_direction(&mut self) {
-        self.directions.rotate_left(1);
-    }
-
-    fn neighbors(elf: &Point, elves: &[Point]) -> [bool; 8] {
-        [
-            elves.contains(&Point::new(elf.row - 1, elf.col - 1)), // NW
-            elves.contains(&Point::new(elf.row - 1, elf.col)),     // N
-            elves.contains(&Point::new(elf.row - 1, elf.col + 1)), // NE
-            elves.contains(&Point::new(elf.row, elf.col + 1)),     // E
-            elves.contains(&Point::new(elf.row + 1, elf.col + 1)), // SE
-            elves.contains(&Point::new(elf.row + 1, elf.col)),     // S
-            elves.contains(&Point::new(elf.row + 1, elf.col - 1)), // SW
-            elves.contains(&Point::new(elf.row, elf.col - 1)),     // W
-        ]
-    }
-
+    /// Run a round, returning how many elves moved.
     fn move_elves(&mut self) -> usize {
-        // First half
-        let mut new_positions = Vec::with_capacity(self.elves.len());
-        for elf in &self.elves {
-            let neighbors = Self::neighbors(elf, &self.elves);
-            if neighbors.iter().map(|b| usize::from(*b)).sum::<usize>() == 0 {
-                new_positions.push(*elf);
-                continue;
-            }
-            let mut found_new_position = false;
-            for direction in &self.directions {
-                match direction {
-                    Direction::North => {
-                        if !neighbors[0] && !neighbors[1] && !neighbors[2] {
-                            new_positions.push(Point::new(elf.row - 1, elf.col));
-                            found_new_position = true;
-                            break;
-                        }
-                    }
-                    Direction::South => {
-                        if !neighbors[4] && !neighbors[5] && !neighbors[6] {
-                            new_positions.push(Point::new(elf.row + 1, elf.col));
-                            found_new_position = true;
-                            break;
-                        }
-                    }
-                    Direction::West => {
-                        if !neighbors[0] && !neighbors[6] && !neighbors[7] {
-                            new_positions.push(Point::new(elf.row, elf.col - 1));
-                            found_new_position = true;
-                            break;
-                        }
-                    }
-                    Direction::East => {
-                        if !neighbors[2] && !neighbors[3] && !neighbors[4] {
-                            new_positions.push(Point::new(elf.row, elf.col + 1));
-                            found_new_position = true;
-                            break;
-                        }
-                    }
-                }
-            }
-            if !found_new_position {
-                new_positions.push(*elf);
-            }
-        }
-
-        // Second half
-        let mut elves_moved = 0;
-        let mut new_position_count = HashMap::new();
-        for position in &new_positions {
-            *new_position_count.entry(*position).or_insert(0) += 1;
-        }
-        for (i, elf) in self.elves.iter_mut().enumerate() {
-            if new_position_count[&new_positions[i]] == 1 {
-                if *elf != new_positions[i] {
-                    elf.row = new_positions[i].row;
-                    elf.col = new_positions[i].col;
-                    elves_moved += 1;
-                }
-            }
-        }
-
-        self.next_direction();
-        elves_moved
+        self.elves.step()
     }
 
     fn smallest_rectangle(&self) -> (i32, i32, i32, i32) {
-        let min_row = self.elves.iter().map(|p| p.row).min().unwrap();
-        let max_row = self.elves.iter().map(|p| p.row).max().unwrap();
-        let min_col = self.elves.iter().map(|p| p.col).min().unwrap();
-        let max_col = self.elves.iter().map(|p| p.col).max().unwrap();
+        let elves = self.elves.grid().cells();
+        let min_row = elves.iter().map(|p| p[0]).min().unwrap();
+        let max_row = elves.iter().map(|p| p[0]).max().unwrap();
+        let min_col = elves.iter().map(|p| p[1]).min().unwrap();
+        let max_col = elves.iter().map(|p| p[1]).max().unwrap();
         (min_row, max_row, min_col, max_col)
     }
 
     fn get_ground_tiles_count(&self) -> usize {
-        let mut ground_tiles_count = 0;
         let (min_row, max_row, min_col, max_col) = self.smallest_rectangle();
-        for row in min_row..=max_row {
-            for col in min_col..=max_col {
-                if !self.elves.contains(&Point::new(row, col)) {
-                    ground_tiles_count += 1;
-                }
-            }
-        }
-        ground_tiles_count
+        let area = ((max_row - min_row + 1) * (max_col - min_col + 1)) as usize;
+        area - self.elves.live_count()
     }
 
     fn num_rounds_to_finalise(&mut self) -> usize {
@@ -189,23 +145,12 @@ impl Map {
 impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let (min_row, max_row, min_col, max_col) = self.smallest_rectangle();
-        write!(f, "   ")?;
-        for col in min_col..=max_col {
-            write!(f, "{:02} ", col)?;
-        }
-        writeln!(f)?;
         for row in min_row..=max_row {
-            write!(f, "{:02} ", row)?;
             for col in min_col..=max_col {
-                if self.elves.contains(&Point::new(row, col)) {
-                    let pos = self
-                        .elves
-                        .iter()
-                        .position(|e| *e == Point::new(row, col))
-                        .unwrap();
-                    write!(f, "{:02} ", pos)?;
+                if self.elves.grid().contains(&[row, col]) {
+                    write!(f, "#")?;
                 } else {
-                    write!(f, ".. ")?;
+                    write!(f, ".")?;
                 }
             }
             writeln!(f)?;
@@ -218,12 +163,12 @@ impl fmt::Display for Map {
 fn test_parse_map() {
     let contents = ".....\n..##.\n..#..\n.....\n..##.\n.....\n";
     let map = Map::parse(contents);
-    assert_eq!(map.elves.len(), 5);
-    assert!(map.elves.contains(&Point::new(1, 2)));
-    assert!(map.elves.contains(&Point::new(1, 3)));
-    assert!(map.elves.contains(&Point::new(2, 2)));
-    assert!(map.elves.contains(&Point::new(4, 2)));
-    assert!(map.elves.contains(&Point::new(4, 3)));
+    assert_eq!(map.elves.live_count(), 5);
+    assert!(map.elves.grid().contains(&[1, 2]));
+    assert!(map.elves.grid().contains(&[1, 3]));
+    assert!(map.elves.grid().contains(&[2, 2]));
+    assert!(map.elves.grid().contains(&[4, 2]));
+    assert!(map.elves.grid().contains(&[4, 3]));
 }
 
 #[test]
@@ -231,13 +176,13 @@ fn test_move_elves() {
     let contents = ".....\n..##.\n..#..\n.....\n..##.\n.....\n";
     let mut map = Map::parse(contents);
     map.move_elves();
-    assert!(map.elves.contains(&Point::new(0, 2)));
-    assert!(map.elves.contains(&Point::new(0, 3)));
-    assert!(map.elves.contains(&Point::new(2, 2)));
-    assert!(map.elves.contains(&Point::new(3, 3)));
-    assert!(map.elves.contains(&Point::new(4, 2)));
+    assert!(map.elves.grid().contains(&[0, 2]));
+    assert!(map.elves.grid().contains(&[0, 3]));
+    assert!(map.elves.grid().contains(&[2, 2]));
+    assert!(map.elves.grid().contains(&[3, 3]));
+    assert!(map.elves.grid().contains(&[4, 2]));
     assert_eq!(
-        map.directions,
+        map.elves.rule().0.directions,
         [
             Direction::South,
             Direction::West,
@@ -254,11 +199,11 @@ fn test_move_elves_3_rounds() {
     for _ in 0..3 {
         map.move_elves();
     }
-    assert!(map.elves.contains(&Point::new(0, 2)));
-    assert!(map.elves.contains(&Point::new(1, 4)));
-    assert!(map.elves.contains(&Point::new(2, 0)));
-    assert!(map.elves.contains(&Point::new(3, 4)));
-    assert!(map.elves.contains(&Point::new(5, 2)));
+    assert!(map.elves.grid().contains(&[0, 2]));
+    assert!(map.elves.grid().contains(&[1, 4]));
+    assert!(map.elves.grid().contains(&[2, 0]));
+    assert!(map.elves.grid().contains(&[3, 4]));
+    assert!(map.elves.grid().contains(&[5, 2]));
 }
 
 #[test]
@@ -270,6 +215,10 @@ fn test_get_ground_tiles_count() {
     }
     let ground_tiles_count = map.get_ground_tiles_count();
     assert_eq!(ground_tiles_count, 110);
+    assert_eq!(
+        map.to_string(),
+        "......#.....\n..........#.\n.#.#..#.....\n.....#......\n..#.....#..#\n#......##...\n....##......\n.#........#.\n...#.#..#...\n............\n...#..#..#..\n"
+    );
 }
 
 #[test]
@@ -1,4 +1,5 @@
-use std::{collections::HashMap, fs, ops::RangeInclusive};
+use intervals::Interval;
+use std::{collections::HashMap, fmt, fs};
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
@@ -48,21 +49,169 @@ impl TryFrom<&str> for Category {
 
 #[derive(Debug, PartialEq)]
 struct MapRange {
-    dst_start: u64,
-    src_start: u64,
-    len: u64,
+    dst_start: i64,
+    src_start: i64,
+    len: i64,
 }
 
 impl MapRange {
-    fn src_range(&self) -> RangeInclusive<u64> {
-        self.src_start..=self.src_start + (self.len - 1)
+    fn src_range(&self) -> Interval {
+        Interval::new(self.src_start, self.src_start + (self.len - 1))
+    }
+}
+
+/// A piece of a piecewise map, which shifts every value in `src` by `offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Piece {
+    src: Interval,
+    offset: i64,
+}
+
+impl Piece {
+    fn dst(&self) -> Interval {
+        Interval::new(self.src.start + self.offset, self.src.end + self.offset)
+    }
+}
+
+/// A function on the non-negative integers made of sorted, contiguous pieces that each shift
+/// their values by a constant.
+#[derive(Debug, Clone, PartialEq)]
+struct PiecewiseMap {
+    pieces: Vec<Piece>,
+}
+
+const DOMAIN: Interval = Interval {
+    start: 0,
+    end: i64::MAX / 2,
+};
+
+impl PiecewiseMap {
+    fn identity() -> Self {
+        PiecewiseMap {
+            pieces: vec![Piece {
+                src: DOMAIN,
+                offset: 0,
+            }],
+        }
+    }
+
+    /// The map sending each range's source to its destination, and everything else to itself.
+    fn from_ranges(ranges: &[MapRange]) -> Self {
+        let mut mapped = ranges
+            .iter()
+            .map(|r| Piece {
+                src: r.src_range(),
+                offset: r.dst_start - r.src_start,
+            })
+            .collect::<Vec<_>>();
+        mapped.sort_by_key(|p| p.src.start);
+
+        let mut pieces = Vec::new();
+        let mut next_start = DOMAIN.start;
+        for piece in mapped {
+            assert!(piece.src.start >= next_start, "overlapping map ranges");
+            if let Some(gap) = Interval::checked(next_start, piece.src.start - 1) {
+                pieces.push(Piece {
+                    src: gap,
+                    offset: 0,
+                });
+            }
+            next_start = piece.src.end + 1;
+            pieces.push(piece);
+        }
+        if let Some(gap) = Interval::checked(next_start, DOMAIN.end) {
+            pieces.push(Piece {
+                src: gap,
+                offset: 0,
+            });
+        }
+        PiecewiseMap::merged(pieces)
+    }
+
+    /// Joins neighbouring pieces with the same offset.
+    fn merged(pieces: Vec<Piece>) -> Self {
+        let mut merged: Vec<Piece> = Vec::with_capacity(pieces.len());
+        for piece in pieces {
+            match merged.last_mut() {
+                Some(last) if last.offset == piece.offset => last.src.end = piece.src.end,
+                _ => merged.push(piece),
+            }
+        }
+        PiecewiseMap { pieces: merged }
+    }
+
+    fn piece_containing(&self, value: i64) -> &Piece {
+        let i = self.pieces.partition_point(|p| p.src.end < value);
+        &self.pieces[i]
+    }
+
+    fn apply(&self, value: i64) -> i64 {
+        value + self.piece_containing(value).offset
+    }
+
+    /// The images of the values in `interval`, one for each piece it passes through.
+    fn apply_interval(&self, interval: Interval) -> Vec<Interval> {
+        let first = self.pieces.partition_point(|p| p.src.end < interval.start);
+        self.pieces[first..]
+            .iter()
+            .map_while(|p| p.src.intersection(&interval).map(|i| (i, p.offset)))
+            .map(|(i, offset)| Interval::new(i.start + offset, i.end + offset))
+            .collect()
+    }
+
+    /// Every value that maps to `value`.
+    #[allow(dead_code)]
+    fn preimage(&self, value: i64) -> Vec<i64> {
+        self.pieces
+            .iter()
+            .filter(|p| p.dst().contains(value))
+            .map(|p| value - p.offset)
+            .collect()
+    }
+
+    /// The map applying this one and then `next`.
+    fn then(&self, next: &PiecewiseMap) -> Self {
+        let mut pieces = Vec::new();
+        for piece in &self.pieces {
+            // Split this piece wherever its image crosses into a new piece of `next`
+            let mut start = piece.src.start;
+            while start <= piece.src.end {
+                let next_piece = next.piece_containing(start + piece.offset);
+                let end = piece.src.end.min(next_piece.src.end - piece.offset);
+                pieces.push(Piece {
+                    src: Interval::new(start, end),
+                    offset: piece.offset + next_piece.offset,
+                });
+                start = end + 1;
+            }
+        }
+        PiecewiseMap::merged(pieces)
+    }
+}
+
+impl fmt::Display for PiecewiseMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for piece in &self.pieces {
+            let dst = piece.dst();
+            if piece.src.end == DOMAIN.end {
+                writeln!(f, "{}.. -> {}..", piece.src.start, dst.start)?;
+            } else {
+                writeln!(
+                    f,
+                    "{}..={} -> {}..={}",
+                    piece.src.start, piece.src.end, dst.start, dst.end
+                )?;
+            }
+        }
+        Ok(())
     }
 }
 
 #[derive(Debug)]
 struct Almanac {
-    seeds: Vec<u64>,
-    maps: HashMap<(Category, Category), Vec<MapRange>>,
+    seeds: Vec<i64>,
+    /// Every map in turn, from seed all the way to location.
+    seed_to_location: PiecewiseMap,
 }
 
 impl Almanac {
@@ -74,65 +223,59 @@ impl Almanac {
             .unwrap()
             .split_whitespace()
             .skip(1)
-            .map(|n| n.parse::<u64>().unwrap())
-            .collect::<Vec<u64>>();
+            .map(|n| n.parse::<i64>().unwrap())
+            .collect::<Vec<i64>>();
 
-        let mut maps: HashMap<(Category, Category), Vec<MapRange>> = HashMap::new();
+        let mut maps: HashMap<Category, (Category, Vec<MapRange>)> = HashMap::new();
         let mut current_map_key = None;
         for line in lines.skip(1) {
             if line.is_empty() {
                 current_map_key = None;
             } else if let Some(current_map_key) = current_map_key.as_ref() {
-                let map = maps.get_mut(current_map_key).unwrap();
+                let (_, map) = maps.get_mut(current_map_key).unwrap();
                 let mut parts = line.split_whitespace();
                 map.push(MapRange {
-                    dst_start: parts.next().unwrap().parse::<u64>().unwrap(),
-                    src_start: parts.next().unwrap().parse::<u64>().unwrap(),
-                    len: parts.next().unwrap().parse::<u64>().unwrap(),
+                    dst_start: parts.next().unwrap().parse::<i64>().unwrap(),
+                    src_start: parts.next().unwrap().parse::<i64>().unwrap(),
+                    len: parts.next().unwrap().parse::<i64>().unwrap(),
                 });
             } else {
                 let mut parts = line.split_whitespace().next().unwrap().split('-');
                 let src_category = Category::try_from(parts.next().unwrap()).unwrap();
                 parts.next();
                 let dst_category = Category::try_from(parts.next().unwrap()).unwrap();
-                current_map_key = Some((src_category, dst_category));
-                maps.insert((src_category, dst_category), Vec::new());
+                current_map_key = Some(src_category);
+                maps.insert(src_category, (dst_category, Vec::new()));
             }
         }
 
-        Almanac { seeds, maps }
+        let mut seed_to_location = PiecewiseMap::identity();
+        let mut category = Category::Seed;
+        while category != Category::Location {
+            let (next_category, ranges) = maps
+                .get(&category)
+                .unwrap_or_else(|| panic!("no map from {category:?}"));
+            seed_to_location = seed_to_location.then(&PiecewiseMap::from_ranges(ranges));
+            category = *next_category;
+        }
+
+        Almanac {
+            seeds,
+            seed_to_location,
+        }
     }
 
-    fn seed_location(&self, seed: u64) -> u64 {
-        let mut current_category = Category::Seed;
-        let mut current_value = seed;
-        while current_category != Category::Location {
-            let new_category = {
-                let mut new_category = None;
-                for key in self.maps.keys() {
-                    if key.0 == current_category {
-                        new_category = Some(key.1);
-                        break;
-                    }
-                }
-                new_category.unwrap()
-            };
-            let map = self.maps.get(&(current_category, new_category)).unwrap();
-
-            for range in map.iter() {
-                if (range.src_start..range.src_start + range.len).contains(&current_value) {
-                    let offset = current_value - range.src_start;
-                    current_value = range.dst_start + offset;
-                    break;
-                }
-            }
+    fn seed_location(&self, seed: i64) -> i64 {
+        self.seed_to_location.apply(seed)
+    }
 
-            current_category = new_category;
-        }
-        current_value
+    /// Every seed that ends up at `location`.
+    #[allow(dead_code)]
+    fn location_seeds(&self, location: i64) -> Vec<i64> {
+        self.seed_to_location.preimage(location)
     }
 
-    fn min_initial_seed_location(&self) -> u64 {
+    fn min_initial_seed_location(&self) -> i64 {
         self.seeds
             .iter()
             .map(|seed| self.seed_location(*seed))
@@ -140,83 +283,16 @@ impl Almanac {
             .unwrap()
     }
 
-    fn min_initial_seed_location_ranges(&self) -> u64 {
-        let mut locations = Vec::with_capacity(self.seeds.len() / 2);
-        for seed_range in self.seeds.chunks_exact(2) {
-            let seed_range = seed_range[0]..=seed_range[0] + (seed_range[1] - 1);
-            let mut current_ranges = vec![seed_range.clone()];
-            let mut current_category = Category::Seed;
-            while current_category != Category::Location {
-                let new_category = {
-                    let mut new_category = None;
-                    for key in self.maps.keys() {
-                        if key.0 == current_category {
-                            new_category = Some(key.1);
-                            break;
-                        }
-                    }
-                    new_category.unwrap()
-                };
-                let map = self.maps.get(&(current_category, new_category)).unwrap();
-
-                let mut new_ranges = Vec::new();
-                while let Some(current_range) = current_ranges.pop() {
-                    if *current_range.end() == u64::MAX {
-                        panic!()
-                    }
-
-                    let mut found_mapping = false;
-                    for map_range in map {
-                        let src_range = map_range.src_range();
-                        if src_range.start() <= current_range.end()
-                            && src_range.end() >= current_range.start()
-                        {
-                            let src_overlap_start = src_range.start().max(current_range.start());
-                            let src_overlap_end = src_range.end().min(current_range.end());
-                            let dst_start =
-                                map_range.dst_start + src_overlap_start - src_range.start();
-                            let dst_end = map_range.dst_start + src_overlap_end - src_range.start();
-                            let dst_range = dst_start..=dst_end;
-
-                            new_ranges.push(dst_range);
-
-                            if src_overlap_start > current_range.start() {
-                                let pre_range =
-                                    *current_range.start()..=(src_overlap_start.saturating_sub(1));
-                                if !pre_range.is_empty() {
-                                    current_ranges.push(pre_range);
-                                }
-                            }
-
-                            if current_range.end() > src_overlap_end {
-                                let post_range =
-                                    (src_overlap_end.saturating_add(1))..=*current_range.end();
-                                if !post_range.is_empty() {
-                                    current_ranges.push(post_range);
-                                }
-                            }
-
-                            found_mapping = true;
-                            break;
-                        }
-                    }
-
-                    if !found_mapping {
-                        // We didn't find a mapped range, so we just pass it through directly
-                        new_ranges.push(current_range);
-                    }
-                }
-                current_ranges = new_ranges;
-                current_category = new_category;
-            }
-            let min_location = current_ranges
-                .iter()
-                .map(|r| *r.start())
-                .min()
-                .unwrap_or(u64::MAX);
-            locations.push(min_location);
-        }
-        *locations.iter().min().unwrap()
+    fn min_initial_seed_location_ranges(&self) -> i64 {
+        self.seeds
+            .chunks_exact(2)
+            .flat_map(|seed_range| {
+                let seeds = Interval::new(seed_range[0], seed_range[0] + (seed_range[1] - 1));
+                self.seed_to_location.apply_interval(seeds)
+            })
+            .map(|locations| locations.start)
+            .min()
+            .unwrap()
     }
 }
 
@@ -226,19 +302,8 @@ fn test_parse_almanac() {
     let almanac = Almanac::parse(input);
     assert_eq!(almanac.seeds, vec![79, 14, 55, 13]);
     assert_eq!(
-        almanac.maps.get(&(Category::Seed, Category::Soil)),
-        Some(&vec![
-            MapRange {
-                dst_start: 50,
-                src_start: 98,
-                len: 2,
-            },
-            MapRange {
-                dst_start: 52,
-                src_start: 50,
-                len: 48,
-            }
-        ])
+        almanac.seed_to_location.to_string(),
+        "0..=13 -> 22..=35\n14..=14 -> 43..=43\n15..=21 -> 36..=42\n22..=25 -> 90..=93\n26..=43 -> 1..=18\n44..=49 -> 61..=66\n50..=51 -> 20..=21\n52..=53 -> 44..=45\n54..=58 -> 85..=89\n59..=61 -> 94..=96\n62..=65 -> 56..=59\n66..=68 -> 97..=99\n69..=69 -> 73..=73\n70..=70 -> 0..=0\n71..=81 -> 74..=84\n82..=91 -> 46..=55\n92..=92 -> 60..=60\n93..=97 -> 68..=72\n98..=98 -> 67..=67\n99..=99 -> 19..=19\n100.. -> 100..\n"
     );
 }
 
@@ -265,3 +330,42 @@ fn test_min_initial_seed_location_ranges() {
     let almanac = Almanac::parse(input);
     assert_eq!(almanac.min_initial_seed_location_ranges(), 46);
 }
+
+#[test]
+fn test_location_seeds() {
+    let input = "seeds: 79 14 55 13\n\nseed-to-soil map:\n50 98 2\n52 50 48\n\nsoil-to-fertilizer map:\n0 15 37\n37 52 2\n39 0 15\n\nfertilizer-to-water map:\n49 53 8\n0 11 42\n42 0 7\n57 7 4\n\nwater-to-light map:\n88 18 7\n18 25 70\n\nlight-to-temperature map:\n45 77 23\n81 45 19\n68 64 13\n\ntemperature-to-humidity map:\n0 69 1\n1 0 69\n\nhumidity-to-location map:\n60 56 37\n56 93 4\n";
+    let almanac = Almanac::parse(input);
+    assert_eq!(almanac.location_seeds(82), vec![79]);
+    assert_eq!(almanac.location_seeds(46), vec![82]);
+    for seed in 0..200 {
+        let location = almanac.seed_location(seed);
+        assert!(almanac.location_seeds(location).contains(&seed));
+    }
+}
+
+#[test]
+fn test_compose_piecewise_maps() {
+    let double_shift = PiecewiseMap::from_ranges(&[MapRange {
+        dst_start: 10,
+        src_start: 0,
+        len: 5,
+    }])
+    .then(&PiecewiseMap::from_ranges(&[MapRange {
+        dst_start: 100,
+        src_start: 12,
+        len: 10,
+    }]));
+    assert_eq!(
+        double_shift.to_string(),
+        "0..=1 -> 10..=11\n2..=4 -> 100..=102\n5..=11 -> 5..=11\n12..=21 -> 100..=109\n22.. -> 22..\n"
+    );
+    assert_eq!(
+        double_shift.apply_interval(Interval::new(1, 13)),
+        vec![
+            Interval::new(11, 11),
+            Interval::new(100, 102),
+            Interval::new(5, 11),
+            Interval::new(100, 101)
+        ]
+    );
+}
@@ -1,4 +1,5 @@
-use std::{collections::HashSet, fs};
+use crate::polygon::{Point, Polygon};
+use std::fs;
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
@@ -151,128 +152,26 @@ impl Map {
         idx % self.width
     }
 
-    fn height(&self) -> usize {
-        self.tiles.len() / self.width
-    }
-
     fn furthest_point_in_loop(&self) -> usize {
         let loop_ = self.find_loop();
         loop_.len() / 2
     }
 
-    fn tile_has_direction(&self, index: usize, dir: Direction) -> bool {
-        if index == self.start_index() {
-            self.start_tile_type().has_direction(dir)
-        } else {
-            self.tiles[index].has_direction(dir)
-        }
-    }
-
-    fn expanded_tiles(&self) -> Vec<Tile> {
-        let loop_ = self.find_loop();
-
-        let mut expanded_tiles = Vec::with_capacity((2 * self.height() - 1) * (2 * self.width - 1));
-        for row in 0..self.height() {
-            for col in 0..self.width {
-                let i = row * self.width + col;
-                if loop_.contains(&i) {
-                    expanded_tiles.push(self.tiles[i]);
-                } else {
-                    expanded_tiles.push(Tile::Ground);
-                }
-                if col != self.width - 1 {
-                    if self.tile_has_direction(i, Direction::East)
-                        && self.tile_has_direction(i + 1, Direction::West)
-                        && loop_.contains(&i)
-                        && loop_.contains(&(i + 1))
-                    {
-                        // This expanded tile is part of the loop
-                        expanded_tiles.push(Tile::EastWest);
-                    } else {
-                        // This expanded tile is not part of the loop
-                        expanded_tiles.push(Tile::Ground);
-                    }
-                }
-            }
-            if row != self.height() - 1 {
-                for col in 0..self.width {
-                    let i = row * self.width + col;
-                    if self.tile_has_direction(i, Direction::South)
-                        && self.tile_has_direction(i + self.width, Direction::North)
-                        && loop_.contains(&i)
-                        && loop_.contains(&(i + self.width))
-                    {
-                        expanded_tiles.push(Tile::NorthSouth);
-                    } else {
-                        expanded_tiles.push(Tile::Ground);
-                    }
-                    if col != self.width - 1 {
-                        expanded_tiles.push(Tile::Ground);
-                    }
-                }
-            }
-        }
-
-        expanded_tiles
+    /// The loop as a lattice polygon through the centre of each of its tiles.
+    fn loop_polygon(&self) -> Polygon {
+        let vertices = self
+            .find_loop()
+            .into_iter()
+            .map(|i| Point::new(self.index_to_col(i) as i64, self.index_to_row(i) as i64))
+            .collect();
+        Polygon::new(vertices)
     }
 
     fn tiles_enclosed_by_loop(&self) -> usize {
-        let expanded_tiles = self.expanded_tiles();
-        let loop_ = self.find_loop();
-
-        let mut inside_count = 0;
-        for row in 0..self.height() {
-            for col in 0..self.width {
-                let i = row * self.width + col;
-                if loop_.contains(&i) {
-                    continue;
-                }
-
-                let j = (row * 2) * (2 * self.width - 1) + (col * 2);
-                if !path_to_edge(
-                    j,
-                    &expanded_tiles,
-                    2 * self.width - 1,
-                    2 * self.height() - 1,
-                ) {
-                    inside_count += 1;
-                }
-            }
-        }
-
-        inside_count
-    }
-}
-
-fn path_to_edge(start: usize, tiles: &[Tile], width: usize, height: usize) -> bool {
-    let mut visited = HashSet::new();
-    let mut stack = Vec::new();
-    stack.push(start);
-    while let Some(tile) = stack.pop() {
-        let row = tile / width;
-        let col = tile % width;
-        if row == 0 || row == height - 1 || col == 0 || col == width - 1 {
-            return true;
-        }
-
-        if !visited.contains(&tile) {
-            visited.insert(tile);
-
-            if row > 0 && tiles[tile - width] == Tile::Ground {
-                stack.push(tile - width);
-            }
-            if row < height - 1 && tiles[tile + width] == Tile::Ground {
-                stack.push(tile + width);
-            }
-            if col > 0 && tiles[tile - 1] == Tile::Ground {
-                stack.push(tile - 1);
-            }
-            if col < width - 1 && tiles[tile + 1] == Tile::Ground {
-                stack.push(tile + 1);
-            }
-        }
+        // Every loop tile is a boundary point one step from the next, so Pick's theorem gives
+        // the tiles inside straight from the shoelace area
+        self.loop_polygon().interior_points() as usize
     }
-    false
 }
 
 impl Tile {
@@ -451,3 +350,19 @@ fn test_tiles_enclosed_by_loop() {
         assert_eq!(map.tiles_enclosed_by_loop(), 10);
     }
 }
+
+#[test]
+fn test_loop_polygon() {
+    let input = "...........\n.S-------7.\n.|F-----7|.\n.||.....||.\n.||.....||.\n.|L-7.F-J|.\n.|..|.|..|.\n.L--J.L--J.\n...........\n";
+    let map = Map::parse(input);
+    let polygon = map.loop_polygon();
+    assert_eq!(polygon.boundary_points(), map.find_loop().len() as u128 - 1);
+
+    let inside = (0..map.tiles.len())
+        .filter(|&i| {
+            let point = Point::new(map.index_to_col(i) as i64, map.index_to_row(i) as i64);
+            polygon.locate(point) == crate::polygon::Location::Inside
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(inside, vec![6 * 11 + 2, 6 * 11 + 3, 6 * 11 + 7, 6 * 11 + 8]);
+}
@@ -1,4 +1,5 @@
-use std::{collections::HashMap, fs};
+use rand::Rng;
+use std::{fs, ops::Add};
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
@@ -64,26 +65,187 @@ impl Row {
     }
 
     fn arrangements_count(&self) -> usize {
-        arrangements_count(
-            &self.springs,
-            &self.damaged_groups,
-            0,
-            0,
-            &mut HashMap::new(),
-        )
+        self.arrangements_count_as::<usize>()
+    }
+
+    /// Counts the arrangements in whatever type is big enough for this row.
+    fn arrangements_count_as<T: Copy + From<u8> + Add<Output = T>>(&self) -> T {
+        self.arrangement_table()[0][0]
+    }
+
+    /// The number of ways to arrange `springs[i..]` into `damaged_groups[g..]`, for every `i`
+    /// and `g`, built from the end of the row in `O(springs * groups)`.
+    fn arrangement_table<T: Copy + From<u8> + Add<Output = T>>(&self) -> Vec<Vec<T>> {
+        let (n, groups) = (self.springs.len(), self.damaged_groups.len());
+
+        let run = self.runs();
+
+        let mut ways = vec![vec![T::from(0); groups + 1]; n + 1];
+        ways[n][groups] = T::from(1);
+        for i in (0..n).rev() {
+            for g in 0..=groups {
+                let mut count = T::from(0);
+                if self.springs[i] != Spring::Damaged {
+                    count = count + ways[i + 1][g];
+                }
+                if let Some(next) = self.group_end(i, g, &run) {
+                    count = count + ways[next][g + 1];
+                }
+                ways[i][g] = count;
+            }
+        }
+        ways
+    }
+
+    /// Where the row carries on after placing group `g` at `i`, if it fits there.
+    fn group_end(&self, i: usize, g: usize, run: &[usize]) -> Option<usize> {
+        let len = *self.damaged_groups.get(g)?;
+        let end = i + len;
+        if run[i] < len || self.springs.get(end) == Some(&Spring::Damaged) {
+            return None;
+        }
+        // Skip the operational spring separating this group from the next
+        Some((end + 1).min(self.springs.len()))
+    }
+
+    /// How many springs from each position on could be damaged.
+    fn runs(&self) -> Vec<usize> {
+        let mut run = vec![0; self.springs.len() + 1];
+        for i in (0..self.springs.len()).rev() {
+            if self.springs[i] != Spring::Operational {
+                run[i] = run[i + 1] + 1;
+            }
+        }
+        run
+    }
+
+    /// Every arrangement of the row, with operational springs before damaged ones.
+    #[allow(dead_code)]
+    fn arrangements(&self) -> impl Iterator<Item = Row> + '_ {
+        let ways = self.arrangement_table::<u128>();
+        let run = self.runs();
+        let mut stack = Vec::new();
+        if ways[0][0] > 0 {
+            stack.push((0, 0, Vec::with_capacity(self.springs.len())));
+        }
+        std::iter::from_fn(move || {
+            while let Some((i, g, springs)) = stack.pop() {
+                if i == self.springs.len() {
+                    return Some(Row {
+                        springs,
+                        damaged_groups: self.damaged_groups.clone(),
+                    });
+                }
+                // Only follow choices that lead to an arrangement, pushing the group first so
+                // the operational choice comes out first
+                if let Some(next) = self.group_end(i, g, &run) {
+                    if ways[next][g + 1] > 0 {
+                        let mut springs = springs.clone();
+                        springs.extend(self.placed_group(g, i));
+                        stack.push((next, g + 1, springs));
+                    }
+                }
+                if self.springs[i] != Spring::Damaged && ways[i + 1][g] > 0 {
+                    let mut springs = springs;
+                    springs.push(Spring::Operational);
+                    stack.push((i + 1, g, springs));
+                }
+            }
+            None
+        })
+    }
+
+    /// The arrangement at `rank` in the order of `arrangements`, without listing the others.
+    #[allow(dead_code)]
+    fn nth_arrangement(&self, mut rank: u128) -> Option<Row> {
+        let ways = self.arrangement_table::<u128>();
+        if rank >= ways[0][0] {
+            return None;
+        }
+        let run = self.runs();
+        let (mut i, mut g) = (0, 0);
+        let mut springs = Vec::with_capacity(self.springs.len());
+        while i < self.springs.len() {
+            if self.springs[i] != Spring::Damaged {
+                let operational = ways[i + 1][g];
+                if rank < operational {
+                    springs.push(Spring::Operational);
+                    i += 1;
+                    continue;
+                }
+                rank -= operational;
+            }
+            let next = self.group_end(i, g, &run).unwrap();
+            springs.extend(self.placed_group(g, i));
+            (i, g) = (next, g + 1);
+        }
+        Some(Row {
+            springs,
+            damaged_groups: self.damaged_groups.clone(),
+        })
+    }
+
+    /// An arrangement chosen uniformly at random, if there are any.
+    #[allow(dead_code)]
+    fn sample_arrangement<R: Rng>(&self, rng: &mut R) -> Option<Row> {
+        let count = self.arrangements_count_as::<u128>();
+        if count == 0 {
+            return None;
+        }
+        self.nth_arrangement(rng.gen_range(0..count))
+    }
+
+    /// The springs from placing group `g` at `start`, including the operational spring after.
+    fn placed_group(&self, g: usize, start: usize) -> impl Iterator<Item = Spring> {
+        let len = self.damaged_groups[g];
+        let separator = start + len < self.springs.len();
+        std::iter::repeat_n(Spring::Damaged, len).chain(separator.then_some(Spring::Operational))
+    }
+
+    /// What each spring must be in every arrangement, leaving it unknown where arrangements
+    /// disagree, or `None` if the row can't be arranged at all.
+    #[allow(dead_code)]
+    fn forced_springs(&self) -> Option<Vec<Spring>> {
+        if self.arrangements_count_as::<u128>() == 0 {
+            return None;
+        }
+        let mut forced = self.springs.clone();
+        for (i, forced_spring) in forced.iter_mut().enumerate() {
+            if *forced_spring != Spring::Unknown {
+                continue;
+            }
+            let possible = [Spring::Operational, Spring::Damaged].map(|spring| {
+                let mut row = Row {
+                    springs: self.springs.clone(),
+                    damaged_groups: self.damaged_groups.clone(),
+                };
+                row.springs[i] = spring;
+                row.arrangements_count_as::<u128>() > 0
+            });
+            *forced_spring = match possible {
+                [true, false] => Spring::Operational,
+                [false, true] => Spring::Damaged,
+                _ => Spring::Unknown,
+            };
+        }
+        Some(forced)
     }
 
     fn unfold(&self) -> Row {
-        let mut unfolded_springs = Vec::with_capacity(self.springs.len() * 5 + self.springs.len());
-        for i in 0..5 {
+        self.unfold_by(5)
+    }
+
+    fn unfold_by(&self, factor: usize) -> Row {
+        let mut unfolded_springs = Vec::with_capacity((self.springs.len() + 1) * factor);
+        for i in 0..factor {
             unfolded_springs.extend(&self.springs);
-            if i < 4 {
+            if i + 1 < factor {
                 unfolded_springs.push(Spring::Unknown);
             }
         }
         Row {
             springs: unfolded_springs,
-            damaged_groups: self.damaged_groups.repeat(5),
+            damaged_groups: self.damaged_groups.repeat(factor),
         }
     }
 
@@ -111,61 +273,69 @@ impl std::fmt::Display for Row {
     }
 }
 
-fn arrangements_count(
-    springs: &[Spring],
-    groups: &[usize],
-    si: usize,
-    gi: usize,
-    states: &mut HashMap<(usize, usize), usize>,
-) -> usize {
-    if let Some(count) = states.get(&(si, gi)) {
-        *count
-    } else {
-        if gi >= groups.len() {
-            // No more groups, so this is a final valid arrangement
-            return 1;
-        }
-        let group = groups[gi];
-        let remaining_springs = springs.len() - si;
-        if remaining_springs < group {
-            // No room for group, so this is not a valid arrangement
-            return 0;
-        }
-        // Find all options for the start of the next group
-        let mut count = 0;
-        for i in si..(si + remaining_springs - group + 1) {
-            // If the group:
-            // - Fits in unknown/damaged springs,
-            // - Is not immediately followed by a damaged spring,
-            // - If is the last group, is not followed by any damaged springs,
-            // - And is not preceded by any damaged strings,
-            // then it's a valid starting place for the group.
-            if springs[i..i + group]
-                .iter()
-                .all(|s| matches!(s, Spring::Unknown | Spring::Damaged))
-                && (i + group == springs.len() || !matches!(springs[i + group], Spring::Damaged))
-                && !springs[si..i].iter().any(|s| matches!(s, Spring::Damaged))
-                && (gi < groups.len() - 1
-                    || !springs[i + group..]
-                        .iter()
-                        .any(|s| matches!(s, Spring::Damaged)))
-            {
-                if i + group == springs.len() {
-                    // This group completes the springs
-                    if gi == groups.len() - 1 {
-                        // It's also the last group, so this is a valid arrangement
-                        count += 1;
-                    } else {
-                        // There's more groups, but no more springs, so this is not a valid arrangement
-                        count += 0;
+/// A picture puzzle where every row and every column is a `Row` of springs with known groups.
+#[allow(dead_code)]
+#[derive(Debug)]
+struct Nonogram {
+    row_groups: Vec<Vec<usize>>,
+    column_groups: Vec<Vec<usize>>,
+}
+
+#[allow(dead_code)]
+impl Nonogram {
+    /// Fills in the grid, or returns `None` if the clues contradict each other. Where the clues
+    /// allow several pictures, any one of them is returned.
+    fn solve(&self) -> Option<Vec<Vec<Spring>>> {
+        let grid = vec![vec![Spring::Unknown; self.column_groups.len()]; self.row_groups.len()];
+        self.solve_from(grid)
+    }
+
+    fn solve_from(&self, mut grid: Vec<Vec<Spring>>) -> Option<Vec<Vec<Spring>>> {
+        // Fill in whatever each line forces until nothing changes
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (y, groups) in self.row_groups.iter().enumerate() {
+                let line = grid[y].clone();
+                let forced = Nonogram::line(line.clone(), groups).forced_springs()?;
+                if forced != line {
+                    grid[y] = forced;
+                    changed = true;
+                }
+            }
+            for (x, groups) in self.column_groups.iter().enumerate() {
+                let line = grid.iter().map(|row| row[x]).collect::<Vec<_>>();
+                let forced = Nonogram::line(line.clone(), groups).forced_springs()?;
+                if forced != line {
+                    for (row, spring) in grid.iter_mut().zip(forced) {
+                        row[x] = spring;
                     }
-                } else {
-                    count += arrangements_count(springs, groups, i + group + 1, gi + 1, states);
+                    changed = true;
                 }
             }
         }
-        states.insert((si, gi), count);
-        count
+
+        // Then guess the first unknown spring both ways
+        let Some((y, x)) = (0..grid.len())
+            .flat_map(|y| (0..grid[y].len()).map(move |x| (y, x)))
+            .find(|&(y, x)| grid[y][x] == Spring::Unknown)
+        else {
+            return Some(grid);
+        };
+        [Spring::Damaged, Spring::Operational]
+            .into_iter()
+            .find_map(|guess| {
+                let mut guessed = grid.clone();
+                guessed[y][x] = guess;
+                self.solve_from(guessed)
+            })
+    }
+
+    fn line(springs: Vec<Spring>, groups: &[usize]) -> Row {
+        Row {
+            springs,
+            damaged_groups: groups.to_vec(),
+        }
     }
 }
 
@@ -252,3 +422,92 @@ fn test_arrangements_count_unfolded() {
         506250
     );
 }
+
+#[test]
+fn test_large_unfold_factor() {
+    let row = Row::parse("?###???????? 3,2,1");
+    assert_eq!(row.unfold_by(5).arrangements_count_as::<u128>(), 506250);
+    // Far too many arrangements for a u64
+    let count = Row::parse("???????? 1")
+        .unfold_by(20)
+        .arrangements_count_as::<u128>();
+    assert!(count > u64::MAX as u128);
+}
+
+#[test]
+fn test_arrangements() {
+    let row = Row::parse("?###???????? 3,2,1");
+    let arrangements = row.arrangements().collect::<Vec<Row>>();
+    assert_eq!(arrangements.len(), 10);
+    assert_eq!(arrangements[0].to_string(), ".###....##.# 3,2,1");
+    assert_eq!(arrangements[9].to_string(), ".###.##.#... 3,2,1");
+    for (rank, arrangement) in arrangements.iter().enumerate() {
+        assert_eq!(
+            row.nth_arrangement(rank as u128).as_ref(),
+            Some(arrangement)
+        );
+        assert_eq!(arrangement.arrangements_count(), 1);
+    }
+    assert_eq!(row.nth_arrangement(10), None);
+    assert_eq!(Row::parse("#.# 3").arrangements().count(), 0);
+}
+
+#[test]
+fn test_sample_arrangement() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let row = Row::parse(".??..??...?##. 1,1,3");
+    let mut rng = StdRng::seed_from_u64(12);
+    let mut seen = [0; 4];
+    for _ in 0..400 {
+        let sample = row.sample_arrangement(&mut rng).unwrap();
+        let rank = row.arrangements().position(|r| r == sample).unwrap();
+        seen[rank] += 1;
+    }
+    assert!(seen.iter().all(|&n| n > 50), "{seen:?}");
+    assert_eq!(Row::parse("## 1").sample_arrangement(&mut rng), None);
+}
+
+#[test]
+fn test_forced_springs() {
+    let forced = Row::parse("?????????? 3,5").forced_springs().unwrap();
+    assert_eq!(
+        Row {
+            springs: forced,
+            damaged_groups: vec![3, 5]
+        }
+        .to_string(),
+        "?##??####? 3,5"
+    );
+    assert_eq!(Row::parse("?.? 2").forced_springs(), None);
+}
+
+#[test]
+fn test_nonogram() {
+    let nonogram = Nonogram {
+        row_groups: vec![vec![1, 1], vec![5], vec![5], vec![3], vec![1]],
+        column_groups: vec![vec![2], vec![4], vec![4], vec![4], vec![2]],
+    };
+    let picture = nonogram
+        .solve()
+        .unwrap()
+        .iter()
+        .map(|row| row.iter().map(|s| char::from(*s)).collect::<String>())
+        .collect::<Vec<String>>();
+    assert_eq!(picture, vec![".#.#.", "#####", "#####", ".###.", "..#.."]);
+
+    // Two pictures fit these clues, so either will do
+    let ambiguous = Nonogram {
+        row_groups: vec![vec![1], vec![1]],
+        column_groups: vec![vec![1], vec![1]],
+    };
+    let picture = ambiguous.solve().unwrap();
+    assert_ne!(picture[0][0], picture[0][1]);
+    assert_ne!(picture[0][0], picture[1][0]);
+
+    let impossible = Nonogram {
+        row_groups: vec![vec![2], vec![]],
+        column_groups: vec![vec![2], vec![]],
+    };
+    assert_eq!(impossible.solve(), None);
+}
@@ -1,4 +1,7 @@
-use std::{collections::HashSet, fs};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+};
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
@@ -18,10 +21,19 @@ fn get_input_file_contents() -> String {
 
 #[derive(Debug)]
 struct Contraption {
-    tiles: Vec<Tile>,
+    tiles: Vec<Box<dyn Optic>>,
     width: usize,
 }
 
+/// Anything that can sit on a tile and redirect the beams passing through it.
+trait Optic: fmt::Debug {
+    /// The directions beams leave in, given a beam arriving travelling in `dir`.
+    fn deflect(&self, dir: Dir) -> Vec<Dir>;
+
+    #[allow(dead_code)]
+    fn symbol(&self) -> char;
+}
+
 #[derive(Debug, PartialEq)]
 enum Tile {
     Empty,
@@ -52,15 +64,35 @@ enum Dir {
     Down,
 }
 
+/// Which tiles a beam crosses from one starting point up to the next split, where it carries
+/// on as new segments.
+#[derive(Debug)]
+struct Segment {
+    tiles: Vec<usize>,
+    next: Vec<usize>,
+}
+
+/// The beam paths through a contraption, broken into segments that are shared between every
+/// entry point that reaches them.
+#[derive(Debug)]
+struct BeamTracer {
+    tile_count: usize,
+    segments: Vec<Segment>,
+    /// The segment starting with a beam on each tile heading in each direction, if any does.
+    segment_starts: HashMap<(usize, Dir), usize>,
+}
+
 impl Contraption {
     fn parse(input: &str) -> Self {
-        let mut tiles = Vec::new();
+        let mut tiles: Vec<Box<dyn Optic>> = Vec::new();
         let mut width = None;
         for line in input.lines() {
             if width.is_none() {
                 width = Some(line.len());
             }
-            tiles.extend(line.chars().map(Tile::parse));
+            for ch in line.chars() {
+                tiles.push(Box::new(Tile::parse(ch)));
+            }
         }
         Contraption {
             tiles,
@@ -72,132 +104,259 @@ impl Contraption {
         self.tiles.len() / self.width
     }
 
+    /// Puts a different optical element on a tile.
+    #[allow(dead_code)]
+    fn place(&mut self, row: usize, col: usize, optic: Box<dyn Optic>) {
+        self.tiles[row * self.width + col] = optic;
+    }
+
+    /// The tile next to `index` in `dir`, if it's in the contraption.
+    fn step(&self, index: usize, dir: Dir) -> Option<usize> {
+        let (row, col) = (index / self.width, index % self.width);
+        match dir {
+            Dir::Left => (col > 0).then(|| index - 1),
+            Dir::Right => (col + 1 < self.width).then(|| index + 1),
+            Dir::Up => (row > 0).then(|| index - self.width),
+            Dir::Down => (row + 1 < self.height()).then(|| index + self.width),
+        }
+    }
+
+    /// Every beam entering from the edge of the contraption, heading inwards.
+    fn edge_entries(&self) -> Vec<Beam> {
+        let (width, height) = (self.width, self.height());
+        let mut entries = Vec::with_capacity(2 * (width + height));
+        for col in 0..width {
+            entries.push(Beam {
+                row: 0,
+                col,
+                dir: Dir::Down,
+            });
+            entries.push(Beam {
+                row: height - 1,
+                col,
+                dir: Dir::Up,
+            });
+        }
+        for row in 0..height {
+            entries.push(Beam {
+                row,
+                col: 0,
+                dir: Dir::Right,
+            });
+            entries.push(Beam {
+                row,
+                col: width - 1,
+                dir: Dir::Left,
+            });
+        }
+        entries
+    }
+
     fn energized_tiles(&self, start_row: usize, start_col: usize, start_dir: Dir) -> usize {
-        let mut energized_tiles = vec![false; self.tiles.len()];
-        let mut beams = vec![Beam {
+        let entry = Beam {
             row: start_row,
             col: start_col,
             dir: start_dir,
-        }];
-        let mut past_beams: HashSet<(usize, Dir)> = HashSet::new();
-
-        while let Some(mut beam) = beams.pop() {
-            loop {
-                energized_tiles[beam.index(self.width)] = true;
-
-                // Deal with mirrors and splitters
-                match self.tiles[beam.index(self.width)] {
-                    Tile::Empty => {}
-                    Tile::ForwardMirror => match beam.dir {
-                        Dir::Left => beam.dir = Dir::Down,
-                        Dir::Right => beam.dir = Dir::Up,
-                        Dir::Up => beam.dir = Dir::Right,
-                        Dir::Down => beam.dir = Dir::Left,
-                    },
-                    Tile::BackwardMirror => match beam.dir {
-                        Dir::Left => beam.dir = Dir::Up,
-                        Dir::Right => beam.dir = Dir::Down,
-                        Dir::Up => beam.dir = Dir::Left,
-                        Dir::Down => beam.dir = Dir::Right,
-                    },
-                    Tile::VertSplitter => match beam.dir {
-                        Dir::Left | Dir::Right => {
-                            beam.dir = Dir::Up;
-                            if past_beams.insert((beam.index(self.width), Dir::Down)) {
-                                beams.push(Beam {
-                                    row: beam.row,
-                                    col: beam.col,
-                                    dir: Dir::Down,
-                                });
-                            }
-                        }
-                        Dir::Up | Dir::Down => {}
-                    },
-                    Tile::HorzSplitter => match beam.dir {
-                        Dir::Up | Dir::Down => {
-                            beam.dir = Dir::Left;
-                            if past_beams.insert((beam.index(self.width), Dir::Right)) {
-                                beams.push(Beam {
-                                    row: beam.row,
-                                    col: beam.col,
-                                    dir: Dir::Right,
-                                });
-                            }
-                        }
-                        Dir::Left | Dir::Right => {}
-                    },
+        };
+        let tracer = BeamTracer::new(self, std::slice::from_ref(&entry));
+        tracer.energized_count(entry.index(self.width), entry.dir)
+    }
+
+    fn max_energized_tiles(&self) -> usize {
+        let entries = self.edge_entries();
+        let tracer = BeamTracer::new(self, &entries);
+        entries
+            .iter()
+            .map(|beam| tracer.energized_count(beam.index(self.width), beam.dir))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// How many of the beams entering from the edge energize each tile.
+    #[allow(dead_code)]
+    fn heatmap(&self) -> Heatmap {
+        let entries = self.edge_entries();
+        let tracer = BeamTracer::new(self, &entries);
+        let mut counts = vec![0; self.tiles.len()];
+        for beam in &entries {
+            for (count, energized) in counts
+                .iter_mut()
+                .zip(tracer.energized(beam.index(self.width), beam.dir))
+            {
+                *count += energized as u32;
+            }
+        }
+        Heatmap {
+            counts,
+            width: self.width,
+        }
+    }
+
+    /// Draws the contraption, letting `overlay` replace the symbol on any tile.
+    #[allow(dead_code)]
+    fn render_with<F: Fn(usize, &dyn Optic) -> Option<char>>(&self, overlay: F) -> String {
+        let mut rendered = String::with_capacity(self.tiles.len() + self.height());
+        for (i, tile) in self.tiles.iter().enumerate() {
+            rendered.push(overlay(i, tile.as_ref()).unwrap_or_else(|| tile.symbol()));
+            if (i + 1) % self.width == 0 {
+                rendered.push('\n');
+            }
+        }
+        rendered
+    }
+}
+
+impl BeamTracer {
+    /// Traces the beams from each of `entries` through the contraption.
+    fn new(contraption: &Contraption, entries: &[Beam]) -> Self {
+        // Segments start at the entries and wherever a beam is split
+        let mut starts = entries
+            .iter()
+            .map(|beam| (beam.index(contraption.width), beam.dir))
+            .collect::<Vec<_>>();
+        for (index, tile) in contraption.tiles.iter().enumerate() {
+            for dir in [Dir::Left, Dir::Right, Dir::Up, Dir::Down] {
+                let outgoing = tile.deflect(dir);
+                if outgoing.len() > 1 {
+                    starts.extend(
+                        outgoing
+                            .into_iter()
+                            .filter_map(|out| Some((contraption.step(index, out)?, out))),
+                    );
                 }
+            }
+        }
+        let mut segment_starts = HashMap::new();
+        for start in starts {
+            let next_id = segment_starts.len();
+            segment_starts.entry(start).or_insert(next_id);
+        }
 
-                // Move beam
-                match &beam.dir {
-                    Dir::Left => {
-                        if beam.col == 0 {
-                            // Left the contraption
-                            break;
-                        } else {
-                            beam.col -= 1;
-                        }
-                    }
-                    Dir::Right => {
-                        if beam.col == self.width - 1 {
-                            // Left the contraption
-                            break;
-                        } else {
-                            beam.col += 1;
-                        }
-                    }
-                    Dir::Up => {
-                        if beam.row == 0 {
-                            // Left the contraption
-                            break;
-                        } else {
-                            beam.row -= 1;
-                        }
-                    }
-                    Dir::Down => {
-                        if beam.row == self.height() - 1 {
-                            // Left the contraption
-                            break;
-                        } else {
-                            beam.row += 1;
-                        }
+        let mut ordered_starts = vec![(0, Dir::Left); segment_starts.len()];
+        for (&start, &id) in &segment_starts {
+            ordered_starts[id] = start;
+        }
+        let segments = ordered_starts
+            .into_iter()
+            .map(|start| BeamTracer::trace_segment(contraption, start, &segment_starts))
+            .collect();
+
+        BeamTracer {
+            tile_count: contraption.tiles.len(),
+            segments,
+            segment_starts,
+        }
+    }
+
+    fn trace_segment(
+        contraption: &Contraption,
+        (mut index, mut dir): (usize, Dir),
+        segment_starts: &HashMap<(usize, Dir), usize>,
+    ) -> Segment {
+        let mut tiles = Vec::new();
+        let mut seen = HashSet::new();
+        loop {
+            tiles.push(index);
+            seen.insert((index, dir));
+            let outgoing = contraption.tiles[index]
+                .deflect(dir)
+                .into_iter()
+                .filter_map(|out| Some((contraption.step(index, out)?, out)))
+                .collect::<Vec<_>>();
+            match outgoing[..] {
+                [state] if !segment_starts.contains_key(&state) => {
+                    if seen.contains(&state) {
+                        // Going round in a loop
+                        return Segment {
+                            tiles,
+                            next: Vec::new(),
+                        };
                     }
+                    (index, dir) = state;
                 }
-
-                if !past_beams.insert((beam.index(self.width), beam.dir)) {
-                    break;
+                _ => {
+                    let next = outgoing.iter().map(|state| segment_starts[state]).collect();
+                    return Segment { tiles, next };
                 }
             }
         }
+    }
 
-        energized_tiles.iter().filter(|t| **t).count()
+    /// Which tiles are energized by a beam entering at `index` travelling in `dir`.
+    fn energized(&self, index: usize, dir: Dir) -> Vec<bool> {
+        let mut energized = vec![false; self.tile_count];
+        let mut visited = vec![false; self.segments.len()];
+        let mut stack = vec![self.segment_starts[&(index, dir)]];
+        while let Some(id) = stack.pop() {
+            if std::mem::replace(&mut visited[id], true) {
+                continue;
+            }
+            for &tile in &self.segments[id].tiles {
+                energized[tile] = true;
+            }
+            stack.extend(&self.segments[id].next);
+        }
+        energized
     }
 
-    fn max_energized_tiles(&self) -> usize {
-        let mut max_energized_tiles = 0;
+    fn energized_count(&self, index: usize, dir: Dir) -> usize {
+        self.energized(index, dir).iter().filter(|t| **t).count()
+    }
+}
 
-        // Top row
-        for col in 0..self.width {
-            let energized_tiles = self.energized_tiles(0, col, Dir::Down);
-            max_energized_tiles = max_energized_tiles.max(energized_tiles);
-        }
-        // Bottom row
-        for col in 0..self.width {
-            let energized_tiles = self.energized_tiles(self.height() - 1, col, Dir::Up);
-            max_energized_tiles = max_energized_tiles.max(energized_tiles);
-        }
-        // Left column
-        for row in 0..self.height() {
-            let energized_tiles = self.energized_tiles(row, 0, Dir::Right);
-            max_energized_tiles = max_energized_tiles.max(energized_tiles);
+/// How often each tile is energized, drawn with the busiest tiles as `9` and unlit tiles as `.`.
+#[allow(dead_code)]
+#[derive(Debug)]
+struct Heatmap {
+    counts: Vec<u32>,
+    width: usize,
+}
+
+impl fmt::Display for Heatmap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max = self.counts.iter().copied().max().unwrap_or(0);
+        for row in self.counts.chunks(self.width) {
+            for &count in row {
+                if count == 0 {
+                    write!(f, ".")?;
+                } else if max == 1 {
+                    write!(f, "9")?;
+                } else {
+                    write!(f, "{}", 1 + (count - 1) * 8 / (max - 1))?;
+                }
+            }
+            writeln!(f)?;
         }
-        // Right column
-        for row in 0..self.height() {
-            let energized_tiles = self.energized_tiles(row, self.width - 1, Dir::Left);
-            max_energized_tiles = max_energized_tiles.max(energized_tiles);
+        Ok(())
+    }
+}
+
+impl Optic for Tile {
+    fn deflect(&self, dir: Dir) -> Vec<Dir> {
+        match (self, dir) {
+            (Tile::Empty, _) => vec![dir],
+            (Tile::ForwardMirror, Dir::Left) => vec![Dir::Down],
+            (Tile::ForwardMirror, Dir::Right) => vec![Dir::Up],
+            (Tile::ForwardMirror, Dir::Up) => vec![Dir::Right],
+            (Tile::ForwardMirror, Dir::Down) => vec![Dir::Left],
+            (Tile::BackwardMirror, Dir::Left) => vec![Dir::Up],
+            (Tile::BackwardMirror, Dir::Right) => vec![Dir::Down],
+            (Tile::BackwardMirror, Dir::Up) => vec![Dir::Left],
+            (Tile::BackwardMirror, Dir::Down) => vec![Dir::Right],
+            (Tile::VertSplitter, Dir::Left | Dir::Right) => vec![Dir::Up, Dir::Down],
+            (Tile::HorzSplitter, Dir::Up | Dir::Down) => vec![Dir::Left, Dir::Right],
+            (Tile::VertSplitter | Tile::HorzSplitter, _) => vec![dir],
         }
+    }
 
-        max_energized_tiles
+    fn symbol(&self) -> char {
+        match self {
+            Tile::Empty => '.',
+            Tile::ForwardMirror => '/',
+            Tile::BackwardMirror => '\\',
+            Tile::VertSplitter => '|',
+            Tile::HorzSplitter => '-',
+        }
     }
 }
 
@@ -234,3 +393,67 @@ fn test_max_energized_tiles() {
     let contraption = Contraption::parse(input);
     assert_eq!(contraption.max_energized_tiles(), 51);
 }
+
+/// Soaks up any beam that reaches it.
+#[allow(dead_code)]
+#[derive(Debug)]
+struct Absorber;
+
+impl Optic for Absorber {
+    fn deflect(&self, _dir: Dir) -> Vec<Dir> {
+        Vec::new()
+    }
+
+    fn symbol(&self) -> char {
+        'O'
+    }
+}
+
+#[test]
+fn test_custom_optic() {
+    let input = ".|...\\....\n|.-.\\.....\n.....|-...\n........|.\n..........\n.........\\\n..../.\\\\..\n.-.-/..|..\n.|....-|.\\\n..//.|....\n";
+    let mut contraption = Contraption::parse(input);
+    // Block the first splitter, so the beam goes no further than it
+    contraption.place(0, 1, Box::new(Absorber));
+    assert_eq!(contraption.energized_tiles(0, 0, Dir::Right), 2);
+    assert!(contraption.render_with(|_, _| None).starts_with(".O...\\"));
+}
+
+#[test]
+fn test_heatmap() {
+    let input = ".|...\\....\n|.-.\\.....\n.....|-...\n........|.\n..........\n.........\\\n..../.\\\\..\n.-.-/..|..\n.|....-|.\\\n..//.|....\n";
+    let contraption = Contraption::parse(input);
+    let heatmap = contraption.heatmap();
+    assert_eq!(heatmap.counts.len(), 100);
+    assert_eq!(
+        heatmap.counts.iter().map(|&c| c as usize).sum::<usize>(),
+        contraption
+            .edge_entries()
+            .iter()
+            .map(|beam| contraption.energized_tiles(beam.row, beam.col, beam.dir))
+            .sum::<usize>()
+    );
+    let rendered = heatmap.to_string();
+    assert_eq!(rendered.lines().count(), 10);
+    assert!(rendered.contains('9'));
+}
+
+#[test]
+fn test_render_energized() {
+    let input = ".|...\\....\n|.-.\\.....\n.....|-...\n........|.\n..........\n.........\\\n..../.\\\\..\n.-.-/..|..\n.|....-|.\\\n..//.|....\n";
+    let contraption = Contraption::parse(input);
+    let tracer = BeamTracer::new(
+        &contraption,
+        &[Beam {
+            row: 0,
+            col: 0,
+            dir: Dir::Right,
+        }],
+    );
+    let energized = tracer.energized(0, Dir::Right);
+    let rendered = contraption.render_with(|i, _| Some(if energized[i] { '#' } else { '.' }));
+    assert_eq!(
+        rendered,
+        "######....\n.#...#....\n.#...#####\n.#...##...\n.#...##...\n.#...##...\n.#..####..\n########..\n.#######..\n.#...#.#..\n"
+    );
+}
@@ -1,17 +1,17 @@
-use hashbrown::HashMap;
-use priority_queue::PriorityQueue;
-use std::{cmp::Reverse, fs};
+use std::fs;
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
     let map = Map::parse(&input);
-    map.minimal_heat_loss(1, 3).to_string()
+    map.minimal_heat_loss(&Crucible::new(1, 3), 0, map.far_corner())
+        .to_string()
 }
 
 pub fn part2() -> String {
     let input = get_input_file_contents();
     let map = Map::parse(&input);
-    map.minimal_heat_loss(4, 10).to_string()
+    map.minimal_heat_loss(&Crucible::new(4, 10), 0, map.far_corner())
+        .to_string()
 }
 
 fn get_input_file_contents() -> String {
@@ -32,17 +32,80 @@ enum Dir {
     Right,
 }
 
+/// How a crucible is allowed to move: it has to go at least `min_run` blocks in a straight
+/// line before turning or stopping, and at most `max_run`.
+#[derive(Debug, Clone, Copy)]
+struct Crucible {
+    min_run: u8,
+    max_run: u8,
+    /// Whether it can turn straight back the way it came.
+    allow_reverse: bool,
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 struct SearchNode {
     index: usize,
     dir: Dir,
+    /// How many blocks the crucible has moved in `dir` without turning, or zero before it
+    /// first moves.
     steps: u8,
 }
 
+/// The cheapest way through the map, as every block the crucible visits in order.
+#[derive(Debug)]
+struct Route {
+    heat_loss: u32,
+    positions: Vec<usize>,
+}
+
+impl Crucible {
+    /// A crucible that never turns back on itself.
+    fn new(min_run: u8, max_run: u8) -> Self {
+        Crucible {
+            min_run,
+            max_run,
+            allow_reverse: false,
+        }
+    }
+}
+
+impl Dir {
+    const ALL: [Dir; 4] = [Dir::Up, Dir::Down, Dir::Left, Dir::Right];
+
+    fn reverse(&self) -> Dir {
+        match self {
+            Dir::Up => Dir::Down,
+            Dir::Down => Dir::Up,
+            Dir::Left => Dir::Right,
+            Dir::Right => Dir::Left,
+        }
+    }
+
+    fn arrow(&self) -> char {
+        match self {
+            Dir::Up => '^',
+            Dir::Down => 'v',
+            Dir::Left => '<',
+            Dir::Right => '>',
+        }
+    }
+}
+
 impl SearchNode {
     fn new(index: usize, dir: Dir, steps: u8) -> Self {
         SearchNode { index, dir, steps }
     }
+
+    /// A dense index for this node, for crucibles that move at most `max_run` in a line.
+    fn id(&self, max_run: u8) -> usize {
+        let runs = max_run as usize + 1;
+        (self.index * 4 + self.dir as usize) * runs + self.steps as usize
+    }
+
+    fn from_id(id: usize, max_run: u8) -> Self {
+        let runs = max_run as usize + 1;
+        SearchNode::new(id / runs / 4, Dir::ALL[id / runs % 4], (id % runs) as u8)
+    }
 }
 
 impl Map {
@@ -65,115 +128,135 @@ impl Map {
         self.blocks.len() / self.width
     }
 
-    fn minimal_heat_loss(&self, min_steps: u8, max_steps: u8) -> u32 {
-        let mut open_set = PriorityQueue::new();
-        open_set.push(SearchNode::new(0, Dir::Right, 0), Reverse(0));
-
-        let mut heat_loss: HashMap<SearchNode, u32> = HashMap::new();
-        heat_loss.insert(SearchNode::new(0, Dir::Right, 0), 0);
+    /// The index of the bottom right block.
+    fn far_corner(&self) -> usize {
+        self.blocks.len() - 1
+    }
 
-        while !open_set.is_empty() {
-            let (current, _) = open_set.pop().unwrap();
-            let current_row = current.index / self.width;
-            let current_col = current.index % self.width;
+    fn minimal_heat_loss(&self, crucible: &Crucible, start: usize, end: usize) -> u32 {
+        self.best_route(crucible, start, end)
+            .expect("no path found")
+            .heat_loss
+    }
 
-            if current_col == self.width - 1 && current_row == self.height() - 1 {
-                // Reached goal
-                return *heat_loss.get(&current).unwrap();
-            }
+    /// The block one step from `index` in `dir`, if that's still on the map.
+    fn step(&self, index: usize, dir: Dir) -> Option<usize> {
+        let (row, col) = (index / self.width, index % self.width);
+        match dir {
+            Dir::Up => (row > 0).then(|| index - self.width),
+            Dir::Down => (row + 1 < self.height()).then(|| index + self.width),
+            Dir::Left => (col > 0).then(|| index - 1),
+            Dir::Right => (col + 1 < self.width).then(|| index + 1),
+        }
+    }
 
-            // Go through neighbors
-            // Up
-            let up = if current_row > 0
-                && current.dir != Dir::Down
-                && !(current.dir == Dir::Up && current.steps == max_steps)
-                && !(current.dir != Dir::Up && current.steps < min_steps)
-            {
-                Some(SearchNode::new(
-                    (current_row - 1) * self.width + current_col,
-                    Dir::Up,
-                    if current.dir == Dir::Up {
-                        current.steps + 1
-                    } else {
-                        1
-                    },
-                ))
-            } else {
-                None
-            };
-            // Down
-            let down = if current_row < self.height() - 1
-                && current.dir != Dir::Up
-                && !(current.dir == Dir::Down && current.steps == max_steps)
-                && (!(current.dir != Dir::Down && current.steps < min_steps) || current.index == 0)
-                && !(min_steps > 1
-                    && current_row == self.height() - 2
-                    && current_col == self.width - 1
-                    && (current.dir != Dir::Down || current.steps < min_steps - 1))
-            {
-                Some(SearchNode::new(
-                    (current_row + 1) * self.width + current_col,
-                    Dir::Down,
-                    if current.dir == Dir::Down {
-                        current.steps + 1
-                    } else {
-                        1
-                    },
-                ))
-            } else {
-                None
-            };
-            // Left
-            let left = if current_col > 0
-                && current.dir != Dir::Right
-                && !(current.dir == Dir::Left && current.steps == max_steps)
-                && !(current.dir != Dir::Left && current.steps < min_steps)
-            {
-                Some(SearchNode::new(
-                    current_row * self.width + current_col - 1,
-                    Dir::Left,
-                    if current.dir == Dir::Left {
-                        current.steps + 1
-                    } else {
-                        1
-                    },
-                ))
+    /// The nodes a crucible can move to from `node` in one step.
+    fn moves<'a>(
+        &'a self,
+        crucible: &'a Crucible,
+        node: SearchNode,
+    ) -> impl Iterator<Item = SearchNode> + 'a {
+        Dir::ALL.into_iter().filter_map(move |dir| {
+            let allowed = if node.steps == 0 {
+                true
+            } else if dir == node.dir {
+                node.steps < crucible.max_run
+            } else if dir == node.dir.reverse() {
+                crucible.allow_reverse && node.steps >= crucible.min_run
             } else {
-                None
+                node.steps >= crucible.min_run
             };
-            // Right
-            let right = if current_col < self.width - 1
-                && current.dir != Dir::Left
-                && !(current.dir == Dir::Right && current.steps == max_steps)
-                && !(current.dir != Dir::Right && current.steps < min_steps)
-                && !(min_steps > 1
-                    && current_row == self.height() - 1
-                    && current_col == self.width - 2
-                    && (current.dir != Dir::Right || current.steps < min_steps - 1))
-            {
-                Some(SearchNode::new(
-                    current_row * self.width + current_col + 1,
-                    Dir::Right,
-                    if current.dir == Dir::Right && current.index != 0 {
-                        current.steps + 1
-                    } else {
-                        1
-                    },
-                ))
+            if !allowed {
+                return None;
+            }
+            let steps = if dir == node.dir && node.steps > 0 {
+                node.steps + 1
             } else {
-                None
+                1
             };
-            for neighbor in [up, down, left, right].into_iter().flatten() {
-                let tentative_heat_loss =
-                    heat_loss.get(&current).unwrap() + self.blocks[neighbor.index] as u32;
-                if tentative_heat_loss < *heat_loss.get(&neighbor).unwrap_or(&u32::MAX) {
-                    heat_loss.insert(neighbor, tentative_heat_loss);
-                    open_set.push(neighbor, Reverse(tentative_heat_loss));
+            Some(SearchNode::new(self.step(node.index, dir)?, dir, steps))
+        })
+    }
+
+    /// The route from `start` to `end` losing the least heat, found with Dijkstra's algorithm
+    /// over a bucket queue, since each step costs between one and nine.
+    fn best_route(&self, crucible: &Crucible, start: usize, end: usize) -> Option<Route> {
+        let max_run = crucible.max_run;
+        let node_count = self.blocks.len() * 4 * (max_run as usize + 1);
+        let mut heat_loss = vec![u32::MAX; node_count];
+        let mut came_from = vec![usize::MAX; node_count];
+
+        let start_node = SearchNode::new(start, Dir::Right, 0);
+        heat_loss[start_node.id(max_run)] = 0;
+        let mut buckets: Vec<Vec<usize>> = vec![vec![start_node.id(max_run)]];
+        let mut cost = 0;
+        while cost < buckets.len() {
+            while let Some(id) = buckets[cost].pop() {
+                if heat_loss[id] as usize != cost {
+                    // Already reached more cheaply
+                    continue;
+                }
+                let current = SearchNode::from_id(id, max_run);
+                let stopped = current.steps == 0 || current.steps >= crucible.min_run;
+                if current.index == end && stopped {
+                    return Some(self.route(cost as u32, id, &came_from, max_run));
+                }
+
+                for neighbor in self.moves(crucible, current) {
+                    let neighbor_id = neighbor.id(max_run);
+                    let tentative = cost + self.blocks[neighbor.index] as usize;
+                    if tentative < heat_loss[neighbor_id] as usize {
+                        heat_loss[neighbor_id] = tentative as u32;
+                        came_from[neighbor_id] = id;
+                        if buckets.len() <= tentative {
+                            buckets.resize_with(tentative + 1, Vec::new);
+                        }
+                        buckets[tentative].push(neighbor_id);
+                    }
                 }
             }
+            cost += 1;
         }
+        None
+    }
 
-        panic!("no path found")
+    fn route(&self, heat_loss: u32, end_id: usize, came_from: &[usize], max_run: u8) -> Route {
+        let mut positions = Vec::new();
+        let mut id = end_id;
+        loop {
+            positions.push(SearchNode::from_id(id, max_run).index);
+            if came_from[id] == usize::MAX {
+                break;
+            }
+            id = came_from[id];
+        }
+        positions.reverse();
+        Route {
+            heat_loss,
+            positions,
+        }
+    }
+
+    /// The map with the route drawn over it, as arrows showing which way the crucible left
+    /// each block.
+    #[allow(dead_code)]
+    fn render_route(&self, route: &Route) -> String {
+        let mut chars = self
+            .blocks
+            .iter()
+            .map(|b| char::from(b'0' + b))
+            .collect::<Vec<char>>();
+        for pair in route.positions.windows(2) {
+            let dir = Dir::ALL
+                .into_iter()
+                .find(|&dir| self.step(pair[0], dir) == Some(pair[1]))
+                .unwrap();
+            chars[pair[0]] = dir.arrow();
+        }
+        chars
+            .chunks(self.width)
+            .map(|row| row.iter().collect::<String>() + "\n")
+            .collect()
     }
 }
 
@@ -188,7 +271,10 @@ fn test_parse() {
 fn test_minimal_heat_loss() {
     let input = "2413432311323\n3215453535623\n3255245654254\n3446585845452\n4546657867536\n1438598798454\n4457876987766\n3637877979653\n4654967986887\n4564679986453\n1224686865563\n2546548887735\n4322674655533\n";
     let map = Map::parse(input);
-    assert_eq!(map.minimal_heat_loss(1, 3), 102);
+    assert_eq!(
+        map.minimal_heat_loss(&Crucible::new(1, 3), 0, map.far_corner()),
+        102
+    );
 }
 
 #[test]
@@ -196,25 +282,87 @@ fn test_minimal_heat_loss_ultra() {
     {
         let input = "2413432311323\n3215453535623\n3255245654254\n3446585845452\n4546657867536\n1438598798454\n4457876987766\n3637877979653\n4654967986887\n4564679986453\n1224686865563\n2546548887735\n4322674655533\n";
         let map = Map::parse(input);
-        assert_eq!(map.minimal_heat_loss(4, 10), 94);
-    }
-
-    {
-        let input = "111111111111\n999999999991\n999999999991\n999999999991\n999999999991\n";
-        let map = Map::parse(input);
-        assert_eq!(map.minimal_heat_loss(4, 10), 71);
+        assert_eq!(
+            map.minimal_heat_loss(&Crucible::new(4, 10), 0, map.far_corner()),
+            94
+        );
     }
 
     {
         let input = "111111111111\n999999999991\n999999999991\n999999999991\n999999999991\n";
         let map = Map::parse(input);
-        assert_eq!(map.minimal_heat_loss(4, 10), 71);
+        assert_eq!(
+            map.minimal_heat_loss(&Crucible::new(4, 10), 0, map.far_corner()),
+            71
+        );
     }
 
     {
         let input =
             "19999\n19999\n19999\n19999\n19999\n19999\n19999\n19999\n19999\n19999\n19999\n11111\n";
         let map = Map::parse(input);
-        assert_eq!(map.minimal_heat_loss(4, 10), 71);
+        assert_eq!(
+            map.minimal_heat_loss(&Crucible::new(4, 10), 0, map.far_corner()),
+            71
+        );
     }
 }
+
+#[test]
+fn test_best_route() {
+    let input = "2413432311323\n3215453535623\n3255245654254\n3446585845452\n4546657867536\n1438598798454\n4457876987766\n3637877979653\n4654967986887\n4564679986453\n1224686865563\n2546548887735\n4322674655533\n";
+    let map = Map::parse(input);
+    let crucible = Crucible {
+        min_run: 1,
+        max_run: 3,
+        allow_reverse: false,
+    };
+    let route = map.best_route(&crucible, 0, map.blocks.len() - 1).unwrap();
+    assert_eq!(route.heat_loss, 102);
+    assert_eq!(route.positions.first(), Some(&0));
+    assert_eq!(route.positions.last(), Some(&(map.blocks.len() - 1)));
+    assert_eq!(
+        route.positions[1..]
+            .iter()
+            .map(|&i| map.blocks[i] as u32)
+            .sum::<u32>(),
+        102
+    );
+
+    let rendered = map.render_route(&route);
+    assert_eq!(rendered.lines().count(), 13);
+    assert!(rendered.lines().all(|line| line.len() == 13));
+    assert_eq!(
+        rendered.chars().filter(|ch| "^v<>".contains(*ch)).count(),
+        route.positions.len() - 1
+    );
+
+    // Going back the other way costs the same as long as the ends are swapped
+    let back = map.best_route(&crucible, map.blocks.len() - 1, 0).unwrap();
+    assert_eq!(
+        back.heat_loss + map.blocks[map.blocks.len() - 1] as u32,
+        route.heat_loss + map.blocks[0] as u32
+    );
+}
+
+#[test]
+fn test_reversing_crucible() {
+    // Stepping straight to the end is too short a run, so the crucible has to head the other
+    // way first and come back
+    let map = Map::parse("1111\n");
+    let crucible = Crucible {
+        min_run: 2,
+        max_run: 3,
+        allow_reverse: false,
+    };
+    assert!(map.best_route(&crucible, 1, 0).is_none());
+
+    let reversing = Crucible {
+        allow_reverse: true,
+        ..crucible
+    };
+    let route = map.best_route(&reversing, 1, 0).unwrap();
+    assert_eq!(route.heat_loss, 5);
+    assert_eq!(route.positions, vec![1, 2, 3, 2, 1, 0]);
+    assert_eq!(map.minimal_heat_loss(&reversing, 1, 0), 5);
+}
@@ -1,16 +1,16 @@
-use hashbrown::HashSet;
+use crate::polygon::{Point, Polygon};
 use std::fs;
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
     let dig = DigPlan::parse(&input);
-    dig.size_flood_fill().to_string()
+    dig.lagoon_size().to_string()
 }
 
 pub fn part2() -> String {
     let input = get_input_file_contents();
     let dig = DigPlan::parse_hex(&input);
-    dig.size_trapezoid_area().to_string()
+    dig.lagoon_size().to_string()
 }
 
 fn get_input_file_contents() -> String {
@@ -28,12 +28,6 @@ struct DigStep {
     steps: u64,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-struct Position {
-    row: isize,
-    col: isize,
-}
-
 #[derive(Debug, PartialEq, Clone, Copy)]
 enum Dir {
     Up,
@@ -42,12 +36,6 @@ enum Dir {
     Right,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum Turn {
-    Left,
-    Right,
-}
-
 impl DigPlan {
     fn parse(input: &str) -> Self {
         DigPlan {
@@ -61,143 +49,26 @@ impl DigPlan {
         }
     }
 
-    fn size_trapezoid_area(&self) -> u64 {
-        let mut points = Vec::new();
-        let mut current_point = (0.0, 0.0);
-        points.push(current_point);
-
-        let mut last_turn = Turn::Right;
-
-        for (step, next_step) in self.steps.iter().zip(
-            self.steps
-                .iter()
-                .skip(1)
-                .chain(std::iter::once(self.steps.first().unwrap())),
-        ) {
-            let next_turn = next_step.dir.turn(step.dir);
-            let steps_mod = match (last_turn, next_turn) {
-                (Turn::Left, Turn::Left) => -1.0,
-                (Turn::Left, Turn::Right) => 0.0,
-                (Turn::Right, Turn::Left) => 0.0,
-                (Turn::Right, Turn::Right) => 1.0,
-            };
-            last_turn = next_turn;
-
+    /// The outline of the trench, following the centre of each dug cube.
+    fn lagoon_polygon(&self) -> Polygon {
+        let mut pos = Point::new(0, 0);
+        let mut vertices = vec![pos];
+        for step in &self.steps {
+            let steps = step.steps as i64;
             match step.dir {
-                Dir::Up => {
-                    current_point.1 -= step.steps as f64 + steps_mod;
-                    points.push(current_point);
-                }
-                Dir::Down => {
-                    current_point.1 += step.steps as f64 + steps_mod;
-                    points.push(current_point);
-                }
-                Dir::Left => {
-                    current_point.0 -= step.steps as f64 + steps_mod;
-                    points.push(current_point);
-                }
-                Dir::Right => {
-                    current_point.0 += step.steps as f64 + steps_mod;
-                    points.push(current_point);
-                }
+                Dir::Up => pos.y -= steps,
+                Dir::Down => pos.y += steps,
+                Dir::Left => pos.x -= steps,
+                Dir::Right => pos.x += steps,
             }
+            vertices.push(pos);
         }
-
-        let mut area = 0.0;
-        for (p0, p1) in points.iter().zip(points.iter().skip(1)) {
-            let trapezoid_area = (p0.1 + p1.1) * (p0.0 - p1.0);
-            area += trapezoid_area;
-        }
-        area /= 2.0;
-        area as u64
+        Polygon::new(vertices)
     }
 
-    fn size_flood_fill(&self) -> usize {
-        let mut filled = HashSet::new();
-        let mut pos = Position { row: 0, col: 0 };
-        filled.insert(pos);
-        for step in self.steps.iter() {
-            match step.dir {
-                Dir::Up => {
-                    for _ in 0..step.steps {
-                        pos.row -= 1;
-                        filled.insert(pos);
-                    }
-                }
-                Dir::Down => {
-                    for _ in 0..step.steps {
-                        pos.row += 1;
-                        filled.insert(pos);
-                    }
-                }
-                Dir::Left => {
-                    for _ in 0..step.steps {
-                        pos.col -= 1;
-                        filled.insert(pos);
-                    }
-                }
-                Dir::Right => {
-                    for _ in 0..step.steps {
-                        pos.col += 1;
-                        filled.insert(pos);
-                    }
-                }
-            }
-        }
-
-        let min_row = filled.iter().map(|p| p.row).min().unwrap();
-        let max_row = filled.iter().map(|p| p.row).max().unwrap();
-        let min_col = filled.iter().map(|p| p.col).min().unwrap();
-        let max_col = filled.iter().map(|p| p.col).max().unwrap();
-        let grid_height = (max_row - min_row + 1) as usize;
-        let grid_width = (max_col - min_col + 1) as usize;
-
-        let mut grid: Vec<bool> = vec![false; grid_width * grid_height];
-        for row in 0..grid_height {
-            for col in 0..grid_width {
-                if filled.contains(&Position {
-                    row: row as isize + min_row,
-                    col: col as isize + min_col,
-                }) {
-                    grid[row * grid_width + col] = true;
-                }
-            }
-        }
-
-        // Flood fill the interior of the loop
-        let start_row = (-min_row) as usize;
-        let start_col = (-min_col) as usize;
-        let mut filled_grid = grid.clone();
-        let mut queue = Vec::new();
-        queue.push((start_row + 1) * grid_width + start_col + 1);
-        while let Some(index) = queue.pop() {
-            filled_grid[index] = true;
-
-            let row = index / grid_width;
-            let col = index % grid_width;
-
-            // Up
-            if row > 0 && !grid[index - grid_width] && !filled_grid[index - grid_width] {
-                queue.push(index - grid_width);
-            }
-            // Down
-            if row < grid_height - 1
-                && !grid[index + grid_width]
-                && !filled_grid[index + grid_width]
-            {
-                queue.push(index + grid_width);
-            }
-            // Left
-            if col > 0 && !grid[index - 1] && !filled_grid[index - 1] {
-                queue.push(index - 1);
-            }
-            // Right
-            if col < grid_width - 1 && !grid[index + 1] && !filled_grid[index + 1] {
-                queue.push(index + 1);
-            }
-        }
-
-        filled_grid.iter().filter(|t| **t).count()
+    /// How many cubes the lagoon holds: the trench itself plus everything it encloses.
+    fn lagoon_size(&self) -> u64 {
+        self.lagoon_polygon().enclosed_points() as u64
     }
 }
 
@@ -237,35 +108,6 @@ impl Dir {
             _ => panic!("invalid direction {ch}"),
         }
     }
-
-    fn turn(&self, last_dir: Dir) -> Turn {
-        match last_dir {
-            Dir::Up => match self {
-                Dir::Up => unreachable!(),
-                Dir::Down => unreachable!(),
-                Dir::Left => Turn::Left,
-                Dir::Right => Turn::Right,
-            },
-            Dir::Down => match self {
-                Dir::Up => unreachable!(),
-                Dir::Down => unreachable!(),
-                Dir::Left => Turn::Right,
-                Dir::Right => Turn::Left,
-            },
-            Dir::Left => match self {
-                Dir::Up => Turn::Right,
-                Dir::Down => Turn::Left,
-                Dir::Left => unreachable!(),
-                Dir::Right => unreachable!(),
-            },
-            Dir::Right => match self {
-                Dir::Up => Turn::Left,
-                Dir::Down => Turn::Right,
-                Dir::Left => unreachable!(),
-                Dir::Right => unreachable!(),
-            },
-        }
-    }
 }
 
 #[test]
@@ -299,23 +141,16 @@ fn test_parse_hex() {
 }
 
 #[test]
-fn test_size_flood_fill() {
-    let input = "R 6 (#70c710)\nD 5 (#0dc571)\nL 2 (#5713f0)\nD 2 (#d2c081)\nR 2 (#59c680)\nD 2 (#411b91)\nL 5 (#8ceee2)\nU 2 (#caa173)\nL 1 (#1b58a2)\nU 2 (#caa171)\nR 2 (#7807d2)\nU 3 (#a77fa3)\nL 2 (#015232)\nU 2 (#7a21e3)\n";
-    let dig = DigPlan::parse(input);
-    assert_eq!(dig.size_flood_fill(), 62);
-}
-
-#[test]
-fn test_size_trapezoid_area() {
+fn test_lagoon_size() {
     let input = "R 6 (#70c710)\nD 5 (#0dc571)\nL 2 (#5713f0)\nD 2 (#d2c081)\nR 2 (#59c680)\nD 2 (#411b91)\nL 5 (#8ceee2)\nU 2 (#caa173)\nL 1 (#1b58a2)\nU 2 (#caa171)\nR 2 (#7807d2)\nU 3 (#a77fa3)\nL 2 (#015232)\nU 2 (#7a21e3)\n";
 
     {
         let dig = DigPlan::parse(input);
-        assert_eq!(dig.size_trapezoid_area(), 62);
+        assert_eq!(dig.lagoon_size(), 62);
     }
 
     {
         let dig = DigPlan::parse_hex(input);
-        assert_eq!(dig.size_trapezoid_area(), 952408144115);
+        assert_eq!(dig.lagoon_size(), 952408144115);
     }
 }
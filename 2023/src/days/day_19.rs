@@ -1,4 +1,5 @@
 use hashbrown::HashMap;
+use intervals::{Interval, IntervalBox};
 use std::fs;
 
 pub fn part1() -> String {
@@ -44,71 +45,34 @@ fn parse(input: &str) -> (HashMap<&str, Workflow>, Vec<Part>) {
 }
 
 fn acceptance_combinations(workflows: &HashMap<&str, Workflow>) -> u64 {
-    let mut total_combinations = 0;
-    for ranges in acceptance_ranges(workflows).iter() {
-        let mut combinations = 1;
-        for (comp_min, comp_max) in ranges.iter() {
-            combinations *= (comp_max - comp_min + 1) as u64;
-        }
-        total_combinations += combinations;
-    }
-    total_combinations
+    acceptance_ranges(workflows)
+        .iter()
+        .map(IntervalBox::volume)
+        .sum()
 }
 
-fn acceptance_ranges(workflows: &HashMap<&str, Workflow>) -> Vec<[(u32, u32); 4]> {
-    let mut all_ranges = Vec::new();
-    for workflow in workflows.values() {
-        for (i, rule) in workflow.rules.iter().enumerate() {
-            if rule.workflow() == "A" {
-                let mut ranges = [(1, 4000), (1, 4000), (1, 4000), (1, 4000)];
-
-                // The current rule must be true
-                rule.modify_ranges(&mut ranges, true);
-                // Each previous rule in this workflow must be false
-                for prev_rule in workflow.rules.iter().take(i) {
-                    prev_rule.modify_ranges(&mut ranges, false);
-                }
-
-                // Now follow the previous workflows back to "in"
-                let mut cur_workflow_name = workflow.name;
-                loop {
-                    let mut prev_workflow = None;
-                    let mut prev_workflow_rule_idx = None;
-                    for maybe_prev_workflow in workflows.values() {
-                        for (j, prev_workflow_rule) in maybe_prev_workflow.rules.iter().enumerate()
-                        {
-                            if prev_workflow_rule.workflow() == cur_workflow_name {
-                                prev_workflow = Some(maybe_prev_workflow);
-                                prev_workflow_rule_idx = Some(j);
-                                break;
-                            }
-                        }
-                        if prev_workflow.is_some() {
-                            break;
-                        }
-                    }
-                    let prev_workflow = prev_workflow.unwrap();
-                    let prev_workflow_rule_idx = prev_workflow_rule_idx.unwrap();
-
-                    // Modify ranges
-                    prev_workflow.rules[prev_workflow_rule_idx].modify_ranges(&mut ranges, true);
-                    for prev_workflow_rule in
-                        prev_workflow.rules.iter().take(prev_workflow_rule_idx)
-                    {
-                        prev_workflow_rule.modify_ranges(&mut ranges, false);
-                    }
-
-                    if prev_workflow.name == "in" {
-                        break;
-                    }
-                    cur_workflow_name = prev_workflow.name;
+/// Disjoint boxes of x, m, a and s ratings that end up accepted.
+fn acceptance_ranges(workflows: &HashMap<&str, Workflow>) -> Vec<IntervalBox<4>> {
+    let mut accepted = Vec::new();
+    let mut pending = vec![("in", IntervalBox::new([Interval::new(1, 4000); 4]))];
+    while let Some((workflow_name, mut ranges)) = pending.pop() {
+        let workflow = workflows.get(workflow_name).expect("workflow not found");
+        for rule in workflow.rules.iter() {
+            let (matching, rest) = rule.split_ranges(&ranges);
+            if let Some(matching) = matching {
+                match rule.workflow() {
+                    "A" => accepted.push(matching),
+                    "R" => {}
+                    next => pending.push((next, matching)),
                 }
-
-                all_ranges.push(ranges);
+            }
+            match rest {
+                Some(rest) => ranges = rest,
+                None => break,
             }
         }
     }
-    all_ranges
+    accepted
 }
 
 #[derive(Debug, PartialEq)]
@@ -218,30 +182,29 @@ impl<'input> Rule<'input> {
         }
     }
 
-    fn modify_ranges(&self, ranges: &mut [(u32, u32); 4], result: bool) {
+    /// Splits the ranges into the parts that do and don't match this rule.
+    fn split_ranges(
+        &self,
+        ranges: &IntervalBox<4>,
+    ) -> (Option<IntervalBox<4>>, Option<IntervalBox<4>>) {
         match self {
             Rule::Comparison {
                 component,
                 cmp_op,
                 value,
                 ..
-            } => match cmp_op {
-                CmpOp::GreaterThan => {
-                    if result {
-                        ranges[*component as usize].0 = ranges[*component as usize].0.max(value + 1)
-                    } else {
-                        ranges[*component as usize].1 = ranges[*component as usize].1.min(*value)
+            } => {
+                let axis = *component as usize;
+                let value = i64::from(*value);
+                match cmp_op {
+                    CmpOp::GreaterThan => {
+                        let (below, above) = ranges.split_at(axis, value + 1);
+                        (above, below)
                     }
+                    CmpOp::LessThan => ranges.split_at(axis, value),
                 }
-                CmpOp::LessThan => {
-                    if result {
-                        ranges[*component as usize].1 = ranges[*component as usize].1.min(value - 1)
-                    } else {
-                        ranges[*component as usize].0 = ranges[*component as usize].0.max(*value)
-                    }
-                }
-            },
-            Rule::Direct(_) => {}
+            }
+            Rule::Direct(_) => (Some(*ranges), None),
         }
     }
 }
@@ -1,5 +1,6 @@
-use hashbrown::HashMap;
-use std::{collections::VecDeque, fs};
+use hashbrown::{HashMap, HashSet};
+use num::Integer;
+use std::{collections::VecDeque, fmt::Write, fs};
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
@@ -12,31 +13,33 @@ pub fn part1() -> String {
 
 pub fn part2() -> String {
     let input = get_input_file_contents();
-    buttons_presses_until_low_pulse_to_module(&input, "vr").to_string()
+    button_presses_until_low_pulse_to_output(&input)
+        .expect("Network can't be split into periodic sub-counters")
+        .to_string()
 }
 
 fn get_input_file_contents() -> String {
     fs::read_to_string("inputs/input20").expect("Failed to open input file")
 }
 
-fn buttons_presses_until_low_pulse_to_module(input: &str, module: &str) -> u64 {
-    const SECOND_LAST_MODULES: [&str; 4] = ["pq", "fg", "dk", "fm"];
-    let mut button_presses = Vec::new();
-    for second_last_module in SECOND_LAST_MODULES {
-        let mut modules = Modules::parse(input);
-        modules.push_button_until_module_has_high_input(module, second_last_module);
-        button_presses.push(modules.button_presses);
-    }
-    button_presses.iter().product::<u64>()
+fn button_presses_until_low_pulse_to_output(input: &str) -> Option<u64> {
+    let mut modules = Modules::parse(input);
+    let periods = modules.sub_counter_periods(MAX_PERIOD_SEARCH_PRESSES)?;
+    periods
+        .into_iter()
+        .map(|(_, period)| period)
+        .reduce(|acc, period| acc.lcm(&period))
 }
 
+/// Give up looking for sub-counter periods after this many button presses.
+const MAX_PERIOD_SEARCH_PRESSES: u64 = 100_000;
+
 #[derive(Debug)]
 struct Modules<'input> {
     modules: HashMap<&'input str, Module<'input>>,
     output: Option<&'input str>,
     button_presses: u64,
     pulse_counts: HashMap<Pulse, u64>,
-    input_buffers: HashMap<&'input str, VecDeque<(&'input str, Pulse)>>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -63,6 +66,23 @@ enum Pulse {
     High,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct PulseEvent<'input> {
+    source: &'input str,
+    pulse: Pulse,
+    destination: &'input str,
+}
+
+/// An independent part of the network, fed by the broadcaster, which drives one input of
+/// the final conjunction.
+#[derive(Debug, PartialEq)]
+struct SubCounter<'input> {
+    /// The module which sends pulses to the final conjunction.
+    output: &'input str,
+    /// Every module (including `output`) upstream of `output`, excluding the broadcaster.
+    modules: Vec<&'input str>,
+}
+
 impl<'input> Modules<'input> {
     fn parse(input: &'input str) -> Self {
         let mut modules: HashMap<&str, Module> = input
@@ -93,11 +113,6 @@ impl<'input> Modules<'input> {
             }
         }
 
-        let mut input_buffers: HashMap<&str, VecDeque<(&str, Pulse)>> = HashMap::new();
-        for module_name in modules.keys() {
-            input_buffers.insert(module_name, VecDeque::new());
-        }
-
         Modules {
             modules,
             output,
@@ -105,7 +120,6 @@ impl<'input> Modules<'input> {
             pulse_counts: ([(Pulse::Low, 0), (Pulse::High, 0)])
                 .into_iter()
                 .collect::<HashMap<Pulse, u64>>(),
-            input_buffers,
         }
     }
 
@@ -113,122 +127,211 @@ impl<'input> Modules<'input> {
         self.pulse_counts.get(&Pulse::Low).unwrap() * self.pulse_counts.get(&Pulse::High).unwrap()
     }
 
-    fn push_button_until_module_has_high_input(&mut self, conj_module: &str, input_module: &str) {
-        loop {
-            self.button_presses += 1;
-
-            self.input_buffers
-                .get_mut("broadcaster")
-                .unwrap()
-                .push_back(("button", Pulse::Low));
-            *self.pulse_counts.get_mut(&Pulse::Low).unwrap() += 1;
-            while !self.input_buffers.values().all(|pulses| pulses.is_empty()) {
-                for module in self.modules.values_mut() {
-                    if let Some((source, pulse)) =
-                        self.input_buffers.get_mut(module.name).unwrap().pop_front()
-                    {
-                        let pulse_to_send = match &mut module.ty {
-                            ModuleType::Broadcast => Some(pulse),
-                            ModuleType::FlipFlop { on } => {
-                                if pulse == Pulse::Low {
-                                    if *on {
-                                        *on = false;
-                                        Some(Pulse::Low)
-                                    } else {
-                                        *on = true;
-                                        Some(Pulse::High)
-                                    }
-                                } else {
-                                    None
-                                }
-                            }
-                            ModuleType::Conjunction { last_inputs } => {
-                                if module.name == conj_module
-                                    && *last_inputs.get(input_module).unwrap() == Pulse::High
-                                {
-                                    return;
-                                }
-
-                                last_inputs.insert(source, pulse);
-                                if last_inputs.values().all(|p| *p == Pulse::High) {
-                                    Some(Pulse::Low)
-                                } else {
-                                    Some(Pulse::High)
-                                }
-                            }
-                        };
-                        if let Some(pulse_to_send) = pulse_to_send {
-                            *self.pulse_counts.get_mut(&pulse_to_send).unwrap() +=
-                                module.outputs.len() as u64;
-                            for output in module.outputs.iter() {
-                                if self.output == Some(*output) {
-                                    continue;
-                                }
-                                self.input_buffers
-                                    .get_mut(*output)
-                                    .unwrap()
-                                    .push_back((module.name, pulse_to_send));
-                            }
-                        }
-                    }
+    /// Map from each module (including the output) to the modules which send pulses to it.
+    fn inputs(&self) -> HashMap<&'input str, Vec<&'input str>> {
+        let mut inputs: HashMap<&str, Vec<&str>> = HashMap::new();
+        for module in self.modules.values() {
+            for output in module.outputs.iter() {
+                inputs.entry(output).or_default().push(module.name);
+            }
+        }
+        for module_inputs in inputs.values_mut() {
+            module_inputs.sort_unstable();
+        }
+        inputs
+    }
+
+    /// The conjunction which is the sole input to the network's output module, if there is one.
+    fn final_conjunction(&self) -> Option<&'input str> {
+        let output = self.output?;
+        match self.inputs().get(output)?.as_slice() {
+            [module_name] => match self.modules.get(module_name)?.ty {
+                ModuleType::Conjunction { .. } => Some(*module_name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Split the network into the sub-counters feeding the final conjunction.
+    ///
+    /// Returns `None` if there is no final conjunction, or if the sub-counters share modules
+    /// (other than the broadcaster) and so aren't independent.
+    fn sub_counters(&self) -> Option<Vec<SubCounter<'input>>> {
+        let final_conjunction = self.final_conjunction()?;
+        let inputs = self.inputs();
+
+        let mut seen = HashSet::new();
+        let mut sub_counters = Vec::new();
+        for &output in inputs.get(final_conjunction)? {
+            let mut modules = Vec::new();
+            let mut to_visit = vec![output];
+            while let Some(module_name) = to_visit.pop() {
+                if module_name == "broadcaster" || modules.contains(&module_name) {
+                    continue;
+                }
+                if !seen.insert(module_name) {
+                    // Already part of another sub-counter
+                    return None;
+                }
+                modules.push(module_name);
+                if let Some(module_inputs) = inputs.get(module_name) {
+                    to_visit.extend(module_inputs.iter().copied());
                 }
             }
+            modules.sort_unstable();
+            sub_counters.push(SubCounter { output, modules });
         }
+        Some(sub_counters)
+    }
+
+    /// Push the button until every sub-counter has sent two high pulses to the final
+    /// conjunction, and return the number of presses between them for each sub-counter.
+    ///
+    /// Returns `None` if the network can't be split into sub-counters, if any sub-counter
+    /// doesn't repeat within `max_presses`, or if a sub-counter's first high pulse isn't at
+    /// the end of its first period (in which case the periods can't simply be combined).
+    fn sub_counter_periods(&mut self, max_presses: u64) -> Option<Vec<(&'input str, u64)>> {
+        let final_conjunction = self.final_conjunction()?;
+        let sub_counters = self.sub_counters()?;
+
+        let mut high_presses: HashMap<&str, Vec<u64>> = sub_counters
+            .iter()
+            .map(|sub_counter| (sub_counter.output, Vec::new()))
+            .collect();
+        let start_presses = self.button_presses;
+        while high_presses.values().any(|presses| presses.len() < 2) {
+            if self.button_presses - start_presses >= max_presses {
+                return None;
+            }
+            let press = self.button_presses + 1 - start_presses;
+            self.push_button_with(|event| {
+                if event.destination == final_conjunction && event.pulse == Pulse::High {
+                    let presses = high_presses.get_mut(event.source).unwrap();
+                    if presses.len() < 2 && presses.last() != Some(&press) {
+                        presses.push(press);
+                    }
+                }
+            });
+        }
+
+        sub_counters
+            .iter()
+            .map(|sub_counter| {
+                let presses = high_presses.get(sub_counter.output).unwrap();
+                let period = presses[1] - presses[0];
+                (presses[0] == period).then_some((sub_counter.output, period))
+            })
+            .collect()
     }
 
     fn push_button(&mut self) {
+        self.push_button_with(|_| {});
+    }
+
+    /// Push the button, and return every pulse sent in the order they were processed.
+    #[allow(dead_code)]
+    fn push_button_traced(&mut self) -> Vec<PulseEvent<'input>> {
+        let mut trace = Vec::new();
+        self.push_button_with(|event| trace.push(event));
+        trace
+    }
+
+    fn push_button_with<F>(&mut self, mut on_pulse: F)
+    where
+        F: FnMut(PulseEvent<'input>),
+    {
         self.button_presses += 1;
 
-        self.input_buffers
-            .get_mut("broadcaster")
-            .unwrap()
-            .push_back(("button", Pulse::Low));
-        *self.pulse_counts.get_mut(&Pulse::Low).unwrap() += 1;
-        while !self.input_buffers.values().all(|pulses| pulses.is_empty()) {
-            for module in self.modules.values_mut() {
-                if let Some((source, pulse)) =
-                    self.input_buffers.get_mut(module.name).unwrap().pop_front()
-                {
-                    let pulse_to_send = match &mut module.ty {
-                        ModuleType::Broadcast => Some(pulse),
-                        ModuleType::FlipFlop { on } => {
-                            if pulse == Pulse::Low {
-                                if *on {
-                                    *on = false;
-                                    Some(Pulse::Low)
-                                } else {
-                                    *on = true;
-                                    Some(Pulse::High)
-                                }
-                            } else {
-                                None
-                            }
-                        }
-                        ModuleType::Conjunction { last_inputs } => {
-                            last_inputs.insert(source, pulse);
-                            if last_inputs.values().all(|p| *p == Pulse::High) {
-                                Some(Pulse::Low)
-                            } else {
-                                Some(Pulse::High)
-                            }
-                        }
-                    };
-                    if let Some(pulse_to_send) = pulse_to_send {
-                        *self.pulse_counts.get_mut(&pulse_to_send).unwrap() +=
-                            module.outputs.len() as u64;
-                        for output in module.outputs.iter() {
-                            if self.output == Some(*output) {
-                                continue;
-                            }
-                            self.input_buffers
-                                .get_mut(*output)
-                                .unwrap()
-                                .push_back((module.name, pulse_to_send));
+        let mut pulses = VecDeque::new();
+        pulses.push_back(PulseEvent {
+            source: "button",
+            pulse: Pulse::Low,
+            destination: "broadcaster",
+        });
+        while let Some(event) = pulses.pop_front() {
+            on_pulse(event);
+            *self.pulse_counts.get_mut(&event.pulse).unwrap() += 1;
+
+            let Some(module) = self.modules.get_mut(event.destination) else {
+                continue;
+            };
+            let pulse_to_send = match &mut module.ty {
+                ModuleType::Broadcast => Some(event.pulse),
+                ModuleType::FlipFlop { on } => {
+                    if event.pulse == Pulse::Low {
+                        if *on {
+                            *on = false;
+                            Some(Pulse::Low)
+                        } else {
+                            *on = true;
+                            Some(Pulse::High)
                         }
+                    } else {
+                        None
                     }
                 }
+                ModuleType::Conjunction { last_inputs } => {
+                    last_inputs.insert(event.source, event.pulse);
+                    if last_inputs.values().all(|p| *p == Pulse::High) {
+                        Some(Pulse::Low)
+                    } else {
+                        Some(Pulse::High)
+                    }
+                }
+            };
+            if let Some(pulse_to_send) = pulse_to_send {
+                for output in module.outputs.iter() {
+                    pulses.push_back(PulseEvent {
+                        source: module.name,
+                        pulse: pulse_to_send,
+                        destination: output,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Render the module graph in Graphviz DOT format.
+    #[allow(dead_code)]
+    fn to_dot(&self) -> String {
+        let mut names = self.modules.keys().copied().collect::<Vec<&str>>();
+        names.sort_unstable();
+
+        let mut dot = String::from("digraph modules {\n");
+        for name in names.iter() {
+            let (label, shape) = match self.modules.get(name).unwrap().ty {
+                ModuleType::Broadcast => (name.to_string(), "box"),
+                ModuleType::FlipFlop { .. } => (format!("%{name}"), "ellipse"),
+                ModuleType::Conjunction { .. } => (format!("&{name}"), "invhouse"),
+            };
+            writeln!(dot, "    {name} [label=\"{label}\", shape={shape}];").unwrap();
+        }
+        if let Some(output) = self.output {
+            writeln!(dot, "    {output} [shape=doublecircle];").unwrap();
+        }
+        for name in names.iter() {
+            for output in self.modules.get(name).unwrap().outputs.iter() {
+                writeln!(dot, "    {name} -> {output};").unwrap();
             }
         }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl std::fmt::Display for Pulse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pulse::Low => write!(f, "low"),
+            Pulse::High => write!(f, "high"),
+        }
+    }
+}
+
+impl<'input> std::fmt::Display for PulseEvent<'input> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -{}-> {}", self.source, self.pulse, self.destination)
     }
 }
 
@@ -362,3 +465,105 @@ fn test_push_button() {
         assert_eq!(modules.pulse_counts_product(), 11687500);
     }
 }
+
+#[test]
+fn test_push_button_traced() {
+    let input = "broadcaster -> a, b, c\n%a -> b\n%b -> c\n%c -> inv\n&inv -> a\n";
+    let mut modules = Modules::parse(input);
+
+    let trace = modules
+        .push_button_traced()
+        .into_iter()
+        .map(|event| event.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(
+        trace,
+        vec![
+            "button -low-> broadcaster",
+            "broadcaster -low-> a",
+            "broadcaster -low-> b",
+            "broadcaster -low-> c",
+            "a -high-> b",
+            "b -high-> c",
+            "c -high-> inv",
+            "inv -low-> a",
+            "a -low-> b",
+            "b -low-> c",
+            "c -low-> inv",
+            "inv -high-> a",
+        ]
+    );
+}
+
+#[test]
+fn test_to_dot() {
+    let input = "broadcaster -> a\n%a -> inv, con\n&inv -> b\n%b -> con\n&con -> output\n";
+    let modules = Modules::parse(input);
+
+    assert_eq!(
+        modules.to_dot(),
+        "digraph modules {\n    \
+        a [label=\"%a\", shape=ellipse];\n    \
+        b [label=\"%b\", shape=ellipse];\n    \
+        broadcaster [label=\"broadcaster\", shape=box];\n    \
+        con [label=\"&con\", shape=invhouse];\n    \
+        inv [label=\"&inv\", shape=invhouse];\n    \
+        output [shape=doublecircle];\n    \
+        a -> inv;\n    \
+        a -> con;\n    \
+        b -> con;\n    \
+        broadcaster -> a;\n    \
+        con -> output;\n    \
+        inv -> b;\n\
+        }\n"
+    );
+}
+
+#[test]
+fn test_sub_counters() {
+    // A 1-bit counter (period 2) and a 2-bit counter which resets at 3 (period 3)
+    let input = "broadcaster -> a, x0\n%a -> ia\n&ia -> hub\n%x0 -> x1, cx\n%x1 -> cx\n\
+        &cx -> x0, ix\n&ix -> hub\n&hub -> rx\n";
+    let mut modules = Modules::parse(input);
+
+    assert_eq!(modules.final_conjunction(), Some("hub"));
+    assert_eq!(
+        modules.sub_counters(),
+        Some(vec![
+            SubCounter {
+                output: "ia",
+                modules: vec!["a", "ia"],
+            },
+            SubCounter {
+                output: "ix",
+                modules: vec!["cx", "ix", "x0", "x1"],
+            },
+        ])
+    );
+    assert_eq!(
+        modules.sub_counter_periods(100),
+        Some(vec![("ia", 2), ("ix", 3)])
+    );
+
+    // Check the combined period against brute force
+    assert_eq!(button_presses_until_low_pulse_to_output(input), Some(6));
+    let mut modules = Modules::parse(input);
+    let mut low_to_rx = false;
+    while !low_to_rx {
+        modules.push_button_with(|event| {
+            low_to_rx |= event.destination == "rx" && event.pulse == Pulse::Low;
+        });
+    }
+    assert_eq!(modules.button_presses, 6);
+}
+
+#[test]
+fn test_sub_counters_not_independent() {
+    // Both inputs to the final conjunction share the flip-flop `a`
+    let input = "broadcaster -> a\n%a -> ia, ib\n&ia -> hub\n&ib -> hub\n&hub -> rx\n";
+    let mut modules = Modules::parse(input);
+
+    assert_eq!(modules.final_conjunction(), Some("hub"));
+    assert_eq!(modules.sub_counters(), None);
+    assert_eq!(modules.sub_counter_periods(100), None);
+}
@@ -1,5 +1,5 @@
-use hashbrown::HashSet;
-use std::fs;
+use hashbrown::{HashMap, HashSet};
+use std::{collections::VecDeque, fs};
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
@@ -11,7 +11,8 @@ pub fn part2() -> String {
     let input = get_input_file_contents();
     let garden = Garden::parse(&input);
     garden
-        .garden_plots_reachable_after_n_steps_fast(26501365)
+        .garden_plots_reachable_infinite(26501365)
+        .expect("Garden can't be extrapolated and is too far to search")
         .to_string()
 }
 
@@ -63,8 +64,15 @@ impl Garden {
     }
 
     fn height(&self) -> usize {
-        assert_eq!(self.tiles.len() / self.width, self.width);
-        self.width
+        self.tiles.len() / self.width
+    }
+
+    /// Whether the garden is square with the start in the very middle, which is what makes the
+    /// reachable count across the infinite garden settle into a quadratic.
+    fn is_square_and_centred(&self) -> bool {
+        self.height() == self.width
+            && self.width % 2 == 1
+            && self.start == (self.width / 2) * self.width + self.width / 2
     }
 
     fn garden_plots_reachable_after_n_steps(&self, n: usize) -> usize {
@@ -99,120 +107,154 @@ impl Garden {
         plots.len()
     }
 
-    fn garden_plots_reachable_after_n_steps_fast(&self, steps: usize) -> u64 {
-        assert!(steps % 2 == 1);
-        let n = steps / self.width;
-        let remainder = steps % self.width;
-
-        let garden_counts = self.expand_and_move_n_steps(2, 2 * self.width + remainder);
-        let even_full = garden_counts[7];
-        let odd_full = garden_counts[12];
-        let top = garden_counts[2];
-        let left = garden_counts[10];
-        let right = garden_counts[14];
-        let bottom = garden_counts[22];
-        let top_left = garden_counts[1];
-        let top_right = garden_counts[3];
-        let bottom_left = garden_counts[15];
-        let bottom_right = garden_counts[19];
-        let partial_top_left = garden_counts[6];
-        let partial_top_right = garden_counts[8];
-        let partial_bottom_left = garden_counts[16];
-        let partial_bottom_right = garden_counts[18];
-
-        let full_gardens_count = 2 * (n - 1) * n + 1;
-        let odd_full_garden_count = ((n - 1) * n) / 2 + ((n - 2) * (n - 1)) / 2;
-        let even_full_garden_count = full_gardens_count - odd_full_garden_count;
-
-        let full = even_full_garden_count * even_full + odd_full_garden_count * odd_full;
-        let partials = top
-            + left
-            + right
-            + bottom
-            + n * (top_left + top_right + bottom_left + bottom_right)
-            + (n - 1)
-                * (partial_top_left
-                    + partial_top_right
-                    + partial_bottom_left
-                    + partial_bottom_right);
-        (full + partials) as u64
+    /// The number of plots reachable in exactly `steps` steps in the infinitely tiled garden,
+    /// or `None` if the count can't be extrapolated and searching that far is too big.
+    fn garden_plots_reachable_infinite(&self, steps: usize) -> Option<u64> {
+        if self.is_square_and_centred() {
+            if let Some(count) = self.extrapolated_reachable(steps, EXTRAPOLATION_SAMPLES) {
+                return Some(count);
+            }
+        }
+        TiledReach::checked_new(self, steps).map(|reach| reach.reachable(steps))
     }
 
-    /// Return the number of reachable plots in each duplicate of the original garden.
-    fn expand_and_move_n_steps(&self, expansion: usize, steps: usize) -> Vec<usize> {
-        // Expand `expansion` times in each direction
-        let expansion_size = 2 * expansion + 1;
-        let mut expanded_tiles =
-            vec![Tile::GardenPlot; expansion_size * expansion_size * self.tiles.len()];
-        let full_width = expansion_size * self.width;
-        let full_height = expansion_size * self.height();
-        for garden_row in 0..expansion_size {
-            for garden_col in 0..expansion_size {
-                for row in 0..self.width {
-                    for col in 0..self.height() {
-                        let i = (garden_row * self.height() + row) * full_width
-                            + garden_col * self.width
-                            + col;
-                        expanded_tiles[i] = self.tiles[row * self.width + col];
-                    }
-                }
+    /// Samples the reachable counts every period of garden widths, and extrapolates from them
+    /// once their second differences settle, trying longer periods if they don't.
+    fn extrapolated_reachable(&self, steps: usize, samples: usize) -> Option<u64> {
+        for multiple in [1, 2] {
+            let period = multiple * self.width;
+            let first = steps % period;
+            let reach = TiledReach::new(self, first + (samples - 1) * period);
+            let counts = (0..samples)
+                .map(|k| reach.reachable(first + k * period))
+                .collect::<Vec<u64>>();
+            if let Some(count) = extrapolate_quadratic(&counts, steps / period) {
+                return Some(count);
             }
         }
+        None
+    }
+}
 
-        let mut plots: HashSet<usize> = HashSet::new();
-        plots.insert(
-            (expansion * self.height() + self.start / self.width) * full_width
-                + expansion * self.width
-                + self.start % self.width,
-        );
-        for _ in 0..steps {
-            // For each plot, add any of its 4 neighbors which are valid and remove itself
-            let mut new_plots: HashSet<usize> = HashSet::new();
-            for plot in plots.iter() {
-                let row = plot / full_width;
-                let col = plot % full_width;
-                // North
-                if row > 0 && expanded_tiles[(row - 1) * full_width + col] != Tile::Rock {
-                    new_plots.insert((row - 1) * full_width + col);
-                }
-                // South
-                if row < full_height - 1
-                    && expanded_tiles[(row + 1) * full_width + col] != Tile::Rock
-                {
-                    new_plots.insert((row + 1) * full_width + col);
-                }
-                // East
-                if col < full_width - 1 && expanded_tiles[row * full_width + col + 1] != Tile::Rock
-                {
-                    new_plots.insert(row * full_width + col + 1);
-                }
-                // West
-                if col > 0 && expanded_tiles[row * full_width + col - 1] != Tile::Rock {
-                    new_plots.insert(row * full_width + col - 1);
+/// How many periods to sample before extrapolating.
+const EXTRAPOLATION_SAMPLES: usize = 8;
+
+/// Extends `values` to index `k`, if the last few of them have settled into a quadratic.
+fn extrapolate_quadratic(values: &[u64], k: usize) -> Option<u64> {
+    if let Some(value) = values.get(k) {
+        return Some(*value);
+    }
+    let values = values.iter().map(|&v| v as i128).collect::<Vec<_>>();
+    let first = values.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+    let second = first.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+    // Insist on a few matching second differences so a coincidence doesn't count
+    let last_second = *second.last()?;
+    if second.len() < 3 || second[second.len() - 3..].iter().any(|&d| d != last_second) {
+        return None;
+    }
+
+    let t = (k - (values.len() - 1)) as i128;
+    let value = values.last()? + t * first.last()? + t * (t + 1) / 2 * last_second;
+    u64::try_from(value).ok()
+}
+
+/// The most plots to search across copies of the garden, about a gigabyte of distances.
+const MAX_TILED_PLOTS: usize = 1 << 28;
+
+/// Distances from the start to every plot within reach, across enough copies of the garden in
+/// each direction that the edge is never reached.
+#[derive(Debug)]
+struct TiledReach {
+    width: usize,
+    height: usize,
+    copies: usize,
+    distances: Vec<u32>,
+    /// How many plots are first reached after each number of steps.
+    histogram: Vec<u64>,
+}
+
+impl TiledReach {
+    fn new(garden: &Garden, max_steps: usize) -> Self {
+        let (width, height) = (garden.width, garden.height());
+        let copies = Self::copies(garden, max_steps);
+        let (full_width, full_height) = ((2 * copies + 1) * width, (2 * copies + 1) * height);
+        let tile = |row: usize, col: usize| garden.tiles[(row % height) * width + col % width];
+
+        let mut distances = vec![u32::MAX; full_width * full_height];
+        let mut histogram = vec![0; max_steps + 1];
+        let start_row = copies * height + garden.start / width;
+        let start_col = copies * width + garden.start % width;
+        let mut queue = VecDeque::from([(start_row, start_col, 0)]);
+        distances[start_row * full_width + start_col] = 0;
+        while let Some((row, col, distance)) = queue.pop_front() {
+            histogram[distance] += 1;
+            if distance == max_steps {
+                continue;
+            }
+            for (next_row, next_col) in [
+                (row - 1, col),
+                (row + 1, col),
+                (row, col - 1),
+                (row, col + 1),
+            ] {
+                let i = next_row * full_width + next_col;
+                if tile(next_row, next_col) != Tile::Rock && distances[i] == u32::MAX {
+                    distances[i] = distance as u32 + 1;
+                    queue.push_back((next_row, next_col, distance + 1));
                 }
             }
-            plots = new_plots;
         }
 
-        // Count locations in each garden
-        let mut garden_counts = Vec::new();
-        for garden_row in 0..expansion_size {
-            for garden_col in 0..expansion_size {
-                let mut garden_plot_count = 0;
-                for row in 0..self.width {
-                    for col in 0..self.height() {
-                        let i = (garden_row * self.height() + row) * full_width
-                            + garden_col * self.width
-                            + col;
-                        if plots.contains(&i) {
-                            garden_plot_count += 1;
-                        }
-                    }
-                }
-                garden_counts.push(garden_plot_count);
+        TiledReach {
+            width,
+            height,
+            copies,
+            distances,
+            histogram,
+        }
+    }
+
+    /// Like `new`, but `None` if that would search more than `MAX_TILED_PLOTS` plots.
+    fn checked_new(garden: &Garden, max_steps: usize) -> Option<Self> {
+        let side = Self::copies(garden, max_steps).checked_mul(2)? + 1;
+        let plots = side.checked_mul(side)?.checked_mul(garden.tiles.len())?;
+        (plots <= MAX_TILED_PLOTS).then(|| Self::new(garden, max_steps))
+    }
+
+    /// How many copies of the garden each way keep `max_steps` steps from reaching the edge.
+    fn copies(garden: &Garden, max_steps: usize) -> usize {
+        max_steps / garden.width.min(garden.height()) + 1
+    }
+
+    /// The number of plots reachable in exactly `steps` steps, which are those reachable in
+    /// fewer with matching parity, since stepping back and forth wastes two steps at a time.
+    fn reachable(&self, steps: usize) -> u64 {
+        self.histogram
+            .iter()
+            .take(steps + 1)
+            .skip(steps % 2)
+            .step_by(2)
+            .sum()
+    }
+
+    /// The number of plots reachable in exactly `steps` steps within each copy of the garden,
+    /// keyed by how many copies it is down and to the right of the original.
+    #[allow(dead_code)]
+    fn reachable_by_copy(&self, steps: usize) -> HashMap<(i64, i64), u64> {
+        let full_width = (2 * self.copies + 1) * self.width;
+        let mut counts = HashMap::new();
+        for (i, &distance) in self.distances.iter().enumerate() {
+            let distance = distance as usize;
+            if distance <= steps && distance % 2 == steps % 2 {
+                let (row, col) = (i / full_width, i % full_width);
+                let copy = (
+                    (row / self.height) as i64 - self.copies as i64,
+                    (col / self.width) as i64 - self.copies as i64,
+                );
+                *counts.entry(copy).or_insert(0) += 1;
             }
         }
-        garden_counts
+        counts
     }
 }
 
@@ -234,3 +276,59 @@ fn test_garden_plots_reachable_after_n_steps() {
     assert_eq!(garden.garden_plots_reachable_after_n_steps(3), 6);
     assert_eq!(garden.garden_plots_reachable_after_n_steps(6), 16);
 }
+
+#[test]
+fn test_garden_plots_reachable_infinite() {
+    let input = "...........\n.....###.#.\n.###.##..#.\n..#.#...#..\n....#.#....\n.##..S####.\n.##..#...#.\n.......##..\n.##.#.####.\n.##..##.##.\n...........\n";
+    let garden = Garden::parse(input);
+
+    assert_eq!(garden.garden_plots_reachable_infinite(6), Some(16));
+    assert_eq!(garden.garden_plots_reachable_infinite(10), Some(50));
+    assert_eq!(garden.garden_plots_reachable_infinite(50), Some(1594));
+    assert_eq!(garden.garden_plots_reachable_infinite(100), Some(6536));
+    assert_eq!(garden.garden_plots_reachable_infinite(500), Some(167004));
+    assert_eq!(garden.garden_plots_reachable_infinite(1000), Some(668697));
+    assert_eq!(garden.garden_plots_reachable_infinite(5000), Some(16733044));
+}
+
+#[test]
+fn test_extrapolation_matches_brute_force() {
+    let input = "...........\n.....###.#.\n.###.##..#.\n..#.#...#..\n....#.#....\n.##..S####.\n.##..#...#.\n.......##..\n.##.#.####.\n.##..##.##.\n...........\n";
+    let garden = Garden::parse(input);
+    let brute_force = TiledReach::new(&garden, 400);
+    for steps in 200..=400 {
+        assert_eq!(
+            garden.extrapolated_reachable(steps, EXTRAPOLATION_SAMPLES),
+            Some(brute_force.reachable(steps)),
+            "{steps} steps"
+        );
+    }
+}
+
+#[test]
+fn test_reachable_off_centre() {
+    // Not square, with the start in a corner, so only brute force will do
+    let input = "S..#\n.#..\n....\n";
+    let garden = Garden::parse(input);
+    assert!(!garden.is_square_and_centred());
+    assert_eq!(garden.garden_plots_reachable_infinite(1), Some(3));
+    assert_eq!(
+        garden.garden_plots_reachable_infinite(30),
+        Some(TiledReach::new(&garden, 30).reachable(30))
+    );
+    assert_eq!(garden.garden_plots_reachable_infinite(26501365), None);
+}
+
+#[test]
+fn test_reachable_by_copy() {
+    let input = "...........\n.....###.#.\n.###.##..#.\n..#.#...#..\n....#.#....\n.##..S####.\n.##..#...#.\n.......##..\n.##.#.####.\n.##..##.##.\n...........\n";
+    let garden = Garden::parse(input);
+    let reach = TiledReach::new(&garden, 50);
+    let by_copy = reach.reachable_by_copy(50);
+    assert_eq!(by_copy.values().sum::<u64>(), 1594);
+    // Within the original garden, matching the bounded search
+    assert_eq!(
+        by_copy[&(0, 0)],
+        garden.garden_plots_reachable_after_n_steps(50) as u64
+    );
+}
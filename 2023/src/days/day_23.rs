@@ -1,25 +1,17 @@
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use std::fs;
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
     let map = Map::parse(&input);
-    (map.longest_path_length() - 1).to_string()
+    map.longest_hike().expect("no way down").length.to_string()
 }
 
 pub fn part2() -> String {
     let input = get_input_file_contents();
     let mut map = Map::parse(&input);
     map.replace_slopes();
-    let graph_edges = map.graph();
-    let (_, longest_path_length) = find_longest_path(
-        &graph_edges,
-        map.start_index(),
-        map.goal_index(),
-        Vec::new(),
-        0,
-    );
-    longest_path_length.to_string()
+    map.longest_hike().expect("no way down").length.to_string()
 }
 
 fn get_input_file_contents() -> String {
@@ -29,6 +21,8 @@ fn get_input_file_contents() -> String {
 struct Map {
     tiles: Vec<Tile>,
     width: usize,
+    /// Tiles to highlight when displaying the map.
+    route: HashSet<usize>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -41,6 +35,31 @@ enum Tile {
     SlopeRight,
 }
 
+/// The map reduced to its junctions, with the corridors between them as weighted edges.
+#[derive(Debug)]
+struct JunctionGraph {
+    /// The tile index of each junction, starting with the start and ending with the goal.
+    junctions: Vec<usize>,
+    /// The corridors leading out of each junction which can be walked, given the slopes.
+    edges: Vec<Vec<Edge>>,
+}
+
+#[derive(Debug)]
+struct Edge {
+    to: usize,
+    /// Every tile along the corridor, ending with the junction it leads to.
+    tiles: Vec<usize>,
+}
+
+/// A route through the map, as the junctions it passes and every tile it steps on.
+#[derive(Debug)]
+struct Hike {
+    length: usize,
+    #[allow(dead_code)]
+    junctions: Vec<usize>,
+    tiles: Vec<usize>,
+}
+
 impl Map {
     fn parse(input: &str) -> Self {
         let mut tiles = Vec::new();
@@ -56,6 +75,7 @@ impl Map {
         Map {
             tiles,
             width: width.unwrap(),
+            route: HashSet::new(),
         }
     }
 
@@ -79,238 +99,208 @@ impl Map {
         self.tiles.len() - 2
     }
 
-    fn longest_path_length(&self) -> usize {
-        self.longest_path(self.start_index(), Vec::new()).len()
+    /// The open tiles next to `idx`, along with the direction of each.
+    fn neighbours(&self, idx: usize) -> impl Iterator<Item = (usize, Tile)> + '_ {
+        let (row, col) = (idx / self.width, idx % self.width);
+        [
+            (row > 0).then(|| (idx.wrapping_sub(self.width), Tile::SlopeUp)),
+            (row + 1 < self.height()).then(|| (idx + self.width, Tile::SlopeDown)),
+            (col > 0).then(|| (idx.wrapping_sub(1), Tile::SlopeLeft)),
+            (col + 1 < self.width).then(|| (idx + 1, Tile::SlopeRight)),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|(next, _)| self.tiles[*next] != Tile::Forest)
     }
 
-    fn longest_path(&self, mut cur_idx: usize, mut path_so_far: Vec<usize>) -> Vec<usize> {
-        let mut options = Vec::new();
-        loop {
-            path_so_far.push(cur_idx);
-            let cur_row = cur_idx / self.width;
-            let cur_col = cur_idx % self.width;
-
-            options.clear();
-            if self.tiles[cur_idx].is_slope() {
-                // Must follow slope
-                match self.tiles[cur_idx] {
-                    Tile::SlopeUp => options.push(cur_idx - self.width),
-                    Tile::SlopeDown => options.push(cur_idx + self.width),
-                    Tile::SlopeLeft => options.push(cur_idx - 1),
-                    Tile::SlopeRight => options.push(cur_idx + 1),
-                    _ => unreachable!(),
-                }
-            } else {
-                // Up
-                if cur_row > 0
-                    && self.tiles[cur_idx - self.width] != Tile::Forest
-                    && !path_so_far.contains(&(cur_idx - self.width))
-                    && self.tiles[cur_idx - self.width] != Tile::SlopeDown
-                {
-                    options.push(cur_idx - self.width);
-                }
-                // Down
-                if cur_row < self.height() - 1
-                    && self.tiles[cur_idx + self.width] != Tile::Forest
-                    && !path_so_far.contains(&(cur_idx + self.width))
-                    && self.tiles[cur_idx + self.width] != Tile::SlopeUp
-                {
-                    options.push(cur_idx + self.width);
-                }
-                // Left
-                if cur_col > 0
-                    && self.tiles[cur_idx - 1] != Tile::Forest
-                    && !path_so_far.contains(&(cur_idx - 1))
-                    && self.tiles[cur_idx - 1] != Tile::SlopeRight
-                {
-                    options.push(cur_idx - 1);
-                }
-                // Right
-                if cur_col < self.width - 1
-                    && self.tiles[cur_idx + 1] != Tile::Forest
-                    && !path_so_far.contains(&(cur_idx + 1))
-                    && self.tiles[cur_idx + 1] != Tile::SlopeLeft
-                {
-                    options.push(cur_idx + 1);
-                }
-            }
-            if options.len() == 1 && options[0] != self.goal_index() {
-                // Don't need to recurse if there's only one option
-                cur_idx = options[0];
-            } else {
-                break;
-            }
-        }
-
-        if options.is_empty() {
-            // Dead end
-            Vec::new()
-        } else {
-            let mut longest_path = Vec::new();
-            for new_idx in options.iter() {
-                let path = if *new_idx == self.goal_index() {
-                    path_so_far.push(*new_idx);
-                    path_so_far.clone()
-                } else {
-                    self.longest_path(*new_idx, path_so_far.clone())
-                };
-                if path.len() > longest_path.len() {
-                    longest_path = path;
-                }
-            }
-            longest_path
-        }
+    /// Whether a step from `from` to `to` in `direction` goes with the slopes, if there are any.
+    fn can_step(&self, from: usize, to: usize, direction: &Tile) -> bool {
+        let with_slope = |tile: &Tile| !tile.is_slope() || tile == direction;
+        with_slope(&self.tiles[from]) && with_slope(&self.tiles[to])
     }
 
-    fn nodes(&self) -> Vec<usize> {
-        let mut nodes = Vec::new();
-        nodes.push(self.start_index());
-        for row in 0..self.height() {
-            for col in 0..self.width {
-                if self.tiles[row * self.width + col] != Tile::Path {
-                    continue;
-                }
+    fn junction_graph(&self) -> JunctionGraph {
+        let mut junctions = vec![self.start_index()];
+        junctions.extend(
+            (0..self.tiles.len())
+                .filter(|&i| self.tiles[i] != Tile::Forest && self.neighbours(i).count() > 2),
+        );
+        junctions.push(self.goal_index());
+        assert!(
+            junctions.len() <= 64,
+            "too many junctions for a u64 bitmask"
+        );
+        let junction_ids = junctions
+            .iter()
+            .enumerate()
+            .map(|(id, &idx)| (idx, id))
+            .collect::<HashMap<usize, usize>>();
 
-                let mut paths = 0;
-                if row > 0 && self.tiles[(row - 1) * self.width + col] == Tile::Path {
-                    paths += 1;
+        let mut edges = Vec::with_capacity(junctions.len());
+        for &junction in &junctions {
+            let mut corridors = Vec::new();
+            for (first, direction) in self.neighbours(junction) {
+                let mut walkable = self.can_step(junction, first, &direction);
+                let (mut prev, mut cur) = (junction, first);
+                let mut tiles = vec![first];
+                // Follow the corridor until it reaches another junction or dead ends
+                while !junction_ids.contains_key(&cur) {
+                    let Some((next, direction)) = self.neighbours(cur).find(|(n, _)| *n != prev)
+                    else {
+                        walkable = false;
+                        break;
+                    };
+                    walkable &= self.can_step(cur, next, &direction);
+                    (prev, cur) = (cur, next);
+                    tiles.push(cur);
                 }
-                if row < self.height() - 1 && self.tiles[(row + 1) * self.width + col] == Tile::Path
-                {
-                    paths += 1;
-                }
-                if col > 0 && self.tiles[row * self.width + col - 1] == Tile::Path {
-                    paths += 1;
-                }
-                if col < self.width - 1 && self.tiles[row * self.width + col + 1] == Tile::Path {
-                    paths += 1;
-                }
-                if paths > 2 {
-                    nodes.push(row * self.width + col);
+                if walkable {
+                    corridors.push(Edge {
+                        to: junction_ids[&cur],
+                        tiles,
+                    });
                 }
             }
+            edges.push(corridors);
         }
-        nodes.push(self.goal_index());
-        nodes
+
+        JunctionGraph { junctions, edges }
     }
 
-    fn next_node(&self, nodes: &[usize], start_node: usize, next_path: usize) -> (usize, usize) {
-        let mut dist = 1;
-        let mut cur_idx = next_path;
-        let mut prev_idx = start_node;
-        while !nodes.contains(&cur_idx) {
-            let row = cur_idx / self.width;
-            let col = cur_idx % self.width;
-            if row > 0
-                && self.tiles[(row - 1) * self.width + col] == Tile::Path
-                && (row - 1) * self.width + col != prev_idx
-            {
-                prev_idx = cur_idx;
-                cur_idx = (row - 1) * self.width + col;
-            } else if row < self.height() - 1
-                && self.tiles[(row + 1) * self.width + col] == Tile::Path
-                && (row + 1) * self.width + col != prev_idx
-            {
-                prev_idx = cur_idx;
-                cur_idx = (row + 1) * self.width + col;
-            } else if col > 0
-                && self.tiles[row * self.width + col - 1] == Tile::Path
-                && row * self.width + col - 1 != prev_idx
-            {
-                prev_idx = cur_idx;
-                cur_idx = row * self.width + col - 1;
-            } else if col < self.width - 1
-                && self.tiles[row * self.width + col + 1] == Tile::Path
-                && row * self.width + col + 1 != prev_idx
-            {
-                prev_idx = cur_idx;
-                cur_idx = row * self.width + col + 1;
-            } else {
-                panic!("dead end");
-            }
-            dist += 1;
+    /// The longest hike from the start to the goal that never steps on the same tile twice.
+    fn longest_hike(&self) -> Option<Hike> {
+        let graph = self.junction_graph();
+        let junctions = graph.longest_path(0, graph.junctions.len() - 1)?;
+        let mut tiles = vec![self.start_index()];
+        for pair in junctions.windows(2) {
+            let edge = graph.edge(pair[0], pair[1]);
+            tiles.extend(&edge.tiles);
         }
-        (cur_idx, dist)
+        Some(Hike {
+            length: tiles.len() - 1,
+            junctions,
+            tiles,
+        })
     }
 
-    fn graph(&self) -> HashMap<(usize, usize), usize> {
-        let nodes = self.nodes();
-        let mut edges = HashMap::new();
-        for node in nodes.iter() {
-            let row = node / self.width;
-            let col = node % self.width;
-            if row > 0 && self.tiles[(row - 1) * self.width + col] == Tile::Path {
-                // Go up and find the next node
-                let (next_node, dist) = self.next_node(&nodes, *node, (row - 1) * self.width + col);
-                edges.insert((*node, next_node), dist);
-            }
-            if row < self.height() - 1 && self.tiles[(row + 1) * self.width + col] == Tile::Path {
-                // Go down and find the next node
-                let (next_node, dist) = self.next_node(&nodes, *node, (row + 1) * self.width + col);
-                edges.insert((*node, next_node), dist);
-            }
-            if col > 0 && self.tiles[row * self.width + col - 1] == Tile::Path {
-                // Go left and find the next node
-                let (next_node, dist) = self.next_node(&nodes, *node, row * self.width + col - 1);
-                edges.insert((*node, next_node), dist);
+    /// Highlights the tiles of `hike` when the map is displayed.
+    #[allow(dead_code)]
+    fn show_route(&mut self, hike: &Hike) {
+        self.route = hike.tiles.iter().copied().collect();
+    }
+}
+
+impl JunctionGraph {
+    /// The longest edge from one junction to another, since that's the one a longest path uses.
+    fn edge(&self, from: usize, to: usize) -> &Edge {
+        self.edges[from]
+            .iter()
+            .filter(|e| e.to == to)
+            .max_by_key(|e| e.tiles.len())
+            .expect("junctions not connected")
+    }
+
+    /// The junctions along the longest simple path from `start` to `goal`. The search splits
+    /// across threads at the first junction with a choice of ways on.
+    fn longest_path(&self, start: usize, goal: usize) -> Option<Vec<usize>> {
+        // Follow the path while there's only one way to go
+        let mut prefix = vec![start];
+        let mut visited = 1u64 << start;
+        let mut length = 0;
+        let mut current = start;
+        let choices = loop {
+            if current == goal {
+                return Some(prefix);
             }
-            if col < self.width - 1 && self.tiles[row * self.width + col + 1] == Tile::Path {
-                // Go right and find the next node
-                let (next_node, dist) = self.next_node(&nodes, *node, row * self.width + col + 1);
-                edges.insert((*node, next_node), dist);
+            let choices = self.edges[current]
+                .iter()
+                .filter(|e| visited & (1 << e.to) == 0)
+                .collect::<Vec<_>>();
+            if choices.len() != 1 {
+                break choices;
             }
-        }
-        edges
+            current = choices[0].to;
+            length += choices[0].tiles.len();
+            visited |= 1 << current;
+            prefix.push(current);
+        };
+
+        let best = std::thread::scope(|scope| {
+            let searches = choices
+                .iter()
+                .map(|edge| {
+                    scope.spawn(move || {
+                        let mut search = Search {
+                            graph: self,
+                            goal,
+                            goal_gate: self.goal_gate(goal),
+                            path: vec![edge.to],
+                            best: None,
+                        };
+                        search.run(edge.to, visited | 1 << edge.to, length + edge.tiles.len());
+                        search.best
+                    })
+                })
+                .collect::<Vec<_>>();
+            searches
+                .into_iter()
+                .filter_map(|s| s.join().unwrap())
+                .max_by_key(|(length, _)| *length)
+        });
+
+        let (_, rest) = best?;
+        prefix.extend(rest);
+        Some(prefix)
+    }
+
+    /// The only junction leading to the goal, if there is just one. Once a path reaches it, it
+    /// has to head straight for the goal, as it could never get there later.
+    fn goal_gate(&self, goal: usize) -> Option<usize> {
+        let mut into_goal =
+            (0..self.edges.len()).filter(|&j| self.edges[j].iter().any(|e| e.to == goal));
+        let gate = into_goal.next()?;
+        into_goal.next().is_none().then_some(gate)
     }
 }
 
-fn find_longest_path(
-    edges: &HashMap<(usize, usize), usize>,
-    cur_idx: usize,
-    goal_idx: usize,
-    mut path_so_far: Vec<usize>,
-    path_length_so_far: usize,
-) -> (Vec<usize>, usize) {
-    path_so_far.push(cur_idx);
-
-    let mut longest_path = Vec::new();
-    let mut longest_path_length = 0;
-    for ((cur_node, next_node), dist) in edges.iter() {
-        if *cur_node != cur_idx {
-            continue;
-        }
-        if path_so_far.contains(next_node) {
-            continue;
-        }
-        if *cur_node == 118 {
-            println!("{cur_node} -> {next_node}: {dist}");
+/// A depth-first search for the longest path, keeping the junctions visited as a bitmask.
+struct Search<'a> {
+    graph: &'a JunctionGraph,
+    goal: usize,
+    goal_gate: Option<usize>,
+    path: Vec<usize>,
+    best: Option<(usize, Vec<usize>)>,
+}
+
+impl Search<'_> {
+    fn run(&mut self, current: usize, visited: u64, length: usize) {
+        if current == self.goal {
+            if self.best.as_ref().is_none_or(|(best, _)| length > *best) {
+                self.best = Some((length, self.path.clone()));
+            }
+            return;
         }
-        let (path, path_length) = if *next_node == goal_idx {
-            let mut path = path_so_far.clone();
-            path.push(*next_node);
-            (path, path_length_so_far + dist)
-        } else {
-            find_longest_path(
-                edges,
-                *next_node,
-                goal_idx,
-                path_so_far.clone(),
-                path_length_so_far + dist,
-            )
-        };
-        if path_length > longest_path_length {
-            longest_path_length = path_length;
-            longest_path = path;
+        let graph = self.graph;
+        for edge in &graph.edges[current] {
+            if visited & (1 << edge.to) != 0
+                || (Some(current) == self.goal_gate && edge.to != self.goal)
+            {
+                continue;
+            }
+            self.path.push(edge.to);
+            self.run(edge.to, visited | 1 << edge.to, length + edge.tiles.len());
+            self.path.pop();
         }
     }
-    (longest_path, longest_path_length)
 }
 
 impl std::fmt::Display for Map {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for row in 0..self.height() {
             for col in 0..self.width {
-                let ch = match self.tiles[row * self.width + col] {
+                let idx = row * self.width + col;
+                let ch = match self.tiles[idx] {
+                    _ if self.route.contains(&idx) => 'O',
                     Tile::Path => '.',
                     Tile::Forest => '#',
                     Tile::SlopeUp => '^',
@@ -358,7 +348,7 @@ fn test_parse_map() {
 fn test_longest_path_length() {
     let input = "#.#####################\n#.......#########...###\n#######.#########.#.###\n###.....#.>.>.###.#.###\n###v#####.#v#.###.#.###\n###.>...#.#.#.....#...#\n###v###.#.#.#########.#\n###...#.#.#.......#...#\n#####.#.#.#######.#.###\n#.....#.#.#.......#...#\n#.#####.#.#.#########v#\n#.#...#...#...###...>.#\n#.#.#v#######v###.###v#\n#...#.>.#...>.>.#.###.#\n#####v#.#.###v#.#.###.#\n#.....#...#...#.#.#...#\n#.#########.###.#.#.###\n#...###...#...#...#.###\n###.###.#.###v#####v###\n#...#...#.#.>.>.#.>.###\n#.###.###.#.###.#.#v###\n#.....###...###...#...#\n#####################.#\n";
     let map = Map::parse(input);
-    assert_eq!(map.longest_path_length() - 1, 94);
+    assert_eq!(map.longest_hike().unwrap().length, 94);
 }
 
 #[test]
@@ -366,7 +356,7 @@ fn test_longest_path_length_no_slopes() {
     let input = "#.#####################\n#.......#########...###\n#######.#########.#.###\n###.....#.>.>.###.#.###\n###v#####.#v#.###.#.###\n###.>...#.#.#.....#...#\n###v###.#.#.#########.#\n###...#.#.#.......#...#\n#####.#.#.#######.#.###\n#.....#.#.#.......#...#\n#.#####.#.#.#########v#\n#.#...#...#...###...>.#\n#.#.#v#######v###.###v#\n#...#.>.#...>.>.#.###.#\n#####v#.#.###v#.#.###.#\n#.....#...#...#.#.#...#\n#.#########.###.#.#.###\n#...###...#...#...#.###\n###.###.#.###v#####v###\n#...#...#.#.>.>.#.>.###\n#.###.###.#.###.#.#v###\n#.....###...###...#...#\n#####################.#\n";
     let mut map = Map::parse(input);
     map.replace_slopes();
-    assert_eq!(map.longest_path_length() - 1, 154);
+    assert_eq!(map.longest_hike().unwrap().length, 154);
 }
 
 #[test]
@@ -374,8 +364,11 @@ fn test_nodes() {
     let input = "#.#####################\n#.......#########...###\n#######.#########.#.###\n###.....#.>.>.###.#.###\n###v#####.#v#.###.#.###\n###.>...#.#.#.....#...#\n###v###.#.#.#########.#\n###...#.#.#.......#...#\n#####.#.#.#######.#.###\n#.....#.#.#.......#...#\n#.#####.#.#.#########v#\n#.#...#...#...###...>.#\n#.#.#v#######v###.###v#\n#...#.>.#...>.>.#.###.#\n#####v#.#.###v#.#.###.#\n#.....#...#...#.#.#...#\n#.#########.###.#.#.###\n#...###...#...#...#.###\n###.###.#.###v#####v###\n#...#...#.#.>.>.#.>.###\n#.###.###.#.###.#.#v###\n#.....###...###...#...#\n#####################.#\n";
     let mut map = Map::parse(input);
     map.replace_slopes();
-    let nodes = map.nodes();
-    assert_eq!(nodes, vec![1, 80, 118, 274, 304, 312, 450, 456, 527]);
+    let graph = map.junction_graph();
+    assert_eq!(
+        graph.junctions,
+        vec![1, 80, 118, 274, 304, 312, 450, 456, 527]
+    );
 }
 
 #[test]
@@ -383,13 +376,27 @@ fn test_graph_longest_path() {
     let input = "#.#####################\n#.......#########...###\n#######.#########.#.###\n###.....#.>.>.###.#.###\n###v#####.#v#.###.#.###\n###.>...#.#.#.....#...#\n###v###.#.#.#########.#\n###...#.#.#.......#...#\n#####.#.#.#######.#.###\n#.....#.#.#.......#...#\n#.#####.#.#.#########v#\n#.#...#...#...###...>.#\n#.#.#v#######v###.###v#\n#...#.>.#...>.>.#.###.#\n#####v#.#.###v#.#.###.#\n#.....#...#...#.#.#...#\n#.#########.###.#.#.###\n#...###...#...#...#.###\n###.###.#.###v#####v###\n#...#...#.#.>.>.#.>.###\n#.###.###.#.###.#.#v###\n#.....###...###...#...#\n#####################.#\n";
     let mut map = Map::parse(input);
     map.replace_slopes();
-    let graph_edges = map.graph();
-    let (_, longest_path_length) = find_longest_path(
-        &graph_edges,
-        map.start_index(),
-        map.goal_index(),
-        Vec::new(),
-        0,
-    );
-    assert_eq!(longest_path_length, 154);
+    let graph = map.junction_graph();
+    let path = graph.longest_path(0, graph.junctions.len() - 1).unwrap();
+    let length = path
+        .windows(2)
+        .map(|pair| graph.edge(pair[0], pair[1]).tiles.len())
+        .sum::<usize>();
+    assert_eq!(length, 154);
+    assert_eq!(path.first(), Some(&0));
+    assert_eq!(path.last(), Some(&8));
+}
+
+#[test]
+fn test_render_hike() {
+    let input = "#.###\n#...#\n#.#.#\n#...#\n###.#\n";
+    let mut map = Map::parse(input);
+    let hike = map.longest_hike().unwrap();
+    assert_eq!(hike.length, 6);
+    assert_eq!(hike.junctions.len(), 4);
+    map.show_route(&hike);
+    let rendered = map.to_string();
+    assert_eq!(rendered.matches('O').count(), 7);
+    assert_eq!(rendered.lines().next(), Some("#O###"));
+    assert_eq!(rendered.lines().last(), Some("###O#"));
 }
@@ -1,17 +1,19 @@
+use anyhow::{bail, Result};
+use num::{BigInt, Integer, Signed, Zero};
 use std::fs;
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
     let hailstones = parse_hailstones(&input);
-    path_intersections_2d(&hailstones, 200000000000000.0, 400000000000000.0).to_string()
+    path_intersections_2d(&hailstones, 200000000000000, 400000000000000).to_string()
 }
 
 pub fn part2() -> String {
     let input = get_input_file_contents();
     let hailstones = parse_hailstones(&input);
-    let intersecting_hailstone = intersecting_hailstone_3d(&hailstones);
-    (intersecting_hailstone.pos.x + intersecting_hailstone.pos.y + intersecting_hailstone.pos.z)
-        .to_string()
+    let rock = intersecting_hailstone_3d(&hailstones)
+        .expect("Failed to find a throw hitting every hailstone");
+    (rock.pos.x + rock.pos.y + rock.pos.z).to_string()
 }
 
 fn get_input_file_contents() -> String {
@@ -22,26 +24,33 @@ fn parse_hailstones(input: &str) -> Vec<Hailstone> {
     input.lines().map(Hailstone::parse).collect()
 }
 
-fn path_intersections_2d(hailstones: &[Hailstone], min: f64, max: f64) -> usize {
+fn path_intersections_2d(hailstones: &[Hailstone], min: i64, max: i64) -> usize {
     let mut count = 0;
     for (i, h1) in hailstones.iter().enumerate() {
-        for (_j, h2) in hailstones.iter().enumerate().skip(i + 1) {
-            let u = (h1.vel.x * h2.pos.y - h1.vel.x * h1.pos.y - h1.vel.y * h2.pos.x
-                + h1.vel.y * h1.pos.x)
-                / (h2.vel.x * h1.vel.y - h1.vel.x * h2.vel.y);
-            let t = (h2.pos.x + u * h2.vel.x - h1.pos.x) / h1.vel.x;
-
-            if t < 0.0 || u < 0.0 {
-                // Intersection occurs in the past
+        for h2 in hailstones.iter().skip(i + 1) {
+            // Solve p1 + t * v1 = p2 + u * v2, keeping t and u as fractions over `denominator`
+            let (p1, v1) = (h1.pos.widen(), h1.vel.widen());
+            let (p2, v2) = (h2.pos.widen(), h2.vel.widen());
+            let mut denominator = v1[0] * v2[1] - v1[1] * v2[0];
+            if denominator == 0 {
+                // Paths are parallel
                 continue;
             }
-            if t.is_infinite() || u.is_infinite() {
-                // Lines are parallel
+            let (dx, dy) = (p2[0] - p1[0], p2[1] - p1[1]);
+            let mut t = dx * v2[1] - dy * v2[0];
+            let mut u = dx * v1[1] - dy * v1[0];
+            if denominator < 0 {
+                (denominator, t, u) = (-denominator, -t, -u);
+            }
+
+            if t < 0 || u < 0 {
+                // Intersection occurs in the past
                 continue;
             }
 
-            let int_x = h1.pos.x + h1.vel.x * t;
-            let int_y = h1.pos.y + h1.vel.y * t;
+            let (min, max) = (min as i128 * denominator, max as i128 * denominator);
+            let int_x = p1[0] * denominator + v1[0] * t;
+            let int_y = p1[1] * denominator + v1[1] * t;
             if int_x >= min && int_x <= max && int_y >= min && int_y <= max {
                 // Collision is within test range
                 count += 1;
@@ -51,65 +60,124 @@ fn path_intersections_2d(hailstones: &[Hailstone], min: f64, max: f64) -> usize
     count
 }
 
-fn intersecting_hailstone_3d(hailstones: &[Hailstone]) -> Hailstone {
-    const COEFF_PAIRS: [(usize, usize); 4] = [(0, 1), (0, 2), (0, 3), (0, 4)];
-
-    let rows = COEFF_PAIRS.map(|(i, j)| {
-        nalgebra::RowVector4::new(
-            hailstones[j].vel.y - hailstones[i].vel.y,
-            hailstones[i].vel.x - hailstones[j].vel.x,
-            hailstones[i].pos.y - hailstones[j].pos.y,
-            hailstones[j].pos.x - hailstones[i].pos.x,
-        )
-    });
-    let coefficients = nalgebra::Matrix4::from_rows(&rows);
-    let rhs_value = |n: usize, _| {
-        let i = COEFF_PAIRS[n].0;
-        let j = COEFF_PAIRS[n].1;
-        hailstones[i].pos.x * hailstones[i].vel.y - hailstones[j].pos.x * hailstones[j].vel.y
-            + hailstones[j].pos.y * hailstones[j].vel.x
-            - hailstones[i].pos.y * hailstones[i].vel.x
-    };
-    let rhs = nalgebra::Matrix4x1::from_columns(&[nalgebra::Vector4::from_fn(rhs_value)]);
-    let solution = coefficients.try_inverse().unwrap() * rhs;
-    let x = solution.x;
-    let y = solution.y;
-    let vx = solution.z;
-    let vy = solution.w;
-
-    let rows = COEFF_PAIRS.map(|(i, j)| {
-        nalgebra::RowVector4::new(
-            hailstones[j].vel.z - hailstones[i].vel.z,
-            hailstones[i].vel.x - hailstones[j].vel.x,
-            hailstones[i].pos.z - hailstones[j].pos.z,
-            hailstones[j].pos.x - hailstones[i].pos.x,
-        )
-    });
-    let coefficients = nalgebra::Matrix4::from_rows(&rows);
-    let rhs_value = |n: usize, _| {
-        let i = COEFF_PAIRS[n].0;
-        let j = COEFF_PAIRS[n].1;
-        hailstones[i].pos.x * hailstones[i].vel.z - hailstones[j].pos.x * hailstones[j].vel.z
-            + hailstones[j].pos.z * hailstones[j].vel.x
-            - hailstones[i].pos.z * hailstones[i].vel.x
-    };
-    let rhs = nalgebra::Matrix4x1::from_columns(&[nalgebra::Vector4::from_fn(rhs_value)]);
-    let solution = coefficients.try_inverse().unwrap() * rhs;
-    let z = solution.y;
-    let vz = solution.w;
-
-    Hailstone {
-        pos: Vec3 {
-            x: -x.round(),
-            y: -y.round(),
-            z: -z.round(),
-        },
-        vel: Vec3 {
-            x: -vx.round(),
-            y: -vy.round(),
-            z: -vz.round(),
-        },
+/// The rock thrown from an integer position with an integer velocity that hits every hailstone.
+fn intersecting_hailstone_3d(hailstones: &[Hailstone]) -> Result<Hailstone> {
+    // Hitting hailstones a, b and c gives six linear equations in the rock's position and
+    // velocity, which are enough unless the hailstones are degenerate, so try them in turn
+    for a in 0..hailstones.len() {
+        for b in a + 1..hailstones.len() {
+            for c in b + 1..hailstones.len() {
+                let (mut matrix, mut rhs) = (Vec::new(), Vec::new());
+                for other in [b, c] {
+                    let (rows, values) = rock_equations(&hailstones[a], &hailstones[other]);
+                    matrix.extend(rows);
+                    rhs.extend(values);
+                }
+                let Some(solution) = solve_exact(matrix, rhs) else {
+                    continue;
+                };
+
+                let mut components = Vec::new();
+                for (numerator, denominator) in solution {
+                    let (value, remainder) = numerator.div_rem(&denominator);
+                    if !remainder.is_zero() {
+                        bail!(
+                            "the only rock hitting hailstones {a}, {b} and {c} isn't on the grid"
+                        );
+                    }
+                    components.push(i64::try_from(value)?);
+                }
+                let rock = Hailstone {
+                    pos: Vec3::new(components[0], components[1], components[2]),
+                    vel: Vec3::new(components[3], components[4], components[5]),
+                };
+                if let Some(missed) = hailstones.iter().position(|h| !rock.hits(h)) {
+                    bail!("the only rock hitting hailstones {a}, {b} and {c} misses hailstone {missed}");
+                }
+                return Ok(rock);
+            }
+        }
     }
+    bail!("every set of three hailstones is degenerate")
+}
+
+/// The three linear equations in the rock's position and velocity from hitting both hailstones.
+///
+/// The rock hits hailstone i when `(P - p_i) x (V - v_i) = 0`. The `P x V` term is the same
+/// for every hailstone, so subtracting the equations for i and j leaves a linear system.
+fn rock_equations(hi: &Hailstone, hj: &Hailstone) -> ([[BigInt; 6]; 3], [BigInt; 3]) {
+    let (pi, vi, pj, vj) = (
+        hi.pos.widen(),
+        hi.vel.widen(),
+        hj.pos.widen(),
+        hj.vel.widen(),
+    );
+    let dp = [0, 1, 2].map(|k| pi[k] - pj[k]);
+    let dv = [0, 1, 2].map(|k| vi[k] - vj[k]);
+    let (ci, cj) = (cross(pi, vi), cross(pj, vj));
+
+    // P x dv + dp x V = p_i x v_i - p_j x v_j, with unknowns ordered Px, Py, Pz, Vx, Vy, Vz
+    let rows = [
+        [0, dv[2], -dv[1], 0, -dp[2], dp[1]],
+        [-dv[2], 0, dv[0], dp[2], 0, -dp[0]],
+        [dv[1], -dv[0], 0, -dp[1], dp[0], 0],
+    ];
+    (
+        rows.map(|row| row.map(BigInt::from)),
+        [0, 1, 2].map(|k| BigInt::from(ci[k] - cj[k])),
+    )
+}
+
+fn cross(a: [i128; 3], b: [i128; 3]) -> [i128; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Solves a square linear system exactly with fraction-free (Bareiss) Gauss-Jordan
+/// elimination, giving each unknown as a numerator over a positive denominator. Returns
+/// `None` if the matrix is singular.
+fn solve_exact<const N: usize>(
+    matrix: Vec<[BigInt; N]>,
+    rhs: Vec<BigInt>,
+) -> Option<Vec<(BigInt, BigInt)>> {
+    assert_eq!(matrix.len(), N);
+    let mut rows = matrix
+        .into_iter()
+        .zip(rhs)
+        .map(|(row, value)| {
+            let mut row = row.to_vec();
+            row.push(value);
+            row
+        })
+        .collect::<Vec<_>>();
+
+    let mut previous_pivot = BigInt::from(1);
+    for k in 0..N {
+        let pivot_row = (k..N).find(|&i| !rows[i][k].is_zero())?;
+        rows.swap(k, pivot_row);
+        let pivot_row = rows[k].clone();
+        let pivot = &pivot_row[k];
+        for (_, row) in rows.iter_mut().enumerate().filter(|&(i, _)| i != k) {
+            let factor = row[k].clone();
+            for (entry, pivot_entry) in row.iter_mut().zip(&pivot_row) {
+                // Every entry stays a minor of the original matrix, so the division is exact
+                *entry = (pivot * &*entry - &factor * pivot_entry) / &previous_pivot;
+            }
+        }
+        previous_pivot = pivot.clone();
+    }
+
+    // Every diagonal entry is now the determinant
+    let sign = previous_pivot.signum();
+    let determinant = previous_pivot.abs();
+    Some(
+        rows.into_iter()
+            .map(|row| (&row[N] * &sign, determinant.clone()))
+            .collect(),
+    )
 }
 
 #[derive(Debug, PartialEq)]
@@ -120,9 +188,9 @@ struct Hailstone {
 
 #[derive(Debug, PartialEq, Clone)]
 struct Vec3 {
-    x: f64,
-    y: f64,
-    z: f64,
+    x: i64,
+    y: i64,
+    z: i64,
 }
 
 impl Hailstone {
@@ -135,12 +203,34 @@ impl Hailstone {
     }
 }
 
+impl Hailstone {
+    /// Whether a hailstone thrown from here meets `other` at the same place at some point in
+    /// the future.
+    fn hits(&self, other: &Hailstone) -> bool {
+        let offset = [0, 1, 2].map(|k| other.pos.widen()[k] - self.pos.widen()[k]);
+        let closing = [0, 1, 2].map(|k| self.vel.widen()[k] - other.vel.widen()[k]);
+        if closing == [0; 3] {
+            return offset == [0; 3];
+        }
+        // The offset must close up at the relative velocity, so be a non-negative multiple of it
+        cross(offset, closing) == [0; 3] && (0..3).all(|k| offset[k] * closing[k] >= 0)
+    }
+}
+
 impl Vec3 {
+    fn new(x: i64, y: i64, z: i64) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    fn widen(&self) -> [i128; 3] {
+        [self.x, self.y, self.z].map(i128::from)
+    }
+
     fn parse(input: &str) -> Self {
         let mut parts = input.split(',');
-        let x = parts.next().unwrap().trim().parse::<f64>().unwrap();
-        let y = parts.next().unwrap().trim().parse::<f64>().unwrap();
-        let z = parts.next().unwrap().trim().parse::<f64>().unwrap();
+        let x = parts.next().unwrap().trim().parse::<i64>().unwrap();
+        let y = parts.next().unwrap().trim().parse::<i64>().unwrap();
+        let z = parts.next().unwrap().trim().parse::<i64>().unwrap();
         Vec3 { x, y, z }
     }
 }
@@ -149,14 +239,7 @@ impl Vec3 {
 fn test_parse_vec3() {
     let input = "-2,  1, -2";
     let vec = Vec3::parse(input);
-    assert_eq!(
-        vec,
-        Vec3 {
-            x: -2.0,
-            y: 1.0,
-            z: -2.0
-        }
-    );
+    assert_eq!(vec, Vec3 { x: -2, y: 1, z: -2 });
 }
 
 #[test]
@@ -167,15 +250,11 @@ fn test_parse_hailstone() {
         hailstone,
         Hailstone {
             pos: Vec3 {
-                x: 19.0,
-                y: 13.0,
-                z: 30.0
+                x: 19,
+                y: 13,
+                z: 30
             },
-            vel: Vec3 {
-                x: -2.0,
-                y: 1.0,
-                z: -2.0
-            }
+            vel: Vec3 { x: -2, y: 1, z: -2 }
         }
     );
 }
@@ -184,7 +263,7 @@ fn test_parse_hailstone() {
 fn test_path_intersections() {
     let input = "19, 13, 30 @ -2,  1, -2\n18, 19, 22 @ -1, -1, -2\n20, 25, 34 @ -2, -2, -4\n12, 31, 28 @ -1, -2, -1\n20, 19, 15 @  1, -5, -3\n";
     let hailstones = parse_hailstones(input);
-    assert_eq!(path_intersections_2d(&hailstones, 7.0, 27.0), 2);
+    assert_eq!(path_intersections_2d(&hailstones, 7, 27), 2);
 }
 
 #[test]
@@ -192,18 +271,43 @@ fn test_intersecting_hailstone_3d() {
     let input = "19, 13, 30 @ -2,  1, -2\n18, 19, 22 @ -1, -1, -2\n20, 25, 34 @ -2, -2, -4\n12, 31, 28 @ -1, -2, -1\n20, 19, 15 @  1, -5, -3\n";
     let hailstones = parse_hailstones(input);
     assert_eq!(
-        intersecting_hailstone_3d(&hailstones),
+        intersecting_hailstone_3d(&hailstones).unwrap(),
         Hailstone {
             pos: Vec3 {
-                x: 24.0,
-                y: 13.0,
-                z: 10.0
+                x: 24,
+                y: 13,
+                z: 10
             },
-            vel: Vec3 {
-                x: -3.0,
-                y: 1.0,
-                z: 2.0
-            }
+            vel: Vec3 { x: -3, y: 1, z: 2 }
         }
     );
 }
+
+#[test]
+fn test_intersecting_hailstone_3d_skips_degenerate_hailstones() {
+    // The first two hailstones are identical, so together they say nothing about the rock
+    let input = "19, 13, 30 @ -2,  1, -2\n19, 13, 30 @ -2,  1, -2\n18, 19, 22 @ -1, -1, -2\n20, 25, 34 @ -2, -2, -4\n12, 31, 28 @ -1, -2, -1\n20, 19, 15 @  1, -5, -3\n";
+    let hailstones = parse_hailstones(input);
+    let rock = intersecting_hailstone_3d(&hailstones).unwrap();
+    assert_eq!(rock.pos, Vec3::new(24, 13, 10));
+    assert_eq!(rock.vel, Vec3::new(-3, 1, 2));
+}
+
+#[test]
+fn test_intersecting_hailstone_3d_large_coordinates() {
+    let input = "244259316451563, 333880314382621, 183218059857863 @ 17, -211, 113\n322856275505143, 276104369024881, 144256338686308 @ -93, -4, 251\n139572827724319, 306843302251365, 305396136588144 @ 165, -143, -24\n297396163878704, 466408163420567, 276482222056847 @ -58, -377, -15\n165211791597868, 159200463587462, 351263350454948 @ 108, -8, -66\n";
+    let hailstones = parse_hailstones(input);
+    let rock = intersecting_hailstone_3d(&hailstones).unwrap();
+    assert_eq!(
+        rock.pos,
+        Vec3::new(287430900705823, 451620998712421, 260730677041648)
+    );
+    assert_eq!(rock.vel, Vec3::new(-27, -331, 34));
+}
+
+#[test]
+fn test_no_intersecting_hailstone_3d() {
+    let input = "19, 13, 30 @ -2,  1, -2\n18, 19, 22 @ -1, -1, -2\n20, 25, 34 @ -2, -2, -4\n12, 31, 28 @ -1, -2, -1\n21, 19, 15 @  1, -5, -3\n";
+    let hailstones = parse_hailstones(input);
+    assert!(intersecting_hailstone_3d(&hailstones).is_err());
+}
@@ -1,14 +1,14 @@
-use hashbrown::{HashMap, HashSet};
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use hashbrown::HashMap;
+use priority_queue::PriorityQueue;
+use rand::{seq::SliceRandom, Rng};
 use std::fs;
 
 pub fn part1() -> String {
     let input = get_input_file_contents();
     let graph = Graph::parse(&input);
-    let component_sizes = graph.min_cut_component_sizes();
-    assert_eq!(component_sizes.len(), 2);
-    component_sizes.iter().product::<usize>().to_string()
+    let cut = graph.min_cut();
+    assert_eq!(cut.edges.len(), 3);
+    cut.component_sizes.iter().product::<usize>().to_string()
 }
 
 pub fn part2() -> String {
@@ -23,92 +23,177 @@ fn get_input_file_contents() -> String {
 #[derive(Debug, Clone)]
 struct Graph<'input> {
     nodes: Vec<&'input str>,
-    edges: Vec<(usize, usize)>,
+    /// The neighbours of each node, by index into `nodes`.
+    adjacency: Vec<Vec<usize>>,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-struct Component<'input>(&'input str);
+/// A split of the graph's nodes into two components, and the edges between them.
+#[derive(Debug, PartialEq)]
+struct Cut {
+    edges: Vec<(usize, usize)>,
+    component_sizes: [usize; 2],
+}
 
 impl<'input> Graph<'input> {
     fn parse(input: &'input str) -> Self {
         let mut nodes = Vec::new();
         let mut node_indices = HashMap::new();
-        for line in input.lines() {
-            let (node, connected_nodes) = line.split_once(':').unwrap();
-            if !nodes.contains(&node) {
+        let mut adjacency: Vec<Vec<usize>> = Vec::new();
+        let mut index_of = |node: &'input str, adjacency: &mut Vec<Vec<usize>>| {
+            *node_indices.entry(node).or_insert_with(|| {
                 nodes.push(node);
-                node_indices.insert(node, nodes.len() - 1);
-            }
-            for connected_node in connected_nodes.split_whitespace() {
-                if !nodes.contains(&connected_node) {
-                    nodes.push(connected_node);
-                    node_indices.insert(connected_node, nodes.len() - 1);
-                }
-            }
-        }
-
-        let mut edges = Vec::new();
+                adjacency.push(Vec::new());
+                nodes.len() - 1
+            })
+        };
         for line in input.lines() {
             let (node, connected_nodes) = line.split_once(':').unwrap();
-            let node_index = node_indices.get(&node).unwrap();
+            let node_index = index_of(node, &mut adjacency);
             for connected_node in connected_nodes.split_whitespace() {
-                let connected_node_index = node_indices.get(&connected_node).unwrap();
-                edges.push((*node_index, *connected_node_index));
-                edges.push((*connected_node_index, *node_index));
+                let connected_node_index = index_of(connected_node, &mut adjacency);
+                adjacency[node_index].push(connected_node_index);
+                adjacency[connected_node_index].push(node_index);
             }
         }
 
-        Graph { nodes, edges }
+        Graph { nodes, adjacency }
     }
 
-    fn min_cut_component_sizes(&self) -> Vec<usize> {
-        let mut rng = thread_rng();
-
-        loop {
-            let mut max_node = self.nodes.len();
-            let mut nodes: HashSet<usize> = (0..self.nodes.len()).collect();
-            let mut edges = self.edges.clone();
-            let mut merged_nodes: HashMap<usize, (usize, usize)> = HashMap::new();
-            while nodes.len() > 2 {
-                let (n1, n2) = *edges.choose(&mut rng).unwrap();
-                // Remove nodes and replace with merged node
-                nodes.remove(&n1);
-                nodes.remove(&n2);
-                nodes.insert(max_node);
-                merged_nodes.insert(max_node, (n1, n2));
-                // Remove and replace edges
-                edges.retain(|e| *e != (n1, n2) && *e != (n2, n1));
-                for edge in edges.iter_mut() {
-                    if edge.0 == n1 || edge.0 == n2 {
-                        edge.0 = max_node;
-                    } else if edge.1 == n1 || edge.1 == n2 {
-                        edge.1 = max_node;
-                    }
+    /// The cut with the fewest edges, found deterministically with the Stoer-Wagner algorithm.
+    fn min_cut(&self) -> Cut {
+        let n = self.nodes.len();
+        assert!(n >= 2, "a graph needs two nodes to be cut");
+
+        // Each node stands for the group of original nodes merged into it so far
+        let mut members = (0..n).map(|i| vec![i]).collect::<Vec<_>>();
+        let mut weights = self
+            .adjacency
+            .iter()
+            .map(|neighbours| {
+                let mut weights: HashMap<usize, u32> = HashMap::new();
+                for &neighbour in neighbours {
+                    *weights.entry(neighbour).or_insert(0) += 1;
                 }
-                max_node += 1;
+                weights
+            })
+            .collect::<Vec<_>>();
+        let mut active = (0..n).collect::<Vec<usize>>();
+
+        let mut best: Option<(u32, Vec<usize>)> = None;
+        while active.len() > 1 {
+            // Add nodes in order of how tightly they connect to those already added
+            let mut queue = active
+                .iter()
+                .map(|&i| (i, 0))
+                .collect::<PriorityQueue<_, _>>();
+            let mut order = Vec::with_capacity(active.len());
+            let mut last_weight = 0;
+            while let Some((node, weight)) = queue.pop() {
+                order.push(node);
+                last_weight = weight;
+                for (&neighbour, &w) in weights[node].iter() {
+                    queue.change_priority_by(&neighbour, |p| *p += w);
+                }
+            }
+
+            // The last node alone against everything else is the cut of this phase
+            let (s, t) = (order[order.len() - 2], order[order.len() - 1]);
+            if best
+                .as_ref()
+                .is_none_or(|(weight, _)| last_weight < *weight)
+            {
+                best = Some((last_weight, members[t].clone()));
             }
-            if edges.len() == 6 {
-                let mut components = Vec::new();
-                for component_node in nodes.iter() {
-                    let mut component = HashSet::new();
-                    let mut nodes_to_expand = vec![*component_node];
-                    while let Some(node) = nodes_to_expand.pop() {
-                        if node < self.nodes.len() {
-                            component.insert(node);
-                        } else {
-                            let constituent_nodes = merged_nodes.get(&node).unwrap();
-                            nodes_to_expand.push(constituent_nodes.0);
-                            nodes_to_expand.push(constituent_nodes.1);
-                        }
-                    }
-                    components.push(component);
+
+            // Merge the last node into the one before it
+            let merged = std::mem::take(&mut members[t]);
+            members[s].extend(merged);
+            for (neighbour, w) in std::mem::take(&mut weights[t]) {
+                weights[neighbour].remove(&t);
+                if neighbour != s {
+                    *weights[s].entry(neighbour).or_insert(0) += w;
+                    *weights[neighbour].entry(s).or_insert(0) += w;
                 }
-                return components.iter().map(|c| c.len()).collect();
             }
+            active.retain(|&i| i != t);
+        }
+
+        let (_, side) = best.unwrap();
+        let mut in_side = vec![false; n];
+        for node in side {
+            in_side[node] = true;
+        }
+        self.cut_from_sides(&in_side)
+    }
+
+    /// One run of Karger's contraction, merging the ends of randomly ordered edges until two
+    /// components remain. Each run finds a minimum cut with probability at least `2 / n^2`.
+    #[allow(dead_code)]
+    fn karger_cut<R: Rng>(&self, rng: &mut R) -> Cut {
+        let mut edges = self.edges();
+        edges.shuffle(rng);
+
+        let mut parents = (0..self.nodes.len()).collect::<Vec<usize>>();
+        let mut components = self.nodes.len();
+        for (a, b) in edges {
+            if components == 2 {
+                break;
+            }
+            let (root_a, root_b) = (find_root(&mut parents, a), find_root(&mut parents, b));
+            if root_a != root_b {
+                parents[root_a] = root_b;
+                components -= 1;
+            }
+        }
+
+        let first_root = find_root(&mut parents, 0);
+        let in_side = (0..self.nodes.len())
+            .map(|i| find_root(&mut parents, i) == first_root)
+            .collect::<Vec<bool>>();
+        self.cut_from_sides(&in_side)
+    }
+
+    /// The smallest cut found by `trials` runs of Karger's contraction.
+    #[allow(dead_code)]
+    fn karger_min_cut<R: Rng>(&self, rng: &mut R, trials: usize) -> Cut {
+        (0..trials)
+            .map(|_| self.karger_cut(rng))
+            .min_by_key(|cut| cut.edges.len())
+            .expect("at least one trial")
+    }
+
+    /// Each edge once, with the lower node index first.
+    fn edges(&self) -> Vec<(usize, usize)> {
+        self.adjacency
+            .iter()
+            .enumerate()
+            .flat_map(|(a, neighbours)| neighbours.iter().map(move |&b| (a, b)))
+            .filter(|(a, b)| a < b)
+            .collect()
+    }
+
+    fn cut_from_sides(&self, in_side: &[bool]) -> Cut {
+        let size = in_side.iter().filter(|&&s| s).count();
+        Cut {
+            edges: self
+                .edges()
+                .into_iter()
+                .filter(|&(a, b)| in_side[a] != in_side[b])
+                .collect(),
+            component_sizes: [size, self.nodes.len() - size],
         }
     }
 }
 
+#[allow(dead_code)]
+fn find_root(parents: &mut [usize], mut node: usize) -> usize {
+    while parents[node] != node {
+        parents[node] = parents[parents[node]];
+        node = parents[node];
+    }
+    node
+}
+
 #[test]
 fn test_parse_graph() {
     let input = "jqt: rhn xhk nvd\nrsh: frs pzl lsr\nxhk: hfx\ncmg: qnr nvd lhk bvb\nrhn: xhk bvb hfx\nbvb: xhk hfx\npzl: lsr hfx nvd\nqnr: nvd\nntq: jqt hfx bvb xhk\nnvd: lhk\nlsr: lhk\nrzs: qnr cmg lsr rsh\nfrs: qnr lhk lsr\n";
@@ -120,7 +205,48 @@ fn test_parse_graph() {
 fn test_minimum_cut() {
     let input = "jqt: rhn xhk nvd\nrsh: frs pzl lsr\nxhk: hfx\ncmg: qnr nvd lhk bvb\nrhn: xhk bvb hfx\nbvb: xhk hfx\npzl: lsr hfx nvd\nqnr: nvd\nntq: jqt hfx bvb xhk\nnvd: lhk\nlsr: lhk\nrzs: qnr cmg lsr rsh\nfrs: qnr lhk lsr\n";
     let graph = Graph::parse(input);
-    let mut component_sizes = graph.min_cut_component_sizes();
+    let cut = graph.min_cut();
+    let mut component_sizes = cut.component_sizes;
     component_sizes.sort();
-    assert_eq!(component_sizes, vec![6, 9]);
+    assert_eq!(component_sizes, [6, 9]);
+
+    let mut cut_edges = cut
+        .edges
+        .iter()
+        .map(|&(a, b)| {
+            let mut edge = [graph.nodes[a], graph.nodes[b]];
+            edge.sort();
+            edge
+        })
+        .collect::<Vec<_>>();
+    cut_edges.sort();
+    assert_eq!(
+        cut_edges,
+        vec![["bvb", "cmg"], ["hfx", "pzl"], ["jqt", "nvd"]]
+    );
+}
+
+#[test]
+fn test_karger_min_cut() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let input = "jqt: rhn xhk nvd\nrsh: frs pzl lsr\nxhk: hfx\ncmg: qnr nvd lhk bvb\nrhn: xhk bvb hfx\nbvb: xhk hfx\npzl: lsr hfx nvd\nqnr: nvd\nntq: jqt hfx bvb xhk\nnvd: lhk\nlsr: lhk\nrzs: qnr cmg lsr rsh\nfrs: qnr lhk lsr\n";
+    let graph = Graph::parse(input);
+    let cut = graph.karger_min_cut(&mut StdRng::seed_from_u64(25), 100);
+    assert_eq!(cut.edges.len(), 3);
+    assert_eq!(cut.component_sizes.iter().product::<usize>(), 54);
+
+    // The same seed always gives the same cut
+    let again = graph.karger_min_cut(&mut StdRng::seed_from_u64(25), 100);
+    assert_eq!(cut, again);
+}
+
+#[test]
+fn test_min_cut_of_cycle() {
+    // Any two edges of a cycle cut it, but nothing less will
+    let input = "a: b\nb: c\nc: d\nd: e\ne: a\n";
+    let graph = Graph::parse(input);
+    let cut = graph.min_cut();
+    assert_eq!(cut.edges.len(), 2);
+    assert_eq!(cut.component_sizes.iter().sum::<usize>(), 5);
 }
@@ -1,5 +1,6 @@
 mod days;
 mod inputs;
+mod polygon;
 
 use crate::inputs::get_day_input;
 use days::*;
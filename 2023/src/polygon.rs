@@ -0,0 +1,242 @@
+use num::Integer;
+
+/// A point on the integer lattice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+/// Which way round a polygon's vertices go, with `x` to the right and `y` up.
+///
+/// On a grid where `y` counts rows downwards the picture is mirrored, so the names swap.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Where a point lies relative to a polygon.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Inside,
+    Boundary,
+    Outside,
+}
+
+/// A simple polygon whose vertices all lie on the integer lattice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polygon {
+    vertices: Vec<Point>,
+}
+
+impl Point {
+    pub fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+}
+
+impl Polygon {
+    /// The polygon through `vertices` in order, closing back to the first one. Repeating the
+    /// first vertex at the end is allowed, and collinear vertices are fine.
+    pub fn new(mut vertices: Vec<Point>) -> Self {
+        if vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        assert!(!vertices.is_empty(), "polygon needs at least one vertex");
+        Self { vertices }
+    }
+
+    #[allow(dead_code)]
+    pub fn vertices(&self) -> &[Point] {
+        &self.vertices
+    }
+
+    /// Each edge as a pair of its end points, including the one closing the loop.
+    pub fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        self.vertices
+            .iter()
+            .copied()
+            .zip(self.vertices.iter().copied().cycle().skip(1))
+    }
+
+    /// Twice the signed area from the shoelace formula, positive when counter-clockwise.
+    pub fn twice_signed_area(&self) -> i128 {
+        self.edges()
+            .map(|(a, b)| a.x as i128 * b.y as i128 - b.x as i128 * a.y as i128)
+            .sum()
+    }
+
+    /// Twice the area, which is always a whole number for a lattice polygon.
+    pub fn twice_area(&self) -> u128 {
+        self.twice_signed_area().unsigned_abs()
+    }
+
+    /// The direction the vertices run in, or `None` if the polygon has no area.
+    #[allow(dead_code)]
+    pub fn orientation(&self) -> Option<Orientation> {
+        match self.twice_signed_area().signum() {
+            1 => Some(Orientation::CounterClockwise),
+            -1 => Some(Orientation::Clockwise),
+            _ => None,
+        }
+    }
+
+    /// How many lattice points lie on the edges.
+    pub fn boundary_points(&self) -> u128 {
+        self.edges()
+            .map(|(a, b)| a.x.abs_diff(b.x).gcd(&a.y.abs_diff(b.y)) as u128)
+            .sum()
+    }
+
+    /// How many lattice points lie strictly inside, by Pick's theorem, which is none if the
+    /// polygon has no area.
+    pub fn interior_points(&self) -> u128 {
+        if self.twice_area() == 0 {
+            return 0;
+        }
+        // A = I + B/2 - 1, rearranged to stay in whole numbers
+        (self.twice_area() + 2)
+            .checked_sub(self.boundary_points())
+            .expect("polygon crosses itself")
+            / 2
+    }
+
+    /// How many lattice points lie inside or on the boundary.
+    pub fn enclosed_points(&self) -> u128 {
+        self.interior_points() + self.boundary_points()
+    }
+
+    /// Classifies `point` by counting how many edges a ray towards positive `x` crosses.
+    #[allow(dead_code)]
+    pub fn locate(&self, point: Point) -> Location {
+        let p = (point.x as i128, point.y as i128);
+        let mut inside = false;
+        for (a, b) in self.edges() {
+            let (a, b) = ((a.x as i128, a.y as i128), (b.x as i128, b.y as i128));
+            let cross = (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0);
+            if cross == 0
+                && a.0.min(b.0) <= p.0
+                && p.0 <= a.0.max(b.0)
+                && a.1.min(b.1) <= p.1
+                && p.1 <= a.1.max(b.1)
+            {
+                return Location::Boundary;
+            }
+            // Half-open in y, so a ray through a vertex counts it once
+            if (a.1 > p.1) != (b.1 > p.1) {
+                // The crossing lies to the right when `cross` has the same sign as the edge's dy
+                if (cross > 0) == (b.1 > a.1) {
+                    inside = !inside;
+                }
+            }
+        }
+        if inside {
+            Location::Inside
+        } else {
+            Location::Outside
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, point: Point) -> bool {
+        self.locate(point) != Location::Outside
+    }
+}
+
+#[test]
+fn test_square() {
+    let square = Polygon::new(vec![
+        Point::new(0, 0),
+        Point::new(4, 0),
+        Point::new(4, 4),
+        Point::new(0, 4),
+    ]);
+    assert_eq!(square.twice_signed_area(), 32);
+    assert_eq!(square.orientation(), Some(Orientation::CounterClockwise));
+    assert_eq!(square.boundary_points(), 16);
+    assert_eq!(square.interior_points(), 9);
+    assert_eq!(square.enclosed_points(), 25);
+
+    let mut reversed = square.vertices().to_vec();
+    reversed.reverse();
+    let reversed = Polygon::new(reversed);
+    assert_eq!(reversed.twice_signed_area(), -32);
+    assert_eq!(reversed.orientation(), Some(Orientation::Clockwise));
+    assert_eq!(reversed.interior_points(), 9);
+}
+
+#[test]
+fn test_triangle() {
+    // Diagonal edges only pass through the lattice points a gcd apart
+    let triangle = Polygon::new(vec![
+        Point::new(0, 0),
+        Point::new(6, 0),
+        Point::new(0, 4),
+        Point::new(0, 0),
+    ]);
+    assert_eq!(triangle.vertices().len(), 3);
+    assert_eq!(triangle.twice_area(), 24);
+    assert_eq!(triangle.boundary_points(), 6 + 2 + 4);
+    assert_eq!(triangle.interior_points(), 7);
+
+    let flat = Polygon::new(vec![Point::new(0, 0), Point::new(3, 0)]);
+    assert_eq!(flat.orientation(), None);
+    assert_eq!(flat.boundary_points(), 6);
+    assert_eq!(flat.interior_points(), 0);
+}
+
+#[test]
+fn test_locate_agrees_with_pick() {
+    // An L shape with collinear vertices and a notch, traced clockwise
+    let polygon = Polygon::new(
+        [
+            (0, 0),
+            (0, 6),
+            (2, 6),
+            (2, 3),
+            (3, 3),
+            (3, 5),
+            (6, 5),
+            (6, 2),
+            (4, 0),
+        ]
+        .into_iter()
+        .map(|(x, y)| Point::new(x, y))
+        .collect(),
+    );
+    assert_eq!(polygon.orientation(), Some(Orientation::Clockwise));
+
+    let mut counts = [0; 3];
+    for x in -1..=7 {
+        for y in -1..=7 {
+            let index = match polygon.locate(Point::new(x, y)) {
+                Location::Inside => 0,
+                Location::Boundary => 1,
+                Location::Outside => 2,
+            };
+            counts[index] += 1;
+        }
+    }
+    assert_eq!(counts[0], polygon.interior_points());
+    assert_eq!(counts[1], polygon.boundary_points());
+    assert!(polygon.contains(Point::new(2, 4)));
+    assert!(!polygon.contains(Point::new(5, 0)));
+    assert_eq!(polygon.locate(Point::new(5, 1)), Location::Boundary);
+}
+
+#[test]
+fn test_large_coordinates() {
+    let big = 3_000_000_000_i64;
+    let square = Polygon::new(vec![
+        Point::new(-big, -big),
+        Point::new(big, -big),
+        Point::new(big, big),
+        Point::new(-big, big),
+    ]);
+    let side = 2 * big as u128;
+    assert_eq!(square.twice_area(), 2 * side * side);
+    assert_eq!(square.enclosed_points(), (side + 1) * (side + 1));
+}
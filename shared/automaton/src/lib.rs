@@ -0,0 +1,545 @@
+use std::collections::{HashMap, HashSet};
+
+/// A cell in an `N` dimensional lattice.
+pub type Cell<const N: usize> = [i32; N];
+
+/// Which cells count as neighbours of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbourhood {
+    /// Every cell differing by at most one in each coordinate.
+    Moore,
+    /// Every cell differing by one in exactly one coordinate.
+    VonNeumann,
+    /// The six neighbours of a hexagon in axial coordinates, which are two dimensional.
+    HexAxial,
+}
+
+impl Neighbourhood {
+    /// The offsets from a cell to each of its neighbours.
+    pub fn offsets<const N: usize>(&self) -> Vec<Cell<N>> {
+        match self {
+            Neighbourhood::Moore => (0..3_usize.pow(N as u32))
+                .map(|mut index| {
+                    let mut offset = [0; N];
+                    for coord in offset.iter_mut() {
+                        *coord = (index % 3) as i32 - 1;
+                        index /= 3;
+                    }
+                    offset
+                })
+                .filter(|offset| offset.iter().any(|coord| *coord != 0))
+                .collect(),
+            Neighbourhood::VonNeumann => (0..N)
+                .flat_map(|axis| {
+                    [-1, 1].map(|delta| {
+                        let mut offset = [0; N];
+                        offset[axis] = delta;
+                        offset
+                    })
+                })
+                .collect(),
+            Neighbourhood::HexAxial => {
+                assert_eq!(N, 2, "Hex grids are two dimensional");
+                [[1, 0], [1, -1], [0, -1], [-1, 0], [-1, 1], [0, 1]]
+                    .iter()
+                    .map(|[q, r]| {
+                        let mut offset = [0; N];
+                        offset[0] = *q;
+                        offset[1] = *r;
+                        offset
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Which numbers of live neighbours bring a dead cell to life, and keep a live cell alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    birth: u128,
+    survival: u128,
+}
+
+impl Rule {
+    pub fn new(birth: &[usize], survival: &[usize]) -> Self {
+        let mask = |counts: &[usize]| {
+            counts.iter().fold(0, |mask, count| {
+                assert!(*count < 128, "Too many neighbours for a rule");
+                mask | 1 << count
+            })
+        };
+        Self {
+            birth: mask(birth),
+            survival: mask(survival),
+        }
+    }
+
+    /// Parse a rule in `B3/S23` notation.
+    pub fn parse(input: &str) -> Self {
+        let (birth, survival) = input
+            .trim()
+            .split_once('/')
+            .expect("Rule should be of the form B3/S23");
+        let counts = |part: &str, prefix: char| {
+            part.strip_prefix(prefix)
+                .expect("Rule should be of the form B3/S23")
+                .chars()
+                .map(|ch| ch.to_digit(10).expect("Invalid neighbour count") as usize)
+                .collect::<Vec<usize>>()
+        };
+        Self::new(&counts(birth, 'B'), &counts(survival, 'S'))
+    }
+
+    pub fn next(&self, alive: bool, live_neighbours: usize) -> bool {
+        let mask = if alive { self.survival } else { self.birth };
+        live_neighbours < 128 && mask & (1 << live_neighbours) != 0
+    }
+}
+
+/// Storage for the live cells of an automaton.
+pub trait Grid<const N: usize> {
+    fn from_cells<I: IntoIterator<Item = Cell<N>>>(cells: I) -> Self;
+
+    fn contains(&self, cell: &Cell<N>) -> bool;
+
+    /// All the live cells, in no particular order.
+    fn cells(&self) -> Vec<Cell<N>>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Advance a generation, where `offsets` are the offsets to each neighbour of a cell,
+    /// returning how many cells were born or died.
+    fn step(&mut self, offsets: &[Cell<N>], rule: &Rule) -> usize;
+
+    /// Move live cells where `movement` proposes, returning how many moved.
+    ///
+    /// A cell only moves into an empty cell that no other cell proposes moving into too.
+    fn step_moves<M: Movement<N>>(&mut self, offsets: &[Cell<N>], movement: &M) -> usize
+    where
+        Self: Sized,
+    {
+        let cells = self.cells();
+        let proposals = cells
+            .iter()
+            .map(|cell| {
+                let neighbours = Neighbours {
+                    grid: &*self,
+                    cell: *cell,
+                    offsets,
+                };
+                movement
+                    .propose(&neighbours)
+                    .filter(|proposal| !self.contains(proposal))
+            })
+            .collect::<Vec<Option<Cell<N>>>>();
+
+        let mut proposal_count = HashMap::new();
+        for proposal in proposals.iter().flatten() {
+            *proposal_count.entry(*proposal).or_insert(0) += 1;
+        }
+        let mut moved = 0;
+        let cells = cells
+            .iter()
+            .zip(proposals)
+            .map(|(cell, proposal)| match proposal {
+                Some(proposal) if proposal_count[&proposal] == 1 => {
+                    moved += 1;
+                    proposal
+                }
+                _ => *cell,
+            })
+            .collect::<Vec<Cell<N>>>();
+        *self = Self::from_cells(cells);
+        moved
+    }
+}
+
+/// The cells around one live cell, as seen by a movement rule.
+pub struct Neighbours<'a, const N: usize, G> {
+    grid: &'a G,
+    cell: Cell<N>,
+    offsets: &'a [Cell<N>],
+}
+
+impl<const N: usize, G: Grid<N>> Neighbours<'_, N, G> {
+    /// The live cell whose neighbours these are.
+    pub fn cell(&self) -> Cell<N> {
+        self.cell
+    }
+
+    /// How many cells in the automaton's neighbourhood are live.
+    pub fn count(&self) -> usize {
+        self.count_at(self.offsets)
+    }
+
+    /// How many of the cells at the given offsets are live.
+    pub fn count_at(&self, offsets: &[Cell<N>]) -> usize {
+        offsets
+            .iter()
+            .filter(|offset| self.grid.contains(&add(&self.cell, offset)))
+            .count()
+    }
+}
+
+/// How an automaton's live cells change from one generation to the next.
+pub trait Transition<const N: usize> {
+    /// Advance `grid` a generation, returning how many cells changed.
+    fn apply<G: Grid<N>>(&mut self, grid: &mut G, offsets: &[Cell<N>]) -> usize;
+}
+
+impl<const N: usize> Transition<N> for Rule {
+    fn apply<G: Grid<N>>(&mut self, grid: &mut G, offsets: &[Cell<N>]) -> usize {
+        grid.step(offsets, self)
+    }
+}
+
+/// A rule where live cells move around rather than being born or dying.
+pub trait Movement<const N: usize> {
+    /// Where the cell wants to move to, if anywhere.
+    fn propose<G: Grid<N>>(&self, neighbours: &Neighbours<N, G>) -> Option<Cell<N>>;
+
+    /// Called once every cell has moved, ready for the next generation.
+    fn advance(&mut self) {}
+}
+
+/// Runs a `Movement` as the rule of an automaton.
+#[derive(Debug, Clone)]
+pub struct Moves<M>(pub M);
+
+impl<const N: usize, M: Movement<N>> Transition<N> for Moves<M> {
+    fn apply<G: Grid<N>>(&mut self, grid: &mut G, offsets: &[Cell<N>]) -> usize {
+        let moved = grid.step_moves(offsets, &self.0);
+        self.0.advance();
+        moved
+    }
+}
+
+fn add<const N: usize>(cell: &Cell<N>, offset: &Cell<N>) -> Cell<N> {
+    let mut result = *cell;
+    for (coord, delta) in result.iter_mut().zip(offset) {
+        *coord += delta;
+    }
+    result
+}
+
+/// Only stores the live cells, which suits patterns that are mostly empty space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseGrid<const N: usize> {
+    cells: HashSet<Cell<N>>,
+}
+
+impl<const N: usize> Grid<N> for SparseGrid<N> {
+    fn from_cells<I: IntoIterator<Item = Cell<N>>>(cells: I) -> Self {
+        Self {
+            cells: cells.into_iter().collect(),
+        }
+    }
+
+    fn contains(&self, cell: &Cell<N>) -> bool {
+        self.cells.contains(cell)
+    }
+
+    fn cells(&self) -> Vec<Cell<N>> {
+        self.cells.iter().copied().collect()
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn step(&mut self, offsets: &[Cell<N>], rule: &Rule) -> usize {
+        // Only cells next to a live cell can have any live neighbours
+        let mut live_neighbours = HashMap::new();
+        for cell in &self.cells {
+            for offset in offsets {
+                *live_neighbours.entry(add(cell, offset)).or_insert(0) += 1;
+            }
+        }
+        let mut cells = self
+            .cells
+            .iter()
+            .filter(|cell| rule.next(true, live_neighbours.get(*cell).copied().unwrap_or(0)))
+            .copied()
+            .collect::<HashSet<Cell<N>>>();
+        let deaths = self.cells.len() - cells.len();
+        let births = live_neighbours
+            .into_iter()
+            .filter(|(cell, count)| !self.cells.contains(cell) && rule.next(false, *count))
+            .map(|(cell, _)| cell)
+            .collect::<Vec<Cell<N>>>();
+        let changed = deaths + births.len();
+        cells.extend(births);
+        self.cells = cells;
+        changed
+    }
+}
+
+/// Stores every cell in the bounding box of the live cells, which suits crowded patterns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenseGrid<const N: usize> {
+    origin: Cell<N>,
+    extent: [usize; N],
+    alive: Vec<bool>,
+}
+
+impl<const N: usize> DenseGrid<N> {
+    fn empty(origin: Cell<N>, extent: [usize; N]) -> Self {
+        Self {
+            origin,
+            extent,
+            alive: vec![false; extent.iter().product()],
+        }
+    }
+
+    fn index(&self, cell: &Cell<N>) -> Option<usize> {
+        let mut index = 0;
+        for axis in (0..N).rev() {
+            let coord = cell[axis] - self.origin[axis];
+            if coord < 0 || coord as usize >= self.extent[axis] {
+                return None;
+            }
+            index = index * self.extent[axis] + coord as usize;
+        }
+        Some(index)
+    }
+
+    fn cell(&self, mut index: usize) -> Cell<N> {
+        let mut cell = self.origin;
+        for (coord, length) in cell.iter_mut().zip(self.extent) {
+            *coord += (index % length) as i32;
+            index /= length;
+        }
+        cell
+    }
+}
+
+impl<const N: usize> Grid<N> for DenseGrid<N> {
+    fn from_cells<I: IntoIterator<Item = Cell<N>>>(cells: I) -> Self {
+        let cells = cells.into_iter().collect::<Vec<Cell<N>>>();
+        if cells.is_empty() {
+            return Self::empty([0; N], [0; N]);
+        }
+        let mut origin = cells[0];
+        let mut end = cells[0];
+        for cell in &cells {
+            for axis in 0..N {
+                origin[axis] = origin[axis].min(cell[axis]);
+                end[axis] = end[axis].max(cell[axis]);
+            }
+        }
+        let mut extent = [0; N];
+        for axis in 0..N {
+            extent[axis] = (end[axis] - origin[axis] + 1) as usize;
+        }
+        let mut grid = Self::empty(origin, extent);
+        for cell in &cells {
+            let index = grid.index(cell).unwrap();
+            grid.alive[index] = true;
+        }
+        grid
+    }
+
+    fn contains(&self, cell: &Cell<N>) -> bool {
+        self.index(cell).is_some_and(|index| self.alive[index])
+    }
+
+    fn cells(&self) -> Vec<Cell<N>> {
+        (0..self.alive.len())
+            .filter(|index| self.alive[*index])
+            .map(|index| self.cell(index))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.alive.iter().filter(|alive| **alive).count()
+    }
+
+    fn step(&mut self, offsets: &[Cell<N>], rule: &Rule) -> usize {
+        // Cells can only come to life next to a live cell, so the pattern grows by at most
+        // the reach of the neighbourhood in each direction
+        let reach = offsets
+            .iter()
+            .flatten()
+            .map(|delta| delta.unsigned_abs() as usize)
+            .max()
+            .unwrap_or(0);
+        let mut next = Self::empty(
+            self.origin.map(|coord| coord - reach as i32),
+            self.extent.map(|length| length + 2 * reach),
+        );
+        let mut changed = 0;
+        for index in 0..next.alive.len() {
+            let cell = next.cell(index);
+            let live_neighbours = offsets
+                .iter()
+                .filter(|offset| self.contains(&add(&cell, offset)))
+                .count();
+            let alive = self.contains(&cell);
+            next.alive[index] = rule.next(alive, live_neighbours);
+            if next.alive[index] != alive {
+                changed += 1;
+            }
+        }
+        // Shrink back down to the live cells
+        *self = Self::from_cells(next.cells());
+        changed
+    }
+}
+
+/// A cellular automaton in `N` dimensions, storing its live cells in `G` and changing them
+/// by the rule `R`.
+#[derive(Debug, Clone)]
+pub struct Automaton<const N: usize, G: Grid<N>, R: Transition<N> = Rule> {
+    grid: G,
+    offsets: Vec<Cell<N>>,
+    rule: R,
+}
+
+impl<const N: usize, G: Grid<N>, R: Transition<N>> Automaton<N, G, R> {
+    pub fn new<I: IntoIterator<Item = Cell<N>>>(
+        cells: I,
+        neighbourhood: Neighbourhood,
+        rule: R,
+    ) -> Self {
+        Self {
+            grid: G::from_cells(cells),
+            offsets: neighbourhood.offsets(),
+            rule,
+        }
+    }
+
+    /// Advance a generation, returning how many cells changed.
+    pub fn step(&mut self) -> usize {
+        self.rule.apply(&mut self.grid, &self.offsets)
+    }
+
+    pub fn run(&mut self, generations: usize) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+
+    pub fn live_count(&self) -> usize {
+        self.grid.len()
+    }
+
+    pub fn grid(&self) -> &G {
+        &self.grid
+    }
+
+    pub fn rule(&self) -> &R {
+        &self.rule
+    }
+}
+
+#[test]
+fn test_neighbourhood_offsets() {
+    assert_eq!(Neighbourhood::Moore.offsets::<2>().len(), 8);
+    assert_eq!(Neighbourhood::Moore.offsets::<3>().len(), 26);
+    assert_eq!(Neighbourhood::Moore.offsets::<4>().len(), 80);
+    assert_eq!(
+        Neighbourhood::VonNeumann.offsets::<2>(),
+        vec![[-1, 0], [1, 0], [0, -1], [0, 1]]
+    );
+    assert_eq!(Neighbourhood::VonNeumann.offsets::<3>().len(), 6);
+    assert_eq!(Neighbourhood::HexAxial.offsets::<2>().len(), 6);
+}
+
+#[test]
+fn test_rule() {
+    let rule = Rule::parse("B3/S23");
+    assert_eq!(rule, Rule::new(&[3], &[2, 3]));
+    assert!(rule.next(false, 3));
+    assert!(!rule.next(false, 2));
+    assert!(rule.next(true, 2));
+    assert!(!rule.next(true, 4));
+    assert!(!rule.next(true, 200));
+}
+
+#[test]
+fn test_automaton_backends() {
+    // A glider moves one cell diagonally every four generations
+    let glider = [[1, 0], [2, 1], [0, 2], [1, 2], [2, 2]];
+    let mut sparse =
+        Automaton::<2, SparseGrid<2>>::new(glider, Neighbourhood::Moore, Rule::parse("B3/S23"));
+    let mut dense =
+        Automaton::<2, DenseGrid<2>>::new(glider, Neighbourhood::Moore, Rule::parse("B3/S23"));
+    sparse.run(4);
+    dense.run(4);
+    let mut expected = glider.map(|[x, y]| [x + 1, y + 1]).to_vec();
+    expected.sort();
+    let mut cells = sparse.grid().cells();
+    cells.sort();
+    assert_eq!(cells, expected);
+    let mut cells = dense.grid().cells();
+    cells.sort();
+    assert_eq!(cells, expected);
+
+    // With the von Neumann neighbourhood, cells with exactly one live neighbour are born and
+    // nothing dies, so a single cell grows along the axes faster than between them
+    let mut sparse = Automaton::<2, SparseGrid<2>>::new(
+        [[0, 0]],
+        Neighbourhood::VonNeumann,
+        Rule::parse("B1/S01234"),
+    );
+    let mut dense = Automaton::<2, DenseGrid<2>>::new(
+        [[0, 0]],
+        Neighbourhood::VonNeumann,
+        Rule::parse("B1/S01234"),
+    );
+    sparse.run(3);
+    dense.run(3);
+    let mut sparse_cells = sparse.grid().cells();
+    sparse_cells.sort();
+    let mut dense_cells = dense.grid().cells();
+    dense_cells.sort();
+    assert_eq!(sparse_cells, dense_cells);
+    assert!(dense.grid().contains(&[0, 3]));
+    assert!(!dense.grid().contains(&[0, 4]));
+}
+
+#[test]
+fn test_step_changes() {
+    // A blinker flips between a row and a column, changing four cells each time
+    let mut blinker = Automaton::<2, SparseGrid<2>>::new(
+        [[0, 1], [1, 1], [2, 1]],
+        Neighbourhood::Moore,
+        Rule::parse("B3/S23"),
+    );
+    assert_eq!(blinker.step(), 4);
+    assert!(blinker.grid().contains(&[1, 0]));
+    let mut block = Automaton::<2, DenseGrid<2>>::new(
+        [[0, 0], [0, 1], [1, 0], [1, 1]],
+        Neighbourhood::Moore,
+        Rule::parse("B3/S23"),
+    );
+    assert_eq!(block.step(), 0);
+}
+
+#[test]
+fn test_moves() {
+    /// Every cell heads one step towards the line `x = 2`.
+    struct Drift;
+
+    impl Movement<2> for Drift {
+        fn propose<G: Grid<2>>(&self, neighbours: &Neighbours<2, G>) -> Option<Cell<2>> {
+            let [x, y] = neighbours.cell();
+            (x != 2).then_some([x + (2 - x).signum(), y])
+        }
+    }
+
+    // The cell behind is blocked by the cell in front, and the two cells heading for [2, 1]
+    // from either side would collide, so both stay put
+    let cells = [[0, 0], [1, 0], [1, 1], [3, 1], [2, 2]];
+    let mut automaton =
+        Automaton::<2, SparseGrid<2>, _>::new(cells, Neighbourhood::Moore, Moves(Drift));
+    assert_eq!(automaton.step(), 1);
+    let mut cells = automaton.grid().cells();
+    cells.sort();
+    assert_eq!(cells, vec![[0, 0], [1, 1], [2, 0], [2, 2], [3, 1]]);
+}
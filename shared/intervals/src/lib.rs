@@ -0,0 +1,365 @@
+/// An inclusive range of integers, never empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Interval {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Interval {
+    pub fn new(start: i64, end: i64) -> Self {
+        assert!(start <= end, "empty interval {start}..={end}");
+        Self { start, end }
+    }
+
+    /// The interval from `start` to `end`, or `None` if that would be empty.
+    pub fn checked(start: i64, end: i64) -> Option<Self> {
+        (start <= end).then_some(Self { start, end })
+    }
+
+    /// How many integers the interval covers.
+    #[allow(clippy::len_without_is_empty)] // Intervals are never empty
+    pub fn len(&self) -> u64 {
+        self.end.abs_diff(self.start) + 1
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    pub fn intersection(&self, other: &Interval) -> Option<Self> {
+        Self::checked(self.start.max(other.start), self.end.min(other.end))
+    }
+
+    /// The parts of this interval below and above `other`, either of which may be missing.
+    pub fn difference(&self, other: &Interval) -> impl Iterator<Item = Self> {
+        let below = Self::checked(self.start, self.end.min(other.start.saturating_sub(1)));
+        let above = Self::checked(self.start.max(other.end.saturating_add(1)), self.end);
+        [below, above].into_iter().flatten()
+    }
+
+    /// Splits into the values less than `value` and the values at least `value`.
+    pub fn split_at(&self, value: i64) -> (Option<Self>, Option<Self>) {
+        (
+            Self::checked(self.start, self.end.min(value.saturating_sub(1))),
+            Self::checked(self.start.max(value), self.end),
+        )
+    }
+}
+
+/// A set of integers stored as sorted intervals that neither overlap nor touch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    intervals: Vec<Interval>,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    /// How many integers the set covers.
+    pub fn len(&self) -> u64 {
+        self.intervals.iter().map(Interval::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        let i = self.intervals.partition_point(|i| i.end < value);
+        self.intervals.get(i).is_some_and(|i| i.contains(value))
+    }
+
+    pub fn insert(&mut self, interval: Interval) {
+        // Everything in first..last overlaps or touches the new interval, so merges into it
+        let first = self
+            .intervals
+            .partition_point(|i| i.end.saturating_add(1) < interval.start);
+        let last = self
+            .intervals
+            .partition_point(|i| i.start <= interval.end.saturating_add(1));
+        let mut merged = interval;
+        if first < last {
+            merged.start = merged.start.min(self.intervals[first].start);
+            merged.end = merged.end.max(self.intervals[last - 1].end);
+        }
+        self.intervals.splice(first..last, [merged]);
+    }
+
+    pub fn remove(&mut self, interval: Interval) {
+        let first = self.intervals.partition_point(|i| i.end < interval.start);
+        let last = self.intervals.partition_point(|i| i.start <= interval.end);
+        let remaining = self.intervals[first..last]
+            .iter()
+            .flat_map(|i| i.difference(&interval))
+            .collect::<Vec<_>>();
+        self.intervals.splice(first..last, remaining);
+    }
+
+    pub fn union(&self, other: &IntervalSet) -> Self {
+        let mut result = self.clone();
+        for interval in &other.intervals {
+            result.insert(*interval);
+        }
+        result
+    }
+
+    pub fn intersection(&self, other: &IntervalSet) -> Self {
+        let mut intervals = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (self.intervals[i], other.intervals[j]);
+            if let Some(overlap) = a.intersection(&b) {
+                intervals.push(overlap);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self { intervals }
+    }
+
+    pub fn difference(&self, other: &IntervalSet) -> Self {
+        let mut result = self.clone();
+        for interval in &other.intervals {
+            result.remove(*interval);
+        }
+        result
+    }
+}
+
+impl From<Interval> for IntervalSet {
+    fn from(interval: Interval) -> Self {
+        Self {
+            intervals: vec![interval],
+        }
+    }
+}
+
+impl FromIterator<Interval> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = Interval>>(iter: T) -> Self {
+        let mut set = Self::new();
+        for interval in iter {
+            set.insert(interval);
+        }
+        set
+    }
+}
+
+/// An axis-aligned box of integer points, with an interval along each axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IntervalBox<const N: usize> {
+    pub axes: [Interval; N],
+}
+
+impl<const N: usize> IntervalBox<N> {
+    pub fn new(axes: [Interval; N]) -> Self {
+        Self { axes }
+    }
+
+    /// How many integer points the box covers.
+    pub fn volume(&self) -> u64 {
+        self.axes.iter().map(Interval::len).product()
+    }
+
+    pub fn contains(&self, point: [i64; N]) -> bool {
+        self.axes
+            .iter()
+            .zip(point)
+            .all(|(axis, p)| axis.contains(p))
+    }
+
+    pub fn intersection(&self, other: &IntervalBox<N>) -> Option<Self> {
+        let mut axes = self.axes;
+        for (axis, other_axis) in axes.iter_mut().zip(other.axes.iter()) {
+            *axis = axis.intersection(other_axis)?;
+        }
+        Some(Self { axes })
+    }
+
+    /// This box with `other` cut out, as at most `2 * N` disjoint boxes.
+    pub fn difference(&self, other: &IntervalBox<N>) -> Vec<Self> {
+        let Some(overlap) = self.intersection(other) else {
+            return vec![*self];
+        };
+        // Slice off the parts outside the overlap one axis at a time, shrinking what's left
+        let mut pieces = Vec::new();
+        let mut remaining = *self;
+        for axis in 0..N {
+            for interval in remaining.axes[axis].difference(&overlap.axes[axis]) {
+                let mut piece = remaining;
+                piece.axes[axis] = interval;
+                pieces.push(piece);
+            }
+            remaining.axes[axis] = overlap.axes[axis];
+        }
+        pieces
+    }
+
+    /// Splits along `axis` into the points less than `value` and the points at least `value`.
+    pub fn split_at(&self, axis: usize, value: i64) -> (Option<Self>, Option<Self>) {
+        let (below, above) = self.axes[axis].split_at(value);
+        let with_axis = |interval| {
+            let mut axes = self.axes;
+            axes[axis] = interval;
+            Self { axes }
+        };
+        (below.map(with_axis), above.map(with_axis))
+    }
+}
+
+/// A set of integer points stored as pairwise disjoint boxes.
+#[derive(Debug, Clone, Default)]
+pub struct BoxSet<const N: usize> {
+    boxes: Vec<IntervalBox<N>>,
+}
+
+impl<const N: usize> BoxSet<N> {
+    pub fn new() -> Self {
+        Self { boxes: Vec::new() }
+    }
+
+    pub fn boxes(&self) -> &[IntervalBox<N>] {
+        &self.boxes
+    }
+
+    pub fn volume(&self) -> u64 {
+        self.boxes.iter().map(IntervalBox::volume).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    pub fn contains(&self, point: [i64; N]) -> bool {
+        self.boxes.iter().any(|b| b.contains(point))
+    }
+
+    pub fn insert(&mut self, new_box: IntervalBox<N>) {
+        self.remove(&new_box);
+        self.boxes.push(new_box);
+    }
+
+    pub fn remove(&mut self, old_box: &IntervalBox<N>) {
+        self.boxes = self
+            .boxes
+            .iter()
+            .flat_map(|b| b.difference(old_box))
+            .collect();
+    }
+
+    pub fn union(&self, other: &BoxSet<N>) -> Self {
+        let mut result = self.clone();
+        for b in &other.boxes {
+            result.insert(*b);
+        }
+        result
+    }
+
+    pub fn intersection(&self, other: &BoxSet<N>) -> Self {
+        // Overlaps of disjoint boxes with disjoint boxes are themselves disjoint
+        let boxes = self
+            .boxes
+            .iter()
+            .flat_map(|a| other.boxes.iter().filter_map(|b| a.intersection(b)))
+            .collect();
+        Self { boxes }
+    }
+
+    pub fn difference(&self, other: &BoxSet<N>) -> Self {
+        let mut result = self.clone();
+        for b in &other.boxes {
+            result.remove(b);
+        }
+        result
+    }
+}
+
+impl<const N: usize> FromIterator<IntervalBox<N>> for BoxSet<N> {
+    fn from_iter<T: IntoIterator<Item = IntervalBox<N>>>(iter: T) -> Self {
+        let mut set = Self::new();
+        for b in iter {
+            set.insert(b);
+        }
+        set
+    }
+}
+
+#[test]
+fn test_interval_set() {
+    let mut set = [(-2, 0), (-1, 3), (5, 7)]
+        .into_iter()
+        .map(|(start, end)| Interval::new(start, end))
+        .collect::<IntervalSet>();
+    assert_eq!(set.intervals(), [Interval::new(-2, 3), Interval::new(5, 7)]);
+    assert_eq!(set.len(), 9);
+
+    // Touching intervals merge
+    set.insert(Interval::new(4, 4));
+    assert_eq!(set.intervals(), [Interval::new(-2, 7)]);
+
+    set.remove(Interval::new(0, 1));
+    assert_eq!(
+        set.intervals(),
+        [Interval::new(-2, -1), Interval::new(2, 7)]
+    );
+    assert!(set.contains(-1) && !set.contains(0) && set.contains(7));
+
+    let other = IntervalSet::from(Interval::new(-1, 3));
+    assert_eq!(
+        set.intersection(&other).intervals(),
+        [Interval::new(-1, -1), Interval::new(2, 3)]
+    );
+    assert_eq!(
+        set.difference(&other).intervals(),
+        [Interval::new(-2, -2), Interval::new(4, 7)]
+    );
+    assert_eq!(set.union(&other).intervals(), [Interval::new(-2, 7)]);
+}
+
+#[test]
+fn test_box_difference() {
+    let cube = |min: i64, max: i64| IntervalBox::new([Interval::new(min, max); 3]);
+    let outer = cube(0, 9);
+    let inner = cube(3, 5);
+    let pieces = outer.difference(&inner);
+    assert_eq!(pieces.len(), 6);
+    assert_eq!(
+        pieces.iter().map(IntervalBox::volume).sum::<u64>(),
+        outer.volume() - inner.volume()
+    );
+    for (i, a) in pieces.iter().enumerate() {
+        assert!(a.intersection(&inner).is_none());
+        for b in &pieces[i + 1..] {
+            assert!(a.intersection(b).is_none());
+        }
+    }
+    assert_eq!(inner.difference(&outer), vec![]);
+    assert_eq!(outer.difference(&cube(20, 30)), vec![outer]);
+}
+
+#[test]
+fn test_box_set() {
+    let cube = |min: i64, max: i64| IntervalBox::new([Interval::new(min, max); 3]);
+    let mut set = BoxSet::new();
+    set.insert(cube(10, 12));
+    set.insert(cube(11, 13));
+    assert_eq!(set.volume(), 27 + 19);
+    set.remove(&cube(9, 11));
+    assert_eq!(set.volume(), 38);
+    set.insert(cube(10, 10));
+    assert_eq!(set.volume(), 39);
+    assert!(set.contains([10, 10, 10]) && !set.contains([11, 11, 11]));
+
+    let other = [cube(0, 11)].into_iter().collect::<BoxSet<3>>();
+    assert_eq!(set.intersection(&other).volume(), 1);
+    assert_eq!(set.difference(&other).volume(), 38);
+    assert_eq!(set.union(&other).volume(), 12 * 12 * 12 + 38);
+}
@@ -0,0 +1,381 @@
+/// Letters in the small font, which is 6 pixels tall.
+const SMALL_FONT: [(char, [&str; 6]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Letters in the large font, which is 10 pixels tall.
+const LARGE_FONT: [(char, &str); 15] = [
+    (
+        'A',
+        "\
+..##..
+.#..#.
+#....#
+#....#
+#....#
+######
+#....#
+#....#
+#....#
+#....#",
+    ),
+    (
+        'B',
+        "\
+#####.
+#....#
+#....#
+#....#
+#####.
+#....#
+#....#
+#....#
+#....#
+#####.",
+    ),
+    (
+        'C',
+        "\
+.####.
+#....#
+#.....
+#.....
+#.....
+#.....
+#.....
+#.....
+#....#
+.####.",
+    ),
+    (
+        'E',
+        "\
+######
+#.....
+#.....
+#.....
+#####.
+#.....
+#.....
+#.....
+#.....
+######",
+    ),
+    (
+        'F',
+        "\
+######
+#.....
+#.....
+#.....
+#####.
+#.....
+#.....
+#.....
+#.....
+#.....",
+    ),
+    (
+        'G',
+        "\
+.####.
+#....#
+#.....
+#.....
+#.....
+#..###
+#....#
+#....#
+#...##
+.###.#",
+    ),
+    (
+        'H',
+        "\
+#....#
+#....#
+#....#
+#....#
+######
+#....#
+#....#
+#....#
+#....#
+#....#",
+    ),
+    (
+        'J',
+        "\
+...###
+....#.
+....#.
+....#.
+....#.
+....#.
+....#.
+#...#.
+#...#.
+.###..",
+    ),
+    (
+        'K',
+        "\
+#....#
+#...#.
+#..#..
+#.#...
+##....
+##....
+#.#...
+#..#..
+#...#.
+#....#",
+    ),
+    (
+        'L',
+        "\
+#.....
+#.....
+#.....
+#.....
+#.....
+#.....
+#.....
+#.....
+#.....
+######",
+    ),
+    (
+        'N',
+        "\
+#....#
+##...#
+##...#
+#.#..#
+#.#..#
+#..#.#
+#..#.#
+#...##
+#...##
+#....#",
+    ),
+    (
+        'P',
+        "\
+#####.
+#....#
+#....#
+#....#
+#####.
+#.....
+#.....
+#.....
+#.....
+#.....",
+    ),
+    (
+        'R',
+        "\
+#####.
+#....#
+#....#
+#....#
+#####.
+#..#..
+#...#.
+#...#.
+#....#
+#....#",
+    ),
+    (
+        'X',
+        "\
+#....#
+#....#
+.#..#.
+.#..#.
+..##..
+..##..
+.#..#.
+.#..#.
+#....#
+#....#",
+    ),
+    (
+        'Z',
+        "\
+######
+.....#
+.....#
+....#.
+...#..
+..#...
+.#....
+#.....
+#.....
+######",
+    ),
+];
+
+/// Parse a screen drawn with `#` for lit pixels and anything else for unlit pixels.
+fn parse_pixels(screen: &str) -> Vec<Vec<bool>> {
+    screen
+        .lines()
+        .map(|line| line.chars().map(|ch| ch == '#').collect())
+        .collect()
+}
+
+fn parse_glyph(glyph: &[&str]) -> Vec<Vec<bool>> {
+    parse_pixels(&glyph.join("\n"))
+}
+
+/// Recognise the letters drawn on a screen of `#` and `.` characters.
+pub fn recognise_str(screen: &str) -> Option<String> {
+    recognise(&parse_pixels(screen))
+}
+
+/// Recognise the letters drawn in a grid of pixels, in either the 6 or 10 pixel tall font.
+///
+/// Blank rows around the letters are ignored, and letters must be separated by at least
+/// one blank column. Returns `None` if any letter isn't recognised.
+pub fn recognise(pixels: &[Vec<bool>]) -> Option<String> {
+    let lit_rows = pixels
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| row.iter().any(|p| *p))
+        .map(|(i, _)| i)
+        .collect::<Vec<usize>>();
+    let (first_row, last_row) = (*lit_rows.first()?, *lit_rows.last()?);
+    let rows = &pixels[first_row..=last_row];
+
+    let font: Vec<(char, Vec<&str>)> = match rows.len() {
+        6 => SMALL_FONT.iter().map(|(c, g)| (*c, g.to_vec())).collect(),
+        10 => LARGE_FONT
+            .iter()
+            .map(|(c, g)| (*c, g.lines().collect::<Vec<&str>>()))
+            .collect(),
+        _ => return None,
+    };
+    let glyphs = font
+        .into_iter()
+        .map(|(letter, glyph)| {
+            // Some glyphs are padded with blank columns, which we need to trim off
+            let glyph = parse_glyph(&glyph);
+            let lit_cols = (0..glyph[0].len())
+                .filter(|col| glyph.iter().any(|row| row[*col]))
+                .collect::<Vec<usize>>();
+            let (first_col, last_col) = (lit_cols[0], lit_cols[lit_cols.len() - 1]);
+            let glyph = glyph
+                .into_iter()
+                .map(|row| row[first_col..=last_col].to_vec())
+                .collect::<Vec<Vec<bool>>>();
+            (letter, glyph)
+        })
+        .collect::<Vec<(char, Vec<Vec<bool>>)>>();
+
+    let cols = rows.iter().map(|row| row.len()).max()?;
+    let pixel = |row: usize, col: usize| rows[row].get(col).copied().unwrap_or(false);
+    let column_lit = |col: usize| (0..rows.len()).any(|row| pixel(row, col));
+
+    let mut letters = String::new();
+    let mut col = 0;
+    while col < cols {
+        if !column_lit(col) {
+            col += 1;
+            continue;
+        }
+        let start = col;
+        while col < cols && column_lit(col) {
+            col += 1;
+        }
+        let width = col - start;
+        let (letter, _) = glyphs.iter().find(|(_, glyph)| {
+            glyph[0].len() == width
+                && (0..rows.len())
+                    .all(|row| (0..width).all(|c| glyph[row][c] == pixel(row, start + c)))
+        })?;
+        letters.push(*letter);
+    }
+    Some(letters)
+}
+
+#[test]
+fn test_recognise_small_font() {
+    let letters = SMALL_FONT
+        .iter()
+        .map(|(letter, _)| *letter)
+        .collect::<String>();
+    let glyphs = SMALL_FONT
+        .iter()
+        .map(|(_, glyph)| parse_glyph(glyph))
+        .collect::<Vec<Vec<Vec<bool>>>>();
+    let pixels = (0..6)
+        .map(|row| {
+            glyphs
+                .iter()
+                .flat_map(|glyph| glyph[row].iter().copied().chain([false]))
+                .collect()
+        })
+        .collect::<Vec<Vec<bool>>>();
+    assert_eq!(recognise(&pixels), Some(letters));
+}
+
+#[test]
+fn test_recognise_large_font() {
+    let letters = LARGE_FONT
+        .iter()
+        .map(|(letter, _)| *letter)
+        .collect::<String>();
+    let glyphs = LARGE_FONT
+        .iter()
+        .map(|(_, glyph)| parse_pixels(glyph))
+        .collect::<Vec<Vec<Vec<bool>>>>();
+    // Surround the letters with blank rows, and space them out further
+    let mut pixels = vec![vec![false; 5]];
+    pixels.extend((0..10).map(|row| {
+        glyphs
+            .iter()
+            .flat_map(|glyph| glyph[row].iter().copied().chain([false, false]))
+            .collect()
+    }));
+    pixels.push(Vec::new());
+    assert_eq!(recognise(&pixels), Some(letters));
+}
+
+#[test]
+fn test_recognise_str() {
+    let screen = "\
+#..#.###..####.####.###..#..#.####.####.
+#..#.#..#....#.#....#..#.#..#....#.#....
+####.#..#...#..###..#..#.#..#...#..###..
+#..#.###...#...#....###..#..#..#...#....
+#..#.#....#....#....#....#..#.#....#....
+#..#.#....####.#....#.....##..####.#....
+";
+    assert_eq!(recognise_str(screen), Some("HPZFPUZF".to_string()));
+
+    // Unknown letter shape
+    let screen = "#\n#\n#\n#\n#\n#\n";
+    assert_eq!(recognise_str(screen), None);
+
+    // Unknown font size
+    let screen = "#\n#\n#\n";
+    assert_eq!(recognise_str(screen), None);
+}